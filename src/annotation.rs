@@ -0,0 +1,653 @@
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    sync::Arc,
+};
+
+use eframe::egui::{emath::Rot2, Color32, Pos2, Rect, Vec2};
+use xcap::image::RgbaImage;
+
+use crate::{
+    arrow::{self, ArrowStyle},
+    callout::{self, CalloutShape},
+    magnifier,
+    redact::RedactStyle,
+    spotlight::{SpotArea, SpotEffect},
+    sticker::Sticker,
+    stroke::{self, Piece, StrokeStyle},
+    text::{Fonts, TextLayout, TextStyle},
+};
+
+/// How far the rotate handle sits above the top edge, relative to the height.
+const ROTATE_HANDLE: f32 = 0.3;
+/// Step that rotation snaps to while Shift is held: 15°.
+pub const ROTATE_SNAP: f32 = PI / 12.0;
+/// Smallest side a sticker can be resized to.
+const MIN_STICKER: f32 = 4.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    Arrow {
+        start: Pos2,
+        end: Pos2,
+        /// Control point of a curved arrow; straight if `None`.
+        control: Option<Pos2>,
+        color: Color32,
+        thickness: f32,
+        style: ArrowStyle,
+        stroke: StrokeStyle,
+    },
+    /// A box around `rect`, turned by `angle` radians around its centre.
+    Rect {
+        rect: Rect,
+        angle: f32,
+        color: Color32,
+        thickness: f32,
+        stroke: StrokeStyle,
+    },
+    /// Text starting at `pos`, turned by `angle` radians around the centre of its bounds.
+    Text {
+        pos: Pos2,
+        text: String,
+        color: Color32,
+        size: f32,
+        style: TextStyle,
+        angle: f32,
+    },
+    /// Text wrapped inside a box or bubble, with a tail pointing at `tail`. The body is
+    /// turned by `angle` radians around its centre; the tail still points at `tail`.
+    Callout {
+        rect: Rect,
+        tail: Pos2,
+        text: String,
+        shape: CalloutShape,
+        /// Colour of the text and border.
+        color: Color32,
+        fill: Color32,
+        size: f32,
+        angle: f32,
+    },
+    /// Another image layered on top, scaled to fill `rect`.
+    Image { rect: Rect, image: Arc<RgbaImage> },
+    /// Darkens or desaturates everything underneath it outside `areas`.
+    Spotlight {
+        areas: Vec<SpotArea>,
+        effect: SpotEffect,
+        /// From 0 (no effect) to 1 (black or fully grey).
+        strength: f32,
+    },
+    /// An enlarged copy of `source` in the image, in a bordered inset around `centre`.
+    Magnifier {
+        source: Rect,
+        centre: Pos2,
+        zoom: f32,
+        color: Color32,
+        thickness: f32,
+        /// A line from the source to the inset.
+        connector: bool,
+    },
+    /// A stamp, emoji or image from the sticker folder, filling `rect` turned by `angle`
+    /// radians around its centre.
+    Sticker {
+        sticker: Sticker,
+        rect: Rect,
+        angle: f32,
+        color: Color32,
+    },
+    /// Hides what is underneath `rect`, like an email address or a token.
+    Redact { rect: Rect, style: RedactStyle },
+}
+
+/// `p` turned by `angle` radians around `centre`.
+pub fn rotate(p: Pos2, centre: Pos2, angle: f32) -> Pos2 {
+    centre + Rot2::from_angle(angle) * (p - centre)
+}
+
+/// The corners of `rect` turned by `angle` radians around its centre, clockwise from the
+/// top-left.
+pub fn corners(rect: Rect, angle: f32) -> [Pos2; 4] {
+    [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ]
+    .map(|p| rotate(p, rect.center(), angle))
+}
+
+/// `angle` brought into `-π..π`.
+fn normalize(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+/// Where the handle that rotates `rect` by its centre is, above its top edge.
+pub fn rotate_handle(rect: Rect, angle: f32) -> Pos2 {
+    rotate(
+        rect.center_top() - Vec2::new(0.0, rect.height() * ROTATE_HANDLE),
+        rect.center(),
+        angle,
+    )
+}
+
+impl Annotation {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Arrow { .. } => "↗ Arrow".to_string(),
+            Self::Rect { .. } => "⬜ Box".to_string(),
+            Self::Text { text, .. } => format!("T \"{}\"", text.replace('\n', " ")),
+            Self::Callout { text, .. } => format!("💬 \"{}\"", text.replace('\n', " ")),
+            Self::Image { image, .. } => format!("🖼 Image {}×{}", image.width(), image.height()),
+            Self::Spotlight { areas, .. } => format!("🔦 Spotlight ({})", areas.len()),
+            Self::Magnifier { zoom, .. } => format!("🔎 Magnifier {zoom:.1}×"),
+            Self::Sticker { sticker, .. } => format!("🏷 {}", sticker.label()),
+            Self::Redact { style, .. } => format!("⬛ Redaction ({})", style.name()),
+        }
+    }
+
+    pub fn hit_test(&self, p: Pos2, threshold: f32) -> bool {
+        match self {
+            Self::Arrow {
+                start,
+                end,
+                control,
+                ..
+            } => {
+                arrow::distance_to_polyline(&arrow::shaft_points(*start, *end, *control), p)
+                    < threshold
+            }
+            Self::Rect { rect, angle, .. } => {
+                let p = rotate(p, rect.center(), -angle);
+                rect.expand(threshold).contains(p)
+                    && (!rect.shrink(threshold).contains(p) || rect.contains(p))
+            }
+            Self::Text {
+                pos,
+                text,
+                size,
+                style,
+                angle,
+                ..
+            } => {
+                let bounds = text_bounds(*pos, text, *size, style);
+                bounds
+                    .expand(threshold)
+                    .contains(rotate(p, bounds.center(), -angle))
+            }
+            Self::Callout {
+                rect,
+                tail,
+                shape,
+                angle,
+                ..
+            } => {
+                rect.expand(threshold)
+                    .contains(rotate(p, rect.center(), -angle))
+                    || callout::contains_point(
+                        &callout::geometry(*rect, *shape, *tail, *angle).outline,
+                        p,
+                    )
+            }
+            Self::Image { rect, .. } | Self::Redact { rect, .. } => rect.contains(p),
+            Self::Spotlight { areas, .. } => areas
+                .iter()
+                .any(|area| area.signed_distance(p).abs() < threshold),
+            Self::Magnifier {
+                source,
+                centre,
+                zoom,
+                ..
+            } => {
+                magnifier::inset(*source, *centre, *zoom)
+                    .expand(threshold)
+                    .contains(p)
+                    || (source.expand(threshold).contains(p)
+                        && !source.shrink(threshold).contains(p))
+            }
+            Self::Sticker { rect, angle, .. } => {
+                rect.expand(threshold)
+                    .contains(rotate(p, rect.center(), -angle))
+            }
+        }
+    }
+
+    pub fn translate(&mut self, delta: Vec2) {
+        match self {
+            Self::Arrow {
+                start,
+                end,
+                control,
+                ..
+            } => {
+                *start += delta;
+                *end += delta;
+                if let Some(control) = control {
+                    *control += delta;
+                }
+            }
+            Self::Rect { rect, .. }
+            | Self::Image { rect, .. }
+            | Self::Sticker { rect, .. }
+            | Self::Redact { rect, .. } => {
+                *rect = rect.translate(delta);
+            }
+            Self::Spotlight { areas, .. } => {
+                for area in areas {
+                    area.rect = area.rect.translate(delta);
+                }
+            }
+            Self::Text { pos, .. } => *pos += delta,
+            Self::Magnifier { source, centre, .. } => {
+                *source = source.translate(delta);
+                *centre += delta;
+            }
+            Self::Callout { rect, tail, .. } => {
+                *rect = rect.translate(delta);
+                *tail += delta;
+            }
+        }
+    }
+
+    /// Points that can be dragged on their own once the annotation is selected.
+    pub fn handles(&self) -> Vec<Pos2> {
+        match self {
+            Self::Arrow {
+                start,
+                end,
+                control,
+                ..
+            } => vec![
+                *start,
+                *end,
+                control.unwrap_or_else(|| start.lerp(*end, 0.5)),
+            ],
+            // Boxes, text and callouts turn by a handle above their top edge.
+            Self::Rect { .. } | Self::Text { .. } => self
+                .frame()
+                .map_or_else(Vec::new, |(r, angle)| vec![rotate_handle(r, angle)]),
+            Self::Callout {
+                rect, tail, angle, ..
+            } => vec![*tail, rotate_handle(*rect, *angle)],
+            // Each area moves by its centre and is resized by its bottom-right corner.
+            Self::Spotlight { areas, .. } => areas
+                .iter()
+                .flat_map(|area| [area.rect.center(), area.rect.max])
+                .collect(),
+            // The same for the source, and the inset's corner sets the zoom.
+            Self::Magnifier {
+                source,
+                centre,
+                zoom,
+                ..
+            } => vec![
+                source.center(),
+                source.max,
+                *centre,
+                magnifier::inset(*source, *centre, *zoom).max,
+            ],
+            // The bottom-right corner resizes, and a handle above the top edge turns it.
+            Self::Sticker { rect, angle, .. } => vec![
+                rotate(rect.max, rect.center(), *angle),
+                rotate_handle(*rect, *angle),
+            ],
+            // Redactions are resized by their bottom-right corner.
+            Self::Redact { rect, .. } => vec![rect.max],
+            Self::Image { .. } => Vec::new(),
+        }
+    }
+
+    /// The upright rect that an annotation which can be rotated is turned around the
+    /// centre of, and the angle in radians.
+    pub fn frame(&self) -> Option<(Rect, f32)> {
+        match self {
+            Self::Rect { rect, angle, .. }
+            | Self::Callout { rect, angle, .. }
+            | Self::Sticker { rect, angle, .. } => Some((*rect, *angle)),
+            Self::Text {
+                pos,
+                text,
+                size,
+                style,
+                angle,
+                ..
+            } => Some((text_bounds(*pos, text, *size, style), *angle)),
+            _ => None,
+        }
+    }
+
+    pub fn angle_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            Self::Rect { angle, .. }
+            | Self::Text { angle, .. }
+            | Self::Callout { angle, .. }
+            | Self::Sticker { angle, .. } => Some(angle),
+            _ => None,
+        }
+    }
+
+    /// The smallest upright rect around the annotation's shape, leaving out stroke widths
+    /// and callout tails.
+    pub fn bounds(&self) -> Rect {
+        match self {
+            Self::Rect { .. } | Self::Text { .. } | Self::Callout { .. } | Self::Sticker { .. } => {
+                self.frame().map_or(Rect::NOTHING, |(rect, angle)| {
+                    Rect::from_points(&corners(rect, angle))
+                })
+            }
+            Self::Arrow {
+                start,
+                end,
+                control,
+                ..
+            } => Rect::from_points(&arrow::shaft_points(*start, *end, *control)),
+            Self::Spotlight { areas, .. } => areas
+                .iter()
+                .map(|area| area.rect)
+                .reduce(Rect::union)
+                .unwrap_or(Rect::NOTHING),
+            Self::Magnifier {
+                source,
+                centre,
+                zoom,
+                ..
+            } => source.union(magnifier::inset(*source, *centre, *zoom)),
+            Self::Image { rect, .. } | Self::Redact { rect, .. } => *rect,
+        }
+    }
+
+    /// Whether the handle at `index` in [`Self::handles`] turns the annotation. It is always
+    /// the last one.
+    pub fn is_rotate_handle(&self, index: usize) -> bool {
+        self.frame().is_some() && index + 1 == self.handles().len()
+    }
+
+    /// Turns the annotation so its rotate handle points at `p`, in steps of [`ROTATE_SNAP`]
+    /// if `snap` is set.
+    pub fn rotate_towards(&mut self, p: Pos2, snap: bool) {
+        let Some((rect, _)) = self.frame() else {
+            return;
+        };
+        let mut turned = (p - rect.center()).angle() + FRAC_PI_2;
+        if snap {
+            turned = (turned / ROTATE_SNAP).round() * ROTATE_SNAP;
+        }
+        if let Some(angle) = self.angle_mut() {
+            *angle = normalize(turned);
+        }
+    }
+
+    /// Moves the handle at `index` in [`Self::handles`] by `delta`. Dragging the middle of a
+    /// straight arrow bends it. Rotate handles are dragged with [`Self::rotate_towards`]
+    /// instead, so snapping doesn't swallow small movements.
+    pub fn move_handle(&mut self, index: usize, delta: Vec2) {
+        match (self, index) {
+            (Self::Arrow { start, .. }, 0) => *start += delta,
+            (Self::Arrow { end, .. }, 1) => *end += delta,
+            (
+                Self::Arrow {
+                    start,
+                    end,
+                    control,
+                    ..
+                },
+                2,
+            ) => *control = Some(control.unwrap_or_else(|| start.lerp(*end, 0.5)) + delta),
+            (Self::Callout { tail, .. }, 0) => *tail += delta,
+            (Self::Spotlight { areas, .. }, i) => {
+                if let Some(area) = areas.get_mut(i / 2) {
+                    area.rect = if i % 2 == 0 {
+                        area.rect.translate(delta)
+                    } else {
+                        Rect::from_two_pos(area.rect.min, area.rect.max + delta)
+                    };
+                }
+            }
+            (Self::Magnifier { source, .. }, 0) => *source = source.translate(delta),
+            (Self::Magnifier { source, .. }, 1) => {
+                *source = Rect::from_two_pos(source.min, source.max + delta);
+            }
+            (Self::Magnifier { centre, .. }, 2) => *centre += delta,
+            (
+                Self::Magnifier {
+                    source,
+                    centre,
+                    zoom,
+                    ..
+                },
+                3,
+            ) => {
+                let corner = magnifier::inset(*source, *centre, *zoom).max + delta;
+                *zoom = magnifier::zoom_for_corner(*source, *centre, corner);
+            }
+            (Self::Redact { rect, .. }, 0) => {
+                *rect = Rect::from_two_pos(rect.min, rect.max + delta);
+            }
+            // Resizing keeps the aspect ratio and the opposite corner in place.
+            (Self::Sticker { rect, angle, .. }, 0) => {
+                let size = rect.size();
+                let grown = size + Rot2::from_angle(-*angle) * delta;
+                let factor = (grown.dot(size) / size.length_sq().max(f32::EPSILON))
+                    .max(MIN_STICKER / size.min_elem().max(f32::EPSILON));
+                let fixed = rotate(rect.min, rect.center(), *angle);
+                let size = size * factor;
+                *rect =
+                    Rect::from_center_size(fixed + Rot2::from_angle(*angle) * (size / 2.0), size);
+            }
+            _ => {}
+        }
+    }
+
+    /// What a stroked shape is made of, the stroke width, and its colour with the opacity
+    /// applied.
+    pub fn stroke_pieces(&self) -> Option<(Vec<Piece>, f32, Color32)> {
+        match self {
+            Self::Arrow {
+                start,
+                end,
+                control,
+                color,
+                thickness,
+                style,
+                stroke,
+            } => Some((
+                arrow::pieces((*start, *end, *control), *thickness, *style, stroke),
+                *thickness,
+                stroke.color(*color),
+            )),
+            Self::Rect {
+                rect,
+                angle,
+                color,
+                thickness,
+                stroke,
+            } => {
+                // Boxes are stroked on the outside of their rect.
+                let corners = corners(rect.expand(thickness / 2.0), *angle);
+                Some((
+                    stroke::pieces(&corners, true, *thickness, stroke),
+                    *thickness,
+                    stroke.color(*color),
+                ))
+            }
+            Self::Magnifier {
+                source,
+                centre,
+                zoom,
+                color,
+                thickness,
+                connector,
+            } => Some((
+                magnifier::pieces(
+                    *source,
+                    magnifier::inset(*source, *centre, *zoom),
+                    *thickness,
+                    *connector,
+                ),
+                *thickness,
+                *color,
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn text_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Self::Text { text, .. } | Self::Callout { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The layout of the annotation's text and its top-left, if it has text.
+    pub fn text_layout(&self) -> Option<(Pos2, TextLayout)> {
+        match self {
+            Self::Text {
+                pos,
+                text,
+                size,
+                style,
+                ..
+            } => Some((*pos, Fonts::get().layout(text, *size, style))),
+            Self::Callout {
+                rect,
+                shape,
+                text,
+                size,
+                ..
+            } => Some(callout::layout_text(*rect, *shape, text, *size)),
+            _ => None,
+        }
+    }
+
+    /// Moves every point through `map` and scales stroke widths and text by `scale`.
+    pub fn transform(&mut self, map: impl Fn(Pos2) -> Pos2, scale: f32) {
+        // Mirroring turns the other way. Text and stickers keep their shape, so they turn
+        // along with the image; boxes keep their angle, as their width and height swap
+        // instead.
+        let o = map(Pos2::ZERO);
+        let (x, y) = (map(Pos2::new(1.0, 0.0)) - o, map(Pos2::new(0.0, 1.0)) - o);
+        if x.x * y.y - x.y * y.x < 0.0 {
+            if let Some(angle) = self.angle_mut() {
+                *angle = -*angle;
+            }
+        } else if let Self::Text { angle, .. } | Self::Sticker { angle, .. } = self {
+            *angle = normalize(*angle + x.angle());
+        }
+        match self {
+            Self::Arrow {
+                start,
+                end,
+                control,
+                thickness,
+                ..
+            } => {
+                *start = map(*start);
+                *end = map(*end);
+                *control = control.map(&map);
+                *thickness *= scale;
+            }
+            Self::Rect {
+                rect, thickness, ..
+            } => {
+                *rect = Rect::from_two_pos(map(rect.min), map(rect.max));
+                *thickness *= scale;
+            }
+            Self::Text {
+                pos,
+                text,
+                size,
+                style,
+                ..
+            } => {
+                // Text isn't mirrored, so it follows its centre rather than its top-left corner.
+                let centre = map(text_rect(*pos, text, *size, style).center());
+                *size *= scale;
+                *pos = centre - text_rect(Pos2::ZERO, text, *size, style).size() / 2.0;
+            }
+            Self::Callout {
+                rect, tail, size, ..
+            } => {
+                *rect = Rect::from_two_pos(map(rect.min), map(rect.max));
+                *tail = map(*tail);
+                *size *= scale;
+            }
+            Self::Image { rect, .. } | Self::Sticker { rect, .. } => {
+                // Like text, images and stickers aren't mirrored themselves.
+                *rect = Rect::from_center_size(map(rect.center()), rect.size() * scale);
+            }
+            Self::Spotlight { areas, .. } => {
+                for area in areas {
+                    area.rect = Rect::from_two_pos(map(area.rect.min), map(area.rect.max));
+                }
+            }
+            Self::Redact { rect, .. } => *rect = Rect::from_two_pos(map(rect.min), map(rect.max)),
+            // Source and inset scale together, so the zoom stays the same.
+            Self::Magnifier {
+                source,
+                centre,
+                thickness,
+                ..
+            } => {
+                *source = Rect::from_two_pos(map(source.min), map(source.max));
+                *centre = map(*centre);
+                *thickness *= scale;
+            }
+        }
+    }
+}
+
+/// Bounds of the lines of a text annotation.
+fn text_rect(pos: Pos2, text: &str, size: f32, style: &TextStyle) -> Rect {
+    Fonts::get().layout(text, size, style).rect(pos)
+}
+
+/// Bounds of everything a text annotation paints, including its box or outline.
+pub fn text_bounds(pos: Pos2, text: &str, size: f32, style: &TextStyle) -> Rect {
+    style.outer_rect(text_rect(pos, text, size, style), size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sticker::Stamp, transform::Transform};
+
+    /// Whether `a` and `b` are the same corners, in any order.
+    fn same_corners(a: [Pos2; 4], b: [Pos2; 4]) -> bool {
+        a.iter().all(|p| b.iter().any(|q| p.distance(*q) < 0.01))
+    }
+
+    fn corners_after(ann: &Annotation, transform: Transform, size: Vec2) -> bool {
+        let (rect, angle) = ann.frame().unwrap();
+        let expected = corners(rect, angle).map(|p| transform.map(p, size));
+        let mut turned = ann.clone();
+        turned.transform(|p| transform.map(p, size), transform.scale(size));
+        let (rect, angle) = turned.frame().unwrap();
+        same_corners(corners(rect, angle), expected)
+    }
+
+    #[test]
+    fn tilted_annotations_turn_with_the_image() {
+        let size = Vec2::new(400.0, 300.0);
+        let sticker = Annotation::Sticker {
+            sticker: Sticker::Stamp(Stamp::Check),
+            rect: Rect::from_min_size(Pos2::new(50.0, 40.0), Vec2::new(80.0, 30.0)),
+            angle: 0.3,
+            color: Color32::RED,
+        };
+        let rect = Annotation::Rect {
+            rect: Rect::from_min_size(Pos2::new(200.0, 100.0), Vec2::new(60.0, 20.0)),
+            angle: -0.5,
+            color: Color32::RED,
+            thickness: 2.0,
+            stroke: StrokeStyle::default(),
+        };
+        for transform in [
+            Transform::Rotate90,
+            Transform::Rotate180,
+            Transform::Rotate270,
+            Transform::FlipHorizontal,
+            Transform::FlipVertical,
+        ] {
+            for ann in [&sticker, &rect] {
+                assert!(corners_after(ann, transform, size), "{ann:?} {transform:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use eframe::egui::{Pos2, Vec2};
+
+use crate::stroke::{self, Piece, StrokeStyle};
+
+/// Length and half-width of a head, relative to the stroke thickness.
+const HEAD_SIZE: f32 = 3.0;
+/// Width of a tapered shaft at its start and end, relative to the stroke thickness.
+const TAPER: (f32, f32) = (0.25, 1.5);
+const CURVE_STEPS: u8 = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrowHead {
+    /// Two barbs.
+    #[default]
+    Open,
+    /// A solid triangle.
+    Filled,
+}
+
+impl ArrowHead {
+    pub const ALL: [Self; 2] = [Self::Open, Self::Filled];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Open => "Open head",
+            Self::Filled => "Filled head",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrowShaft {
+    #[default]
+    Uniform,
+    /// Thin at the start and thick at the end, like a brush stroke. Always has filled heads.
+    Tapered,
+}
+
+impl ArrowShaft {
+    pub const ALL: [Self; 2] = [Self::Uniform, Self::Tapered];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Uniform => "Uniform",
+            Self::Tapered => "Tapered",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArrowStyle {
+    pub head: ArrowHead,
+    pub shaft: ArrowShaft,
+    /// Heads at both ends.
+    pub double: bool,
+}
+
+/// The arrow's centre line: straight, or a quadratic curve bending towards `control`.
+pub fn shaft_points(start: Pos2, end: Pos2, control: Option<Pos2>) -> Vec<Pos2> {
+    let Some(c) = control else {
+        return vec![start, end];
+    };
+    (0..=CURVE_STEPS)
+        .map(|i| {
+            let t = f32::from(i) / f32::from(CURVE_STEPS);
+            let mt = 1.0 - t;
+            (start.to_vec2() * mt * mt + c.to_vec2() * 2.0 * mt * t + end.to_vec2() * t * t)
+                .to_pos2()
+        })
+        .collect()
+}
+
+/// Shortens `points` by `length` at its end, measured along the line.
+fn trim_end(points: &mut Vec<Pos2>, mut length: f32) {
+    while let [.., a, b] = points[..] {
+        let d = a.distance(b);
+        if d > length {
+            if let Some(last) = points.last_mut() {
+                *last = b + (a - b) * (length / d);
+            }
+            return;
+        }
+        length -= d;
+        points.pop();
+    }
+}
+
+/// The outline of the head at the end of `points`, pointing along its last segment, whether
+/// it is filled, and how much of the shaft it covers.
+fn head(points: &[Pos2], size: f32, head: ArrowHead) -> Option<(Vec<Pos2>, bool, f32)> {
+    let [.., from, tip] = points[..] else {
+        return None;
+    };
+    let dir = (tip - from).normalized();
+    if !dir.is_finite() {
+        return None;
+    }
+    let normal = Vec2::new(-dir.y, dir.x);
+    let base = tip - dir * size;
+    Some(match head {
+        ArrowHead::Open => (
+            vec![base + normal * size, tip, base - normal * size],
+            false,
+            0.0,
+        ),
+        ArrowHead::Filled => (
+            vec![tip, base + normal * size * 0.7, base - normal * size * 0.7],
+            true,
+            size * 0.8,
+        ),
+    })
+}
+
+/// A band around `points` whose width goes from `widths.0` to `widths.1` along its length.
+fn band(points: &[Pos2], widths: (f32, f32)) -> Piece {
+    let lengths: Vec<f32> = points
+        .iter()
+        .scan((0.0, points.first().copied()), |(len, prev), &p| {
+            *len += prev.map_or(0.0, |q| q.distance(p));
+            *prev = Some(p);
+            Some(*len)
+        })
+        .collect();
+    let total = lengths.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+    let mut sections = Vec::with_capacity(points.len());
+    for (i, &p) in points.iter().enumerate() {
+        let (a, b) = (
+            points[i.saturating_sub(1)],
+            points[(i + 1).min(points.len() - 1)],
+        );
+        let dir = (b - a).normalized();
+        let normal = if dir.is_finite() {
+            dir.rot90()
+        } else {
+            Vec2::ZERO
+        };
+        let width = widths.0 + (widths.1 - widths.0) * lengths[i] / total;
+        sections.push((p, normal * width / 2.0));
+    }
+    Piece::Ribbon {
+        sections,
+        closed: false,
+    }
+}
+
+/// Everything that makes up an arrow, shared by the canvas and export so both draw the
+/// same shapes. Heads are never dashed, and tapered shafts ignore the stroke style.
+pub fn pieces(
+    (start, end, control): (Pos2, Pos2, Option<Pos2>),
+    thickness: f32,
+    style: ArrowStyle,
+    stroke: &StrokeStyle,
+) -> Vec<Piece> {
+    let mut shaft = shaft_points(start, end, control);
+    let (head_style, head_size) = match style.shaft {
+        ArrowShaft::Tapered => (ArrowHead::Filled, thickness * TAPER.1 * HEAD_SIZE / 1.5),
+        ArrowShaft::Uniform => (style.head, thickness * HEAD_SIZE),
+    };
+    let take_head = |shaft: &mut Vec<Pos2>| {
+        let (outline, filled, covered) = head(shaft, head_size, head_style)?;
+        trim_end(shaft, covered);
+        Some((outline, filled))
+    };
+    let mut heads: Vec<_> = take_head(&mut shaft).into_iter().collect();
+    if style.double {
+        shaft.reverse();
+        heads.extend(take_head(&mut shaft));
+        shaft.reverse();
+    }
+    let mut pieces = match style.shaft {
+        ArrowShaft::Uniform => stroke::pieces(&shaft, false, thickness, stroke),
+        ArrowShaft::Tapered => vec![band(&shaft, (thickness * TAPER.0, thickness * TAPER.1))],
+    };
+    for (outline, filled) in heads {
+        if filled {
+            pieces.push(Piece::Fill(outline));
+        } else {
+            pieces.extend(stroke::pieces(&outline, false, thickness, &stroke.solid()));
+        }
+    }
+    pieces
+}
+
+/// The distance from `p` to the nearest point of the polyline `points`.
+pub fn distance_to_polyline(points: &[Pos2], p: Pos2) -> f32 {
+    if let [only] = points {
+        return p.distance(*only);
+    }
+    points
+        .windows(2)
+        .map(|w| {
+            let (a, b) = (w[0], w[1]);
+            let ab = b - a;
+            let t = ((p - a).dot(ab) / ab.length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+            p.distance(a + ab * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
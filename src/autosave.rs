@@ -0,0 +1,181 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use xcap::image::{self, RgbaImage};
+
+use crate::{
+    capture::CaptureInfo,
+    settings::{AutoSave, AutoSaveTrigger},
+};
+
+/// Placeholders understood in file name templates, with a description for the settings.
+pub const PLACEHOLDERS: [(&str, &str); 6] = [
+    ("{date}", "date, e.g. 2024-05-31"),
+    ("{time}", "time, e.g. 14-03-59"),
+    ("{window_title}", "title of the captured window"),
+    ("{app}", "app of the captured window"),
+    ("{counter}", "number counting up with every file"),
+    ("{monitor}", "name of the captured monitor"),
+];
+
+/// Characters Windows doesn't allow in file names.
+const ILLEGAL_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+/// Device names Windows reserves, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+/// Long window titles are cut so the whole path stays well within `MAX_PATH`.
+const MAX_NAME_LEN: usize = 120;
+const FALLBACK_NAME: &str = "Screenshot";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AutoSaveError {
+    #[error("no auto-save folder is set")]
+    NoFolder,
+    #[error("failed to create the auto-save folder: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to save image: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Local date and time, to the second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl Timestamp {
+    #[cfg(windows)]
+    pub fn now() -> Self {
+        // SAFETY: `GetLocalTime` has no preconditions.
+        let t = unsafe { windows::Win32::System::SystemInformation::GetLocalTime() };
+        Self {
+            year: t.wYear,
+            month: t.wMonth as u8,
+            day: t.wDay as u8,
+            hour: t.wHour as u8,
+            minute: t.wMinute as u8,
+            second: t.wSecond as u8,
+        }
+    }
+
+    /// UTC, as there is no time zone database to go by outside Windows.
+    #[cfg(not(windows))]
+    pub fn now() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let (days, rest) = ((secs / 86_400).cast_signed(), secs % 86_400);
+        // Days since 1970-01-01 to a civil date, shifted so years start in March.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        Self {
+            year: (yoe + era * 400 + i64::from(month <= 2)) as u16,
+            month: month as u8,
+            day: (doy - (153 * mp + 2) / 5 + 1) as u8,
+            hour: (rest / 3600) as u8,
+            minute: (rest / 60 % 60) as u8,
+            second: (rest % 60) as u8,
+        }
+    }
+
+    fn date(self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    fn time(self) -> String {
+        format!("{:02}-{:02}-{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+/// Makes `name` usable as a Windows file name: illegal characters become `_`, the stray
+/// separators left by empty placeholders are trimmed and reserved device names get a suffix.
+pub fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if ILLEGAL_CHARS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    // Windows also drops trailing dots and spaces by itself.
+    let mut name = name
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, '.' | '_' | '-'))
+        .to_string();
+    if name.is_empty() {
+        return FALLBACK_NAME.to_string();
+    }
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        name.push('_');
+    }
+    name
+}
+
+/// `dir/stem.extension`, or `dir/stem (2).extension` and so on if that file exists.
+pub fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let path = dir.join(format!("{stem}.{extension}"));
+    if !path.exists() {
+        return path;
+    }
+    let mut n = 2;
+    loop {
+        let path = dir.join(format!("{stem} ({n}).{extension}"));
+        if !path.exists() {
+            return path;
+        }
+        n += 1;
+    }
+}
+
+impl AutoSave {
+    pub fn applies(&self, trigger: AutoSaveTrigger) -> bool {
+        self.enabled && self.trigger == trigger
+    }
+
+    /// The file name, without extension, that the template gives for `info`.
+    pub fn file_name(&self, info: &CaptureInfo, time: Timestamp, counter: u32) -> String {
+        let name = self
+            .template
+            .replace("{date}", &time.date())
+            .replace("{time}", &time.time())
+            .replace("{window_title}", &info.window_title)
+            .replace("{app}", &info.app)
+            .replace("{counter}", &format!("{counter:04}"))
+            .replace("{monitor}", &info.monitor);
+        sanitize(&name)
+    }
+
+    /// Writes `img` to a new file in the folder, advancing the counter if the template uses
+    /// it, and returns its path.
+    pub fn save(&mut self, img: &RgbaImage, info: &CaptureInfo) -> Result<PathBuf, AutoSaveError> {
+        if self.folder.as_os_str().is_empty() {
+            return Err(AutoSaveError::NoFolder);
+        }
+        fs::create_dir_all(&self.folder)?;
+        if self.template.contains("{counter}") {
+            self.counter += 1;
+        }
+        let name = self.file_name(info, Timestamp::now(), self.counter);
+        let path = unique_path(&self.folder, &name, "png");
+        img.save(&path)?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,463 @@
+use eframe::egui::{Pos2, Rect};
+use xcap::image::RgbaImage;
+
+use crate::qr;
+
+/// Brightness below which a pixel counts as dark, out of 255.
+const DARK: f32 = 128.0;
+/// Every how many rows linear barcodes are looked for.
+const ROW_STEP: u32 = 2;
+/// Fewest pixel rows a linear barcode must be found in, so a row of text that happens to
+/// decode isn't taken for one.
+const MIN_ROWS: f32 = 4.0;
+/// Largest difference, in modules summed over a symbol, between the bars and spaces that
+/// were read and the pattern they are taken for.
+const MAX_MISMATCH: f32 = 1.0;
+
+/// Widths of the spaces and bars of the digits on the left of EAN-13 codes with odd parity.
+/// Even parity reverses them, and digits on the right start with a bar instead.
+const EAN_DIGITS: [[u8; 4]; 10] = [
+    [3, 2, 1, 1],
+    [2, 2, 2, 1],
+    [2, 1, 2, 2],
+    [1, 4, 1, 1],
+    [1, 1, 3, 2],
+    [1, 2, 3, 1],
+    [1, 1, 1, 4],
+    [1, 3, 1, 2],
+    [1, 2, 1, 3],
+    [3, 1, 1, 2],
+];
+/// The parity, odd (L) or even (G), of the six digits on the left of EAN-13 codes, for each
+/// first digit.
+const EAN_PARITY: [&[u8; 6]; 10] = [
+    b"LLLLLL", b"LLGLGG", b"LLGGLG", b"LLGGGL", b"LGLLGG", b"LGGLLG", b"LGGGLL", b"LGLGLG",
+    b"LGLGGL", b"LGGLGL",
+];
+/// Widths of the bars and spaces of the Code 128 symbols, in modules, by value. 103 to 105
+/// start the code in code set A, B or C, and 106 stops it, followed by a final bar two
+/// modules wide.
+const CODE128: [&[u8; 6]; 107] = [
+    b"212222", b"222122", b"222221", b"121223", b"121322", b"131222", b"122213", b"122312",
+    b"132212", b"221213", b"221312", b"231212", b"112232", b"122132", b"122231", b"113222",
+    b"123122", b"123221", b"223211", b"221132", b"221231", b"213212", b"223112", b"312131",
+    b"311222", b"321122", b"321221", b"312212", b"322112", b"322211", b"212123", b"212321",
+    b"232121", b"111323", b"131123", b"131321", b"112313", b"132113", b"132311", b"211313",
+    b"231113", b"231311", b"112133", b"112331", b"132131", b"113123", b"113321", b"133121",
+    b"313121", b"211331", b"231131", b"213113", b"213311", b"213131", b"311123", b"311321",
+    b"331121", b"312113", b"312311", b"332111", b"314111", b"221411", b"431111", b"111224",
+    b"111422", b"121124", b"121421", b"141122", b"141221", b"112214", b"112412", b"122114",
+    b"122411", b"142112", b"142211", b"241211", b"221114", b"413111", b"241112", b"134111",
+    b"111242", b"121142", b"121241", b"114212", b"124112", b"124211", b"411212", b"421112",
+    b"421211", b"212141", b"214121", b"412121", b"111143", b"111341", b"131141", b"114113",
+    b"114311", b"411113", b"411311", b"113141", b"114131", b"311141", b"411131", b"211412",
+    b"211214", b"211232", b"233111",
+];
+const CODE128_STOP: u8 = 106;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbology {
+    Qr,
+    Ean13,
+    UpcA,
+    Code128,
+}
+
+impl Symbology {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Qr => "QR code",
+            Self::Ean13 => "EAN-13",
+            Self::UpcA => "UPC-A",
+            Self::Code128 => "Code 128",
+        }
+    }
+}
+
+/// A QR code or barcode found in an image, what it says and the area it covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    pub symbology: Symbology,
+    pub text: String,
+    pub rect: Rect,
+}
+
+/// An image reduced to dark and light pixels.
+pub struct Binary {
+    pub width: u32,
+    pub height: u32,
+    dark: Vec<bool>,
+}
+
+/// A stretch of dark or light pixels along a row or column.
+#[derive(Debug, Clone, Copy)]
+pub struct Run {
+    pub dark: bool,
+    pub start: u32,
+    pub len: u32,
+}
+
+impl Run {
+    pub fn end(self) -> u32 {
+        self.start + self.len
+    }
+}
+
+impl Binary {
+    pub fn new(img: &RgbaImage) -> Self {
+        Self {
+            width: img.width(),
+            height: img.height(),
+            dark: img
+                .pixels()
+                .map(|p| {
+                    let [r, g, b, _] = p.0;
+                    0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b) < DARK
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the pixel at `(x, y)` is dark. Outside the image everything is light.
+    pub fn is_dark(&self, x: i64, y: i64) -> bool {
+        x >= 0
+            && y >= 0
+            && x < i64::from(self.width)
+            && y < i64::from(self.height)
+            && self.dark[(y * i64::from(self.width) + x) as usize]
+    }
+
+    pub fn row_runs(&self, y: u32) -> Vec<Run> {
+        let row = &self.dark[(y * self.width) as usize..((y + 1) * self.width) as usize];
+        let mut runs: Vec<Run> = Vec::new();
+        for (x, &dark) in (0..).zip(row) {
+            match runs.last_mut() {
+                Some(run) if run.dark == dark => run.len += 1,
+                _ => runs.push(Run {
+                    dark,
+                    start: x,
+                    len: 1,
+                }),
+            }
+        }
+        runs
+    }
+}
+
+/// The QR codes, EAN-13 and UPC-A barcodes and Code 128 barcodes in a screenshot, decoded.
+/// Barcodes have to be upright and QR codes upright or turned by quarter turns, dark on light.
+pub fn scan(img: &RgbaImage) -> Vec<Code> {
+    let binary = Binary::new(img);
+    let mut codes = qr::find(&binary);
+    codes.extend(linear_codes(&binary));
+    codes
+}
+
+/// The barcodes made of vertical bars: rows that decode to the same text at the same place
+/// are one barcode.
+fn linear_codes(img: &Binary) -> Vec<Code> {
+    let mut codes: Vec<Code> = Vec::new();
+    for y in (0..img.height).step_by(ROW_STEP as usize) {
+        let runs = img.row_runs(y);
+        let mut i = 1;
+        while i < runs.len() {
+            let decoded = if runs[i].dark {
+                ean13(&runs[i..]).or_else(|| code128(&runs[i..]))
+            } else {
+                None
+            };
+            let Some((symbology, text, count)) = decoded else {
+                i += 1;
+                continue;
+            };
+            let (from, to) = (runs[i].start as f32, runs[i + count - 1].end() as f32);
+            let row = Rect::from_min_max(Pos2::new(from, y as f32), Pos2::new(to, (y + 1) as f32));
+            let same = codes.iter_mut().find(|c| {
+                c.symbology == symbology
+                    && c.text == text
+                    && c.rect.max.y + ROW_STEP as f32 >= row.min.y
+                    && c.rect.x_range().intersects(row.x_range())
+            });
+            match same {
+                Some(code) => code.rect = code.rect.union(row),
+                None => codes.push(Code {
+                    symbology,
+                    text,
+                    rect: row,
+                }),
+            }
+            i += count;
+        }
+    }
+    codes.retain(|c| c.rect.height() >= MIN_ROWS);
+    codes
+}
+
+/// How far the widths of `runs` are from `pattern`, in modules, once they are scaled to add
+/// up to the same number of modules.
+fn mismatch(runs: &[Run], pattern: &[u8]) -> f32 {
+    let total: u32 = runs.iter().map(|r| r.len).sum();
+    let modules: u8 = pattern.iter().sum();
+    let scale = f32::from(modules) / total as f32;
+    runs.iter()
+        .zip(pattern)
+        .map(|(r, &p)| (r.len as f32 * scale - f32::from(p)).abs())
+        .sum()
+}
+
+/// The EAN-13 code whose start guard is at the first of `runs`, the text, with UPC-A codes
+/// told apart, and how many runs it covers.
+fn ean13(runs: &[Run]) -> Option<(Symbology, String, usize)> {
+    const RUNS: usize = 59;
+    let runs = runs.get(..RUNS)?;
+    let guards = [&runs[..3], &runs[27..32], &runs[56..]];
+    if guards
+        .iter()
+        .any(|guard| mismatch(guard, &[1; 5][..guard.len()]) > MAX_MISMATCH)
+    {
+        return None;
+    }
+    let best = |digit: &[Run], reversed: bool| {
+        (0u8..10)
+            .map(|d| {
+                let mut pattern = EAN_DIGITS[usize::from(d)];
+                if reversed {
+                    pattern.reverse();
+                }
+                (d, mismatch(digit, &pattern))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    };
+    let mut digits = Vec::with_capacity(13);
+    let mut parity = [0; 6];
+    for (digit, parity) in runs[3..27].chunks(4).zip(&mut parity) {
+        let (odd, even) = (best(digit, false)?, best(digit, true)?);
+        let (d, error) = if odd.1 <= even.1 { odd } else { even };
+        if error > MAX_MISMATCH {
+            return None;
+        }
+        *parity = if even.1 < odd.1 { b'G' } else { b'L' };
+        digits.push(d);
+    }
+    for digit in runs[32..56].chunks(4) {
+        match best(digit, false)? {
+            (d, error) if error <= MAX_MISMATCH => digits.push(d),
+            _ => return None,
+        }
+    }
+    let first = EAN_PARITY.iter().position(|&&p| p == parity)?;
+    digits.insert(0, first as u8);
+    let sum: u32 = digits[..12]
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| u32::from(d) * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    if (10 - sum % 10) % 10 != u32::from(digits[12]) {
+        return None;
+    }
+    let text: String = digits.iter().map(|d| char::from(b'0' + d)).collect();
+    Some(match text.strip_prefix('0') {
+        Some(upc) => (Symbology::UpcA, upc.to_owned(), RUNS),
+        None => (Symbology::Ean13, text, RUNS),
+    })
+}
+
+/// The widths of a Code 128 symbol, from [`CODE128`].
+fn code128_pattern(value: u8) -> [u8; 6] {
+    CODE128[usize::from(value)].map(|w| w - b'0')
+}
+
+/// The value among `values` of the Code 128 symbol made of `runs`, six of them.
+fn code128_symbol(runs: &[Run], values: impl Iterator<Item = u8>) -> Option<u8> {
+    values
+        .map(|v| (v, mismatch(runs, &code128_pattern(v))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|&(_, error)| error <= MAX_MISMATCH)
+        .map(|(v, _)| v)
+}
+
+/// The Code 128 barcode whose start symbol is at the first of `runs`, its text and how many
+/// runs it covers.
+fn code128(runs: &[Run]) -> Option<(Symbology, String, usize)> {
+    let width = |symbol: &[Run]| symbol.iter().map(|r| r.len).sum::<u32>() as f32;
+    let start_width = width(runs.get(..6)?);
+    let mut values = vec![code128_symbol(&runs[..6], 103..=105)?];
+    let mut i = 6;
+    loop {
+        let symbol = runs.get(i..i + 6)?;
+        // Symbols all have the same width, so one that doesn't isn't part of this code.
+        if (width(symbol) / start_width - 1.0).abs() > 0.25 {
+            return None;
+        }
+        let value = code128_symbol(symbol, 0..=CODE128_STOP)?;
+        i += 6;
+        if value == CODE128_STOP {
+            break;
+        }
+        values.push(value);
+    }
+    // The final bar of the stop symbol.
+    let bar = runs.get(i)?;
+    if !bar.dark || (bar.len as f32 / (start_width / 11.0) - 2.0).abs() > 1.0 {
+        return None;
+    }
+    let check = values.pop()?;
+    if values.len() < 2 {
+        return None;
+    }
+    let sum: usize = usize::from(values[0])
+        + values[1..]
+            .iter()
+            .enumerate()
+            .map(|(k, &v)| (k + 1) * usize::from(v))
+            .sum::<usize>();
+    if sum % 103 != usize::from(check) {
+        return None;
+    }
+    Some((Symbology::Code128, code128_text(&values), i + 1))
+}
+
+/// The text encoded by Code 128 symbol values, the first being the start symbol. Function
+/// characters are left out.
+fn code128_text(values: &[u8]) -> String {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Set {
+        A,
+        B,
+        C,
+    }
+    let mut set = match values[0] {
+        103 => Set::A,
+        104 => Set::B,
+        _ => Set::C,
+    };
+    let mut shifted = false;
+    let mut text = String::new();
+    for &v in &values[1..] {
+        let current = match (shifted, set) {
+            (true, Set::A) => Set::B,
+            (true, Set::B) => Set::A,
+            _ => set,
+        };
+        shifted = false;
+        match (current, v) {
+            (Set::C, 0..=99) => text.extend([v / 10, v % 10].map(|d| char::from(b'0' + d))),
+            (Set::A, 0..=63) | (Set::B, 0..=95) => text.push(char::from(v + 32)),
+            (Set::A, 64..=95) => text.push(char::from(v - 64)),
+            (Set::A | Set::B, 98) => shifted = true,
+            (Set::A | Set::B, 99) => set = Set::C,
+            (Set::A | Set::C, 100) => set = Set::B,
+            (Set::B | Set::C, 101) => set = Set::A,
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use xcap::image::Rgba;
+
+    use super::*;
+
+    /// Bars and spaces `widths` modules wide, starting with a bar, `module` pixels a module,
+    /// with quiet zones of ten modules.
+    fn render(widths: &[u8], module: u32) -> RgbaImage {
+        let mut dark = vec![false; 10];
+        for (i, &w) in widths.iter().enumerate() {
+            dark.extend(std::iter::repeat_n(i % 2 == 0, usize::from(w)));
+        }
+        dark.extend([false; 10]);
+        RgbaImage::from_fn(dark.len() as u32 * module, 12, |x, _| {
+            Rgba(if dark[(x / module) as usize] {
+                [0, 0, 0, 255]
+            } else {
+                [255; 4]
+            })
+        })
+    }
+
+    fn ean13_widths(digits: &str) -> Vec<u8> {
+        let digits: Vec<usize> = digits.bytes().map(|b| usize::from(b - b'0')).collect();
+        let mut widths = vec![1, 1, 1];
+        // Digits on the left start with a space, so the guard's last bar is followed by one.
+        for (&digit, &parity) in digits[1..7].iter().zip(EAN_PARITY[digits[0]]) {
+            let mut pattern = EAN_DIGITS[digit];
+            if parity == b'G' {
+                pattern.reverse();
+            }
+            widths.extend(pattern);
+        }
+        widths.extend([1; 5]);
+        for &digit in &digits[7..] {
+            widths.extend(EAN_DIGITS[digit]);
+        }
+        widths.extend([1, 1, 1]);
+        widths
+    }
+
+    /// The widths of the Code 128 barcode of `values`, the first being the start symbol.
+    fn code128_widths(values: &[u8]) -> Vec<u8> {
+        let check = values
+            .iter()
+            .enumerate()
+            .map(|(k, &v)| k.max(1) * usize::from(v))
+            .sum::<usize>()
+            % 103;
+        let mut widths: Vec<u8> = values
+            .iter()
+            .chain(&[check as u8, CODE128_STOP])
+            .flat_map(|&v| code128_pattern(v))
+            .collect();
+        widths.push(2);
+        widths
+    }
+
+    fn texts(img: &RgbaImage) -> Vec<(Symbology, String)> {
+        scan(img)
+            .into_iter()
+            .map(|c| (c.symbology, c.text))
+            .collect()
+    }
+
+    #[test]
+    fn reads_ean13() {
+        let img = render(&ean13_widths("4006381333931"), 2);
+        assert_eq!(
+            texts(&img),
+            [(Symbology::Ean13, "4006381333931".to_owned())]
+        );
+        let img = render(&ean13_widths("0036000291452"), 3);
+        assert_eq!(texts(&img), [(Symbology::UpcA, "036000291452".to_owned())]);
+    }
+
+    #[test]
+    fn checks_ean13_check_digit() {
+        let img = render(&ean13_widths("4006381333932"), 2);
+        assert_eq!(texts(&img), []);
+    }
+
+    #[test]
+    fn reads_code128() {
+        // Code set B, one value per character from the space on.
+        let hello: Vec<u8> = std::iter::once(104)
+            .chain("Hello, world!".bytes().map(|b| b - 32))
+            .collect();
+        let img = render(&code128_widths(&hello), 2);
+        assert_eq!(
+            texts(&img),
+            [(Symbology::Code128, "Hello, world!".to_owned())]
+        );
+        // Code set C, two digits a value, switching to code set B for the last character.
+        let img = render(&code128_widths(&[105, 12, 34, 56, 100, 33]), 3);
+        assert_eq!(texts(&img), [(Symbology::Code128, "123456A".to_owned())]);
+    }
+
+    #[test]
+    fn checks_code128_check_symbol() {
+        let mut widths = code128_widths(&[104, 41, 42, 43]);
+        // Swap the check symbol for another.
+        widths.splice(24..30, code128_pattern(7));
+        assert_eq!(texts(&render(&widths, 2)), []);
+    }
+}
@@ -0,0 +1,193 @@
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI, TAU};
+
+use eframe::egui::{Pos2, Rect, Vec2};
+
+use crate::{
+    annotation::rotate,
+    text::{Fonts, TextAlign, TextLayout, TextStyle},
+};
+
+/// Corner radius of rounded boxes, relative to their shorter side.
+const CORNER_RADIUS: f32 = 0.2;
+/// Width of the tail where it meets the body, relative to the body's shorter side.
+const TAIL_BASE: f32 = 0.3;
+/// Space between the body's edge and the text, relative to the font size.
+const PADDING: f32 = 0.5;
+/// Border width, relative to the font size.
+const BORDER: f32 = 0.1;
+const ARC_STEPS: u8 = 8;
+const ELLIPSE_STEPS: u8 = 64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CalloutShape {
+    /// A rounded rectangle.
+    #[default]
+    Box,
+    /// An ellipse, like a speech bubble in a comic.
+    Bubble,
+}
+
+impl CalloutShape {
+    pub const ALL: [Self; 2] = [Self::Box, Self::Bubble];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Box => "▢ Box",
+            Self::Bubble => "💬 Bubble",
+        }
+    }
+}
+
+pub fn border_width(size: f32) -> f32 {
+    size * BORDER
+}
+
+/// The convex outline of the body, in order of increasing angle around its centre. Points
+/// are close enough together for the tail to replace a few of them anywhere.
+fn body(rect: Rect, shape: CalloutShape) -> Vec<Pos2> {
+    let points: Vec<Pos2> = match shape {
+        CalloutShape::Box => {
+            let r = rect.width().min(rect.height()) * CORNER_RADIUS;
+            let corners = [
+                Pos2::new(rect.max.x - r, rect.max.y - r),
+                Pos2::new(rect.min.x + r, rect.max.y - r),
+                Pos2::new(rect.min.x + r, rect.min.y + r),
+                Pos2::new(rect.max.x - r, rect.min.y + r),
+            ];
+            corners
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, centre)| {
+                    (0..=ARC_STEPS).map(move |step| {
+                        let angle = (i as f32 + f32::from(step) / f32::from(ARC_STEPS)) * FRAC_PI_2;
+                        centre + Vec2::angled(angle) * r
+                    })
+                })
+                .collect()
+        }
+        CalloutShape::Bubble => (0..ELLIPSE_STEPS)
+            .map(|step| {
+                let angle = f32::from(step) / f32::from(ELLIPSE_STEPS) * TAU;
+                let v = Vec2::angled(angle);
+                rect.center() + Vec2::new(v.x * rect.width(), v.y * rect.height()) / 2.0
+            })
+            .collect(),
+    };
+    let max_step = (rect.width().min(rect.height()) * TAIL_BASE / 8.0).max(1.0);
+    let mut dense = Vec::with_capacity(points.len());
+    for (i, &p) in points.iter().enumerate() {
+        let next = points[(i + 1) % points.len()];
+        let steps = (p.distance(next) / max_step).ceil().max(1.0);
+        dense.extend((0..steps as u32).map(|step| p.lerp(next, step as f32 / steps)));
+    }
+    dense
+}
+
+/// The shapes that make up a callout.
+pub struct Geometry {
+    /// The convex outline of the body.
+    pub body: Vec<Pos2>,
+    /// The two corners of the tail's base and its tip, unless the tip is inside the body.
+    pub tail: Option<[Pos2; 3]>,
+    /// Body and tail as one outline.
+    pub outline: Vec<Pos2>,
+}
+
+/// The body of `shape` filling `rect` turned by `angle` radians around its centre, with a
+/// tail pointing at `tail`.
+pub fn geometry(rect: Rect, shape: CalloutShape, tail: Pos2, angle: f32) -> Geometry {
+    let centre = rect.center();
+    let upright = upright_geometry(rect, shape, rotate(tail, centre, -angle));
+    let turn = |points: Vec<Pos2>| -> Vec<Pos2> {
+        points
+            .into_iter()
+            .map(|p| rotate(p, centre, angle))
+            .collect()
+    };
+    Geometry {
+        body: turn(upright.body),
+        tail: upright.tail.map(|t| t.map(|p| rotate(p, centre, angle))),
+        outline: turn(upright.outline),
+    }
+}
+
+fn upright_geometry(rect: Rect, shape: CalloutShape, tail: Pos2) -> Geometry {
+    let body = body(rect, shape);
+    let no_tail = |body: Vec<Pos2>| Geometry {
+        outline: body.clone(),
+        body,
+        tail: None,
+    };
+    if contains_point(&body, tail) {
+        return no_tail(body);
+    }
+    let centre = rect.center();
+    let tail_angle = (tail - centre).angle();
+    let off_angle = |p: Pos2| {
+        let diff = (p - centre).angle() - tail_angle;
+        (diff + PI).rem_euclid(TAU) - PI
+    };
+    // The body point the tail leaves from, and the angle that keeps its base a constant width.
+    let Some(closest) = (0..body.len()).min_by(|&a, &b| {
+        off_angle(body[a])
+            .abs()
+            .total_cmp(&off_angle(body[b]).abs())
+    }) else {
+        return no_tail(body);
+    };
+    let base = rect.width().min(rect.height()) * TAIL_BASE;
+    let half_angle = (base / 2.0).atan2(body[closest].distance(centre));
+    let mut outline = vec![tail];
+    outline.extend(
+        (0..body.len())
+            .map(|i| body[(closest + i) % body.len()])
+            .filter(|&p| off_angle(p).abs() >= half_angle),
+    );
+    let (Some(&first), Some(&last)) = (outline.get(1), outline.last()) else {
+        return no_tail(body);
+    };
+    if outline.len() < 4 {
+        return no_tail(body);
+    }
+    Geometry {
+        body,
+        tail: Some([first, last, tail]),
+        outline,
+    }
+}
+
+/// Whether `p` is inside `polygon`, by counting crossings of a ray to the right.
+pub fn contains_point(polygon: &[Pos2], p: Pos2) -> bool {
+    let mut inside = false;
+    let mut prev = polygon.last().copied().unwrap_or(p);
+    for &point in polygon {
+        if (point.y > p.y) != (prev.y > p.y)
+            && p.x < (prev.x - point.x) * (p.y - point.y) / (prev.y - point.y) + point.x
+        {
+            inside = !inside;
+        }
+        prev = point;
+    }
+    inside
+}
+
+/// How callout text is styled: centred, otherwise plain.
+pub fn text_style() -> TextStyle {
+    TextStyle {
+        align: TextAlign::Center,
+        ..TextStyle::default()
+    }
+}
+
+/// The text wrapped to the inside of the body, and its top-left, centred in the body.
+pub fn layout_text(rect: Rect, shape: CalloutShape, text: &str, size: f32) -> (Pos2, TextLayout) {
+    let area = match shape {
+        CalloutShape::Box => rect,
+        // The largest rectangle that fits inside the ellipse.
+        CalloutShape::Bubble => Rect::from_center_size(rect.center(), rect.size() * FRAC_1_SQRT_2),
+    }
+    .shrink(size * PADDING);
+    let layout = Fonts::get().layout_wrapped(text, size, &text_style(), area.width().max(size));
+    let pos = area.center() - layout.rect(Pos2::ZERO).size() / 2.0;
+    (pos, layout)
+}
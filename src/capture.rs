@@ -0,0 +1,257 @@
+use eframe::egui::{Color32, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+use xcap::{image::RgbaImage, Monitor, Window};
+
+use crate::{
+    effects,
+    settings::{PresetTarget, WindowCapture, WindowMatch},
+};
+
+const SHADOW_BLUR: f32 = 12.0;
+const SHADOW_OFFSET: Vec2 = Vec2::new(0.0, 6.0);
+const SHADOW_COLOR: Color32 = Color32::from_black_alpha(140);
+
+/// Where an image came from, for its tab title and auto-save file names.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureInfo {
+    /// Shown on the image's tab.
+    pub title: String,
+    pub window_title: String,
+    pub app: String,
+    pub monitor: String,
+}
+
+impl CaptureInfo {
+    /// An image that wasn't captured from the screen, such as a file or the clipboard.
+    pub fn named(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// A capture of all or part of the primary monitor.
+    pub fn screen(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            monitor: Monitor::all()
+                .ok()
+                .and_then(|m| m.first().and_then(monitor_name))
+                .unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
+    /// A capture of the window with the given id, or of a control inside it.
+    pub fn window(id: u32, title: &str, app: &str) -> Self {
+        let monitor = Window::all()
+            .ok()
+            .and_then(|all| all.into_iter().find(|w| w.id().ok() == Some(id)))
+            .and_then(|w| w.current_monitor().ok())
+            .and_then(|m| monitor_name(&m))
+            .unwrap_or_default();
+        Self {
+            title: title.to_string(),
+            window_title: title.to_string(),
+            app: app.to_string(),
+            monitor,
+        }
+    }
+}
+
+/// The monitor's name without the `\\.\` device path prefix Windows puts in front of it.
+fn monitor_name(monitor: &Monitor) -> Option<String> {
+    let name = monitor.name().ok()?;
+    Some(name.trim_start_matches(r"\\.\").to_string())
+}
+
+/// Captures the primary monitor.
+pub fn fullscreen() -> Option<RgbaImage> {
+    Monitor::all()
+        .ok()?
+        .first()
+        .and_then(|m| m.capture_image().ok())
+}
+
+/// Crops `rect` out of `img`, clamped to its bounds. Returns `None` if nothing is left.
+pub fn crop(img: &RgbaImage, rect: Rect) -> Option<RgbaImage> {
+    let (x, y) = (rect.min.x.max(0.0) as u32, rect.min.y.max(0.0) as u32);
+    let (w, h) = (
+        (rect.max.x.max(0.0) as u32)
+            .saturating_sub(x)
+            .min(img.width().saturating_sub(x)),
+        (rect.max.y.max(0.0) as u32)
+            .saturating_sub(y)
+            .min(img.height().saturating_sub(y)),
+    );
+    (w > 0 && h > 0).then(|| xcap::image::imageops::crop_imm(img, x, y, w, h).to_image())
+}
+
+pub fn region(rect: Rect) -> Option<RgbaImage> {
+    crop(&fullscreen()?, rect)
+}
+
+/// Captures the client area of the window with the given id, even if it is covered.
+pub fn window_by_id(id: u32) -> Option<RgbaImage> {
+    Window::all()
+        .ok()?
+        .into_iter()
+        .find(|w| w.id().ok() == Some(id))
+        .and_then(|w| w.capture_image().ok())
+}
+
+/// Applies the rounded corners and drop shadow configured in `opts`.
+pub fn style_window(mut img: RgbaImage, opts: &WindowCapture) -> RgbaImage {
+    effects::round_corners(&mut img, opts.corner_radius);
+    if opts.drop_shadow {
+        img = effects::drop_shadow(&img, SHADOW_BLUR, SHADOW_OFFSET, SHADOW_COLOR);
+    }
+    img
+}
+
+/// Captures the first visible window matching `m`. With `include_frame` the window is cut
+/// out of a screen capture, so it must not be covered by anything.
+pub fn window(m: &WindowMatch, opts: &WindowCapture) -> Option<(RgbaImage, CaptureInfo)> {
+    let w = Window::all()
+        .ok()?
+        .into_iter()
+        .filter(|w| !w.is_minimized().unwrap_or(false))
+        .find(|w| {
+            m.matches(
+                &w.title().unwrap_or_default(),
+                &w.app_name().unwrap_or_default(),
+            )
+        })?;
+    let framed = if opts.include_frame {
+        w.id().ok().and_then(frame_bounds).and_then(region)
+    } else {
+        None
+    };
+    let img = framed.or_else(|| w.capture_image().ok())?;
+    let info = CaptureInfo::window(
+        w.id().unwrap_or_default(),
+        &w.title().unwrap_or_default(),
+        &w.app_name().unwrap_or_default(),
+    );
+    Some((style_window(img, opts), info))
+}
+
+impl PresetTarget {
+    pub fn capture(&self, opts: &WindowCapture) -> Option<(RgbaImage, CaptureInfo)> {
+        match self {
+            Self::Region { rect } => region(*rect).map(|img| (img, CaptureInfo::screen("Area"))),
+            Self::Window(m) => window(m, opts),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn hwnd(window_id: u32) -> windows::Win32::Foundation::HWND {
+    windows::Win32::Foundation::HWND(window_id as usize as *mut core::ffi::c_void)
+}
+
+#[cfg(windows)]
+fn rect_from_win32(r: windows::Win32::Foundation::RECT) -> Rect {
+    Rect::from_min_max(
+        eframe::egui::Pos2::new(r.left as f32, r.top as f32),
+        eframe::egui::Pos2::new(r.right as f32, r.bottom as f32),
+    )
+}
+
+/// The visible bounds of a window including title bar and borders, but not its shadow.
+#[cfg(windows)]
+pub fn frame_bounds(window_id: u32) -> Option<Rect> {
+    use windows::Win32::{
+        Foundation::RECT,
+        Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+    };
+
+    let mut r = RECT::default();
+    // SAFETY: `r` is a valid out buffer of the size we pass.
+    unsafe {
+        DwmGetWindowAttribute(
+            hwnd(window_id),
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            (&raw mut r).cast(),
+            size_of::<RECT>() as u32,
+        )
+        .ok()?;
+    }
+    Some(rect_from_win32(r))
+}
+
+#[cfg(not(windows))]
+pub fn frame_bounds(_window_id: u32) -> Option<Rect> {
+    None
+}
+
+/// A child control of a top-level window, in screen pixels.
+pub struct ControlInfo {
+    pub rect: Rect,
+    pub label: String,
+}
+
+/// Lists the visible child controls of the window with the given id.
+#[cfg(windows)]
+pub fn child_controls(window_id: u32) -> Vec<ControlInfo> {
+    use windows::{
+        core::BOOL,
+        Win32::{
+            Foundation::{HWND, LPARAM, RECT},
+            UI::WindowsAndMessaging::{
+                EnumChildWindows, GetClassNameW, GetWindowRect, GetWindowTextW, IsWindowVisible,
+            },
+        },
+    };
+
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        // SAFETY: `lparam` is the `Vec<HWND>` handed to `EnumChildWindows` below,
+        // which outlives the enumeration.
+        let hwnds = unsafe { &mut *(lparam.0 as *mut Vec<HWND>) };
+        hwnds.push(hwnd);
+        true.into()
+    }
+
+    let mut hwnds: Vec<HWND> = Vec::new();
+    // SAFETY: `collect` only touches `hwnds`, which lives for the whole call.
+    unsafe {
+        let _ = EnumChildWindows(
+            Some(hwnd(window_id)),
+            Some(collect),
+            LPARAM(&raw mut hwnds as isize),
+        );
+    }
+    hwnds
+        .into_iter()
+        .filter_map(|hwnd| {
+            // SAFETY: plain Win32 queries on handles returned by the enumeration.
+            unsafe {
+                if !IsWindowVisible(hwnd).as_bool() {
+                    return None;
+                }
+                let mut r = RECT::default();
+                GetWindowRect(hwnd, &mut r).ok()?;
+                let mut buf = [0u16; 256];
+                let len = GetWindowTextW(hwnd, &mut buf).max(0) as usize;
+                let text = String::from_utf16_lossy(&buf[..len]);
+                let len = GetClassNameW(hwnd, &mut buf).max(0) as usize;
+                let class = String::from_utf16_lossy(&buf[..len]);
+                Some(ControlInfo {
+                    rect: rect_from_win32(r),
+                    label: if text.is_empty() {
+                        class
+                    } else {
+                        format!("{text} ({class})")
+                    },
+                })
+            }
+        })
+        .filter(|c| c.rect.width() > 4.0 && c.rect.height() > 4.0)
+        .collect()
+}
+
+#[cfg(not(windows))]
+pub fn child_controls(_window_id: u32) -> Vec<ControlInfo> {
+    Vec::new()
+}
@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+
+use eframe::egui::Vec2;
+use xcap::image::RgbaImage;
+
+use crate::transform::{Filter, ResizeSpec, Transform};
+
+/// Largest `--scale`, in percent, so a typo can't ask for an image too large to make.
+const MAX_SCALE_PERCENT: f32 = 1000.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("unknown argument `{0}`")]
+    UnknownArgument(String),
+    #[error("`{0}` expects a value")]
+    MissingValue(&'static str),
+    #[error("invalid value `{value}` for `{arg}`")]
+    InvalidValue { arg: &'static str, value: String },
+    #[error("only one capture source or file may be given")]
+    ConflictingCapture,
+    #[error("`--scale` can't be combined with `--max-width` or `--max-height`")]
+    ConflictingResize,
+}
+
+/// What to capture right after startup, before the window is shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupCapture {
+    Fullscreen,
+    LastArea,
+    Preset(String),
+    /// An image file to open instead of capturing.
+    File(PathBuf),
+}
+
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub capture: Option<StartupCapture>,
+    /// Applied to the startup capture before `transforms`.
+    pub resize: Option<ResizeSpec>,
+    pub filter: Filter,
+    /// Rotations and flips, in the order given.
+    pub transforms: Vec<Transform>,
+}
+
+fn value(args: &mut impl Iterator<Item = String>, arg: &'static str) -> Result<String, CliError> {
+    args.next().ok_or(CliError::MissingValue(arg))
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str, arg: &'static str) -> Result<T, CliError> {
+    value.parse().map_err(|_| CliError::InvalidValue {
+        arg,
+        value: value.to_string(),
+    })
+}
+
+/// Like [`parse_value`], but also rejects values `valid` doesn't accept.
+fn parse_valid<T: std::str::FromStr>(
+    value: &str,
+    arg: &'static str,
+    valid: impl Fn(&T) -> bool,
+) -> Result<T, CliError> {
+    parse_value(value, arg).and_then(|v| {
+        if valid(&v) {
+            Ok(v)
+        } else {
+            Err(CliError::InvalidValue {
+                arg,
+                value: value.to_string(),
+            })
+        }
+    })
+}
+
+impl CliArgs {
+    /// Parses the arguments following the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut out = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--fullscreen" => out.set_capture(StartupCapture::Fullscreen)?,
+                "--last-area" => out.set_capture(StartupCapture::LastArea)?,
+                "--preset" => {
+                    out.set_capture(StartupCapture::Preset(value(&mut args, "--preset")?))?;
+                }
+                "--scale" => {
+                    let v = value(&mut args, "--scale")?;
+                    let percent = parse_valid(v.trim_end_matches('%'), "--scale", |p: &f32| {
+                        *p > 0.0 && *p <= MAX_SCALE_PERCENT
+                    })?;
+                    if out.resize.is_some() {
+                        return Err(CliError::ConflictingResize);
+                    }
+                    out.resize = Some(ResizeSpec::Percent(percent));
+                }
+                "--max-width" => {
+                    let v = value(&mut args, "--max-width")?;
+                    let px = parse_valid(&v, "--max-width", |px: &u32| *px > 0)?;
+                    out.set_fit(Some(px), None)?;
+                }
+                "--max-height" => {
+                    let v = value(&mut args, "--max-height")?;
+                    let px = parse_valid(&v, "--max-height", |px: &u32| *px > 0)?;
+                    out.set_fit(None, Some(px))?;
+                }
+                "--filter" => {
+                    let v = value(&mut args, "--filter")?;
+                    out.filter = Filter::parse(&v).ok_or(CliError::InvalidValue {
+                        arg: "--filter",
+                        value: v,
+                    })?;
+                }
+                "--rotate" => {
+                    let v = value(&mut args, "--rotate")?;
+                    out.transforms.push(match v.as_str() {
+                        "90" => Transform::Rotate90,
+                        "180" => Transform::Rotate180,
+                        "270" => Transform::Rotate270,
+                        _ => {
+                            return Err(CliError::InvalidValue {
+                                arg: "--rotate",
+                                value: v,
+                            })
+                        }
+                    });
+                }
+                "--flip-horizontal" => out.transforms.push(Transform::FlipHorizontal),
+                "--flip-vertical" => out.transforms.push(Transform::FlipVertical),
+                _ if !arg.starts_with('-') => {
+                    out.set_capture(StartupCapture::File(PathBuf::from(arg)))?;
+                }
+                _ => return Err(CliError::UnknownArgument(arg)),
+            }
+        }
+        Ok(out)
+    }
+
+    fn set_capture(&mut self, capture: StartupCapture) -> Result<(), CliError> {
+        if self.capture.replace(capture).is_some() {
+            return Err(CliError::ConflictingCapture);
+        }
+        Ok(())
+    }
+
+    /// Merges a `--max-width`/`--max-height` limit into the resize spec.
+    fn set_fit(&mut self, width: Option<u32>, height: Option<u32>) -> Result<(), CliError> {
+        let (max_width, max_height) = match self.resize {
+            None => (None, None),
+            Some(ResizeSpec::Fit {
+                max_width,
+                max_height,
+            }) => (max_width, max_height),
+            Some(ResizeSpec::Percent(_)) => return Err(CliError::ConflictingResize),
+        };
+        self.resize = Some(ResizeSpec::Fit {
+            max_width: width.or(max_width),
+            max_height: height.or(max_height),
+        });
+        Ok(())
+    }
+
+    /// Applies the requested resize, rotations and flips to `img`.
+    pub fn transform(&self, mut img: RgbaImage) -> RgbaImage {
+        if let Some(spec) = self.resize {
+            let size = Vec2::new(img.width() as f32, img.height() as f32);
+            img = Transform::Resize {
+                factor: spec.factor(size),
+                filter: self.filter,
+            }
+            .apply(&img);
+        }
+        for t in &self.transforms {
+            img = t.apply(&img);
+        }
+        img
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, CliError> {
+        CliArgs::parse(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(
+            parse(&["--scale", "50%"]).unwrap().resize,
+            Some(ResizeSpec::Percent(50.0))
+        );
+        assert_eq!(
+            parse(&["--scale", "1000"]).unwrap().resize,
+            Some(ResizeSpec::Percent(1000.0))
+        );
+        for bad in ["-50", "0", "NaN", "inf", "1e9", "1000.5", "half"] {
+            assert!(
+                matches!(
+                    parse(&["--scale", bad]),
+                    Err(CliError::InvalidValue { arg: "--scale", .. })
+                ),
+                "--scale {bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn max_size() {
+        assert_eq!(
+            parse(&["--max-width", "800", "--max-height", "600"])
+                .unwrap()
+                .resize,
+            Some(ResizeSpec::Fit {
+                max_width: Some(800),
+                max_height: Some(600),
+            })
+        );
+        for arg in ["--max-width", "--max-height"] {
+            for bad in ["0", "-1", "wide"] {
+                assert!(
+                    matches!(parse(&[arg, bad]), Err(CliError::InvalidValue { .. })),
+                    "{arg} {bad}"
+                );
+            }
+        }
+    }
+}
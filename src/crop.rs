@@ -0,0 +1,406 @@
+use eframe::egui::{CursorIcon, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// How close the pointer has to be to an edge of the crop rectangle to grab it, in UI points.
+const GRAB_DISTANCE: f32 = 8.0;
+/// Largest number either side of a custom aspect ratio can be in the crop window.
+pub const MAX_RATIO_SIDE: f32 = 100.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Aspect {
+    #[default]
+    Free,
+    /// The aspect ratio of the uncropped image.
+    Original,
+    Ratio {
+        w: f32,
+        h: f32,
+    },
+}
+
+impl Aspect {
+    pub const PRESETS: [(&str, Self); 6] = [
+        ("Free", Self::Free),
+        ("Original", Self::Original),
+        ("16:9", Self::Ratio { w: 16.0, h: 9.0 }),
+        ("4:3", Self::Ratio { w: 4.0, h: 3.0 }),
+        ("3:2", Self::Ratio { w: 3.0, h: 2.0 }),
+        ("1:1", Self::Ratio { w: 1.0, h: 1.0 }),
+    ];
+
+    pub fn label(self) -> String {
+        match Self::PRESETS.iter().find(|(_, a)| *a == self) {
+            Some((label, _)) => (*label).to_string(),
+            None => match self {
+                Self::Ratio { w, h } => format!("{w}:{h}"),
+                _ => unreachable!("non-ratio aspects are all presets"),
+            },
+        }
+    }
+
+    /// The aspect ratio of `size` in the smallest whole numbers, e.g. 16:9 for 1920 × 1080,
+    /// brought within [`MAX_RATIO_SIDE`].
+    pub fn of_size(size: Vec2) -> Self {
+        let (w, h) = (
+            size.x.round().max(1.0) as u32,
+            size.y.round().max(1.0) as u32,
+        );
+        let d = gcd(w, h);
+        Self::Ratio {
+            w: (w / d) as f32,
+            h: (h / d) as f32,
+        }
+        .in_range()
+    }
+
+    /// Custom ratios with a side outside `1..=MAX_RATIO_SIDE`, like ones saved before the
+    /// limit, scaled to fit, to a tenth. Others are returned as they are.
+    pub fn in_range(self) -> Self {
+        let Self::Ratio { w, h } = self else {
+            return self;
+        };
+        let (small, large) = (w.min(h), w.max(h));
+        let scale = if small <= 0.0 || (small >= 1.0 && large <= MAX_RATIO_SIDE) {
+            return self;
+        } else if large > MAX_RATIO_SIDE {
+            MAX_RATIO_SIDE / large
+        } else {
+            (1.0 / small).min(MAX_RATIO_SIDE / large)
+        };
+        let fit = |side: f32| ((side * scale * 10.0).round() / 10.0).clamp(1.0, MAX_RATIO_SIDE);
+        Self::Ratio {
+            w: fit(w),
+            h: fit(h),
+        }
+    }
+
+    /// Width over height, or `None` if unconstrained. `image` is the uncropped image size.
+    pub fn ratio(self, image: Vec2) -> Option<f32> {
+        match self {
+            Self::Free => None,
+            Self::Original => Some(image.x / image.y),
+            Self::Ratio { w, h } => (w > 0.0 && h > 0.0).then_some(w / h),
+        }
+    }
+}
+
+/// A grabbed part of the crop rectangle: `-1`/`1` for the min/max edge on each axis,
+/// `0` for neither. Both `0` moves the whole rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle {
+    x: i8,
+    y: i8,
+}
+
+impl Handle {
+    pub const MOVE: Self = Self { x: 0, y: 0 };
+    pub const ALL: [Self; 8] = [
+        Self { x: -1, y: -1 },
+        Self { x: 0, y: -1 },
+        Self { x: 1, y: -1 },
+        Self { x: 1, y: 0 },
+        Self { x: 1, y: 1 },
+        Self { x: 0, y: 1 },
+        Self { x: -1, y: 1 },
+        Self { x: -1, y: 0 },
+    ];
+
+    /// Where the handle sits on `rect`.
+    pub fn pos(self, rect: Rect) -> Pos2 {
+        rect.min + rect.size() * Vec2::new(f32::from(self.x + 1), f32::from(self.y + 1)) / 2.0
+    }
+
+    pub fn cursor(self) -> CursorIcon {
+        match (self.x, self.y) {
+            (0, 0) => CursorIcon::Move,
+            (0, _) => CursorIcon::ResizeVertical,
+            (_, 0) => CursorIcon::ResizeHorizontal,
+            (x, y) if x == y => CursorIcon::ResizeNwSe,
+            _ => CursorIcon::ResizeNeSw,
+        }
+    }
+
+    /// The handle of `rect` under `p`, both in UI points.
+    pub fn at(rect: Rect, p: Pos2) -> Option<Self> {
+        let edge = |p: f32, min: f32, max: f32, across: bool| -> i8 {
+            if !across {
+                0
+            } else if (p - max).abs() <= GRAB_DISTANCE {
+                1
+            } else if (p - min).abs() <= GRAB_DISTANCE {
+                -1
+            } else {
+                0
+            }
+        };
+        let expanded = rect.expand(GRAB_DISTANCE);
+        let handle = Self {
+            x: edge(
+                p.x,
+                rect.min.x,
+                rect.max.x,
+                expanded.y_range().contains(p.y),
+            ),
+            y: edge(
+                p.y,
+                rect.min.y,
+                rect.max.y,
+                expanded.x_range().contains(p.x),
+            ),
+        };
+        (handle != Self::MOVE || rect.contains(p)).then_some(handle)
+    }
+}
+
+struct Drag {
+    handle: Handle,
+    /// The rectangle being resized; zero-sized at the pointer for a new selection.
+    start: Rect,
+    /// The rectangle before the drag, restored if the drag doesn't select anything.
+    previous: Rect,
+    from: Pos2,
+}
+
+/// The crop rectangle while it is being edited, in pixels of the uncropped image.
+pub struct CropEdit {
+    pub rect: Rect,
+    drag: Option<Drag>,
+}
+
+impl CropEdit {
+    pub fn new(rect: Rect) -> Self {
+        Self { rect, drag: None }
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Starts dragging `handle`, or a new selection at `pointer` if `None`.
+    pub fn begin_drag(&mut self, handle: Option<Handle>, pointer: Pos2) {
+        let (handle, start) = match handle {
+            Some(handle) => (handle, self.rect),
+            None => (
+                Handle { x: 1, y: 1 },
+                Rect::from_min_size(pointer.round(), Vec2::ZERO),
+            ),
+        };
+        self.drag = Some(Drag {
+            handle,
+            start,
+            previous: self.rect,
+            from: pointer,
+        });
+    }
+
+    pub fn drag_to(&mut self, pointer: Pos2, ratio: Option<f32>, bounds: Rect) {
+        if let Some(drag) = &self.drag {
+            self.rect = dragged(drag, pointer, ratio, bounds);
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        if let Some(drag) = self.drag.take()
+            && (self.rect.width() < 1.0 || self.rect.height() < 1.0)
+        {
+            self.rect = drag.previous;
+        }
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// `min` moved so a rectangle of `size` starting there stays inside `bounds`, or at the
+/// top-left of `bounds` if it is too large to.
+fn clamp_min(min: Pos2, size: Vec2, bounds: Rect) -> Pos2 {
+    min.clamp(bounds.min, (bounds.max - size).max(bounds.min))
+}
+
+fn round(rect: Rect) -> Rect {
+    Rect::from_min_max(rect.min.round(), rect.max.round())
+}
+
+fn dragged(drag: &Drag, pointer: Pos2, ratio: Option<f32>, bounds: Rect) -> Rect {
+    let Drag {
+        handle,
+        start,
+        from,
+        ..
+    } = *drag;
+    if handle == Handle::MOVE {
+        let min = clamp_min(start.min + (pointer - from), start.size(), bounds);
+        return round(Rect::from_min_size(min, start.size()));
+    }
+    let to = bounds.clamp(pointer);
+    // The edge opposite the handle stays put; an axis the handle doesn't touch stays centred.
+    let anchor = Pos2::new(
+        if handle.x < 0 {
+            start.max.x
+        } else {
+            start.min.x
+        },
+        if handle.y < 0 {
+            start.max.y
+        } else {
+            start.min.y
+        },
+    );
+    let span = |h: i8, to: f32, anchor: f32, len: f32| {
+        if h == 0 {
+            len
+        } else {
+            (to - anchor).abs()
+        }
+    };
+    let mut size = Vec2::new(
+        span(handle.x, to.x, anchor.x, start.width()),
+        span(handle.y, to.y, anchor.y, start.height()),
+    );
+    if let Some(r) = ratio {
+        match (handle.x != 0, handle.y != 0) {
+            (true, true) if size.x > size.y * r => size.y = size.x / r,
+            (true, false) => size.y = size.x / r,
+            _ => size.x = size.y * r,
+        }
+    }
+    let room = |h: i8, to: f32, anchor: f32, centre: f32, min: f32, max: f32| match h {
+        0 => 2.0 * (centre - min).min(max - centre),
+        _ if to >= anchor => max - anchor,
+        _ => anchor - min,
+    };
+    let centre = start.center();
+    let room = Vec2::new(
+        room(
+            handle.x,
+            to.x,
+            anchor.x,
+            centre.x,
+            bounds.min.x,
+            bounds.max.x,
+        ),
+        room(
+            handle.y,
+            to.y,
+            anchor.y,
+            centre.y,
+            bounds.min.y,
+            bounds.max.y,
+        ),
+    );
+    size = match ratio {
+        Some(_) => size * (room.x / size.x).min(room.y / size.y).min(1.0),
+        None => size.min(room),
+    };
+    let range = |h: i8, to: f32, anchor: f32, centre: f32, len: f32| match h {
+        0 => (centre - len / 2.0, centre + len / 2.0),
+        _ if to >= anchor => (anchor, anchor + len),
+        _ => (anchor - len, anchor),
+    };
+    let (x0, x1) = range(handle.x, to.x, anchor.x, centre.x, size.x);
+    let (y0, y1) = range(handle.y, to.y, anchor.y, centre.y, size.y);
+    round(Rect::from_min_max(Pos2::new(x0, y0), Pos2::new(x1, y1)))
+}
+
+/// Applies `ratio` to `rect` by deriving one side from the other (the width if
+/// `keep_width`), then shrinks and moves the result to fit inside `bounds`.
+pub fn constrain(rect: Rect, ratio: Option<f32>, keep_width: bool, bounds: Rect) -> Rect {
+    let mut size = rect.size().max(Vec2::splat(1.0));
+    if let Some(r) = ratio {
+        if keep_width {
+            size.y = size.x / r;
+        } else {
+            size.x = size.y * r;
+        }
+        size *= (bounds.width() / size.x)
+            .min(bounds.height() / size.y)
+            .min(1.0);
+    }
+    // Scaling can leave a side a rounding error larger than `bounds`.
+    size = size.min(bounds.size());
+    let min = clamp_min(rect.min, size, bounds);
+    round(Rect::from_min_size(min, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(width: f32, height: f32) -> Rect {
+        Rect::from_min_size(Pos2::ZERO, Vec2::new(width, height))
+    }
+
+    #[test]
+    fn constrain_rounding() {
+        // 248 / (4 / 3) * (100 / 186) comes out a little over 100.
+        let bounds = bounds(248.0, 100.0);
+        assert_eq!(
+            constrain(bounds, Some(4.0 / 3.0), true, bounds),
+            Rect::from_min_size(Pos2::ZERO, Vec2::new(133.0, 100.0))
+        );
+    }
+
+    #[test]
+    fn reset_with_presets() {
+        for (width, height) in [
+            (248.0, 100.0),
+            (359.0, 182.0),
+            (100.0, 248.0),
+            (1920.0, 1080.0),
+        ] {
+            let bounds = bounds(width, height);
+            for (label, aspect) in Aspect::PRESETS {
+                let rect = constrain(bounds, aspect.ratio(bounds.size()), true, bounds);
+                assert!(
+                    bounds.contains_rect(rect) && rect.is_positive(),
+                    "{label} on {width}×{height}: {rect:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn move_larger_than_bounds() {
+        // A crop from before the image was shrunk.
+        let mut edit = CropEdit::new(bounds(300.0, 200.0));
+        edit.begin_drag(Some(Handle::MOVE), Pos2::new(50.0, 50.0));
+        edit.drag_to(Pos2::new(80.0, 20.0), None, bounds(200.0, 100.0));
+        assert_eq!(edit.rect, bounds(300.0, 200.0));
+    }
+
+    #[test]
+    fn ratio_of_size() {
+        let ratio = |w, h| Aspect::of_size(Vec2::new(w, h));
+        assert_eq!(ratio(1920.0, 1080.0), Aspect::Ratio { w: 16.0, h: 9.0 });
+        assert_eq!(ratio(800.0, 600.0), Aspect::Ratio { w: 4.0, h: 3.0 });
+        assert_eq!(ratio(500.0, 500.0), Aspect::Ratio { w: 1.0, h: 1.0 });
+        // 683:384 doesn't reduce, so it's scaled down.
+        assert_eq!(ratio(1366.0, 768.0), Aspect::Ratio { w: 100.0, h: 56.2 });
+        assert_eq!(ratio(3000.0, 7.0), Aspect::Ratio { w: 100.0, h: 1.0 });
+    }
+
+    #[test]
+    fn ratio_in_range() {
+        let custom = Aspect::Ratio { w: 2.5, h: 1.0 };
+        assert_eq!(custom.in_range(), custom);
+        assert_eq!(Aspect::Free.in_range(), Aspect::Free);
+        assert_eq!(
+            Aspect::Ratio {
+                w: 1920.0,
+                h: 1080.0
+            }
+            .in_range(),
+            Aspect::Ratio { w: 100.0, h: 56.3 }
+        );
+        assert_eq!(
+            Aspect::Ratio { w: 0.5, h: 2.0 }.in_range(),
+            Aspect::Ratio { w: 1.0, h: 4.0 }
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use eframe::egui::{Pos2, Rect};
+use xcap::image::RgbaImage;
+
+use crate::snap::{self, Line};
+
+/// Shortest side of an element, in pixels. Also the shortest straight edge that is looked
+/// at, so small buttons with rounded corners are still found.
+const MIN_SIDE: u32 = 8;
+/// How much rounded corners may shorten the straight part of an edge at each end.
+const MAX_CORNER: f32 = 16.0;
+/// How far edges that belong together may be out of line, in pixels.
+const TOLERANCE: f32 = 2.0;
+/// Rects this close on every side are one element, e.g. the outside and inside of a border.
+const SAME_ELEMENT: f32 = 3.0;
+
+/// The rectangular UI elements in a screenshot, like buttons, fields and panels: where a
+/// pair of horizontal edges spanning the same columns meets a pair of vertical edges
+/// spanning the same rows. Corners may be rounded. Each rect covers the element's pixels,
+/// border included.
+pub fn find_rects(img: &RgbaImage) -> Vec<Rect> {
+    let edges = snap::detect_edges(img, MIN_SIDE);
+    let (mut horizontal, mut vertical) = (edges.horizontal, edges.vertical);
+    horizontal.sort_by(|a, b| a.from.total_cmp(&b.from));
+    vertical.sort_by(|a, b| a.at.total_cmp(&b.at));
+    let mut rects: Vec<Rect> = Vec::new();
+    for top in &horizontal {
+        let lo = horizontal.partition_point(|l| l.from < top.from - TOLERANCE);
+        let hi = horizontal.partition_point(|l| l.from <= top.from + TOLERANCE);
+        for bottom in &horizontal[lo..hi] {
+            if bottom.at - top.at < MIN_SIDE as f32 || (bottom.to - top.to).abs() > TOLERANCE {
+                continue;
+            }
+            let (from, to) = (top.from.min(bottom.from), top.to.max(bottom.to));
+            let (y_min, y_max) = (top.at, bottom.at);
+            let left = side(
+                &vertical,
+                from - MAX_CORNER - TOLERANCE,
+                from + TOLERANCE,
+                y_min,
+                y_max,
+            );
+            let right = side(
+                &vertical,
+                to - TOLERANCE,
+                to + MAX_CORNER + TOLERANCE,
+                y_min,
+                y_max,
+            );
+            let (Some(left), Some(right)) = (left, right) else {
+                continue;
+            };
+            if right.at - left.at < MIN_SIDE as f32 {
+                continue;
+            }
+            add(
+                &mut rects,
+                Rect::from_min_max(Pos2::new(left.at, y_min), Pos2::new(right.at, y_max)),
+            );
+        }
+    }
+    rects
+}
+
+/// The longest vertical edge at an x in `x_min..=x_max` that runs between `y_min` and `y_max`,
+/// except for rounded corners at its ends. `vertical` is sorted by x.
+fn side(vertical: &[Line], x_min: f32, x_max: f32, y_min: f32, y_max: f32) -> Option<Line> {
+    let lo = vertical.partition_point(|l| l.at < x_min);
+    let hi = vertical.partition_point(|l| l.at <= x_max);
+    vertical[lo..hi]
+        .iter()
+        .filter(|l| {
+            l.from >= y_min - TOLERANCE
+                && l.to <= y_max + TOLERANCE
+                && l.from - y_min <= MAX_CORNER + TOLERANCE
+                && y_max - l.to <= MAX_CORNER + TOLERANCE
+        })
+        .max_by(|a, b| (a.to - a.from).total_cmp(&(b.to - b.from)))
+        .copied()
+}
+
+/// Adds `rect` unless it is the same element as one already found, keeping the larger one.
+fn add(rects: &mut Vec<Rect>, rect: Rect) {
+    let same = |a: Rect| {
+        (a.min - rect.min).abs().max_elem() <= SAME_ELEMENT
+            && (a.max - rect.max).abs().max_elem() <= SAME_ELEMENT
+    };
+    match rects.iter_mut().find(|r| same(**r)) {
+        Some(existing) => *existing = existing.union(rect),
+        None => rects.push(rect),
+    }
+}
+
+/// The smallest of `rects` that contains `p`.
+pub fn rect_at(rects: &[Rect], p: Pos2) -> Option<Rect> {
+    rects
+        .iter()
+        .filter(|r| r.contains(p))
+        .min_by(|a, b| a.area().total_cmp(&b.area()))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::egui::Vec2;
+    use xcap::image::Rgba;
+
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const GREY: Rgba<u8> = Rgba([200, 200, 200, 255]);
+    const DARK: Rgba<u8> = Rgba([40, 40, 40, 255]);
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect::from_min_max(Pos2::new(x0, y0), Pos2::new(x1, y1))
+    }
+
+    /// Fills the pixels from `(x0, y0)` up to but not including `(x1, y1)`.
+    fn fill(img: &mut RgbaImage, (x0, y0): (u32, u32), (x1, y1): (u32, u32), color: Rgba<u8>) {
+        for y in y0..y1 {
+            for x in x0..x1 {
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Draws a border `width` pixels wide just inside the pixels from `min` up to `max`.
+    fn border(img: &mut RgbaImage, min: (u32, u32), max: (u32, u32), width: u32) {
+        fill(img, min, (max.0, min.1 + width), DARK);
+        fill(img, (min.0, max.1 - width), max, DARK);
+        fill(img, min, (min.0 + width, max.1), DARK);
+        fill(img, (max.0 - width, min.1), max, DARK);
+    }
+
+    #[test]
+    fn bordered_box() {
+        let mut img = RgbaImage::from_pixel(100, 100, WHITE);
+        border(&mut img, (20, 30), (60, 50), 1);
+        let rects = find_rects(&img);
+        assert_eq!(rects, [rect(20.0, 30.0, 60.0, 50.0)]);
+        assert_eq!(rect_at(&rects, Pos2::new(40.0, 40.0)), Some(rects[0]));
+        assert_eq!(rect_at(&rects, Pos2::new(10.0, 40.0)), None);
+    }
+
+    #[test]
+    fn rounded_button() {
+        let mut img = RgbaImage::from_pixel(60, 40, WHITE);
+        let radius = 5.0;
+        let (min, max) = (Pos2::new(10.0, 10.0), Pos2::new(50.0, 30.0));
+        for y in 10..30 {
+            for x in 10..50 {
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                // The nearest point of the button shrunk by the radius.
+                let inner = p.clamp(min + Vec2::splat(radius), max - Vec2::splat(radius));
+                if p.distance(inner) <= radius {
+                    img.put_pixel(x, y, DARK);
+                }
+            }
+        }
+        let rects = find_rects(&img);
+        assert_eq!(rects, [rect(10.0, 10.0, 50.0, 30.0)]);
+        assert_eq!(rect_at(&rects, Pos2::new(12.0, 20.0)), Some(rects[0]));
+    }
+
+    #[test]
+    fn box_in_panel() {
+        let mut img = RgbaImage::from_pixel(100, 100, WHITE);
+        fill(&mut img, (10, 10), (90, 90), GREY);
+        border(&mut img, (30, 40), (60, 60), 1);
+        let rects = find_rects(&img);
+        let (panel, button) = (rect(10.0, 10.0, 90.0, 90.0), rect(30.0, 40.0, 60.0, 60.0));
+        assert_eq!(rects, [panel, button]);
+        // The smallest element under the point wins.
+        assert_eq!(rect_at(&rects, Pos2::new(45.0, 50.0)), Some(button));
+        assert_eq!(rect_at(&rects, Pos2::new(20.0, 20.0)), Some(panel));
+        assert_eq!(rect_at(&rects, Pos2::new(95.0, 50.0)), None);
+    }
+
+    #[test]
+    fn thick_border_is_one_element() {
+        let mut img = RgbaImage::from_pixel(100, 80, WHITE);
+        // The outside and inside of the border are 3 pixels apart.
+        border(&mut img, (20, 20), (70, 60), 3);
+        assert_eq!(find_rects(&img), [rect(20.0, 20.0, 70.0, 60.0)]);
+    }
+
+    #[test]
+    fn too_small() {
+        let mut img = RgbaImage::from_pixel(40, 40, WHITE);
+        fill(&mut img, (10, 10), (16, 30), DARK);
+        assert_eq!(find_rects(&img), []);
+    }
+}
@@ -0,0 +1,1176 @@
+use std::sync::{Arc, Weak};
+
+use eframe::{
+    egui::{self, emath::Rot2, Color32, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2},
+    epaint::{tessellator, PathStroke},
+};
+use xcap::image::{imageops, RgbaImage};
+
+use crate::{
+    annotation::{self, text_bounds, Annotation},
+    arrow,
+    barcode::{self, Code},
+    callout,
+    capture::{self, CaptureInfo},
+    crop::CropEdit,
+    detect,
+    frame::FrameStyle,
+    history::History,
+    magnifier, ocr,
+    redact::{self, Proposal, RedactStyle},
+    render,
+    snap::{self, Guides},
+    spotlight::{self, SpotArea, SpotEffect, SpotShape},
+    sticker::Sticker,
+    stroke::Piece,
+    text::{self, Fonts, TextLayout, TextStyle},
+    transform::Transform,
+    view::{View, Zoom},
+};
+
+/// Radius of the dots marking the draggable points of the selected annotation, in points.
+const HANDLE_RADIUS: f32 = 5.0;
+
+/// Which side of the current image another image is appended on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Right,
+    Below,
+}
+
+/// The undoable part of a document.
+pub struct Snapshot {
+    image: Arc<RgbaImage>,
+    crop: Option<Rect>,
+    annotations: Vec<Annotation>,
+}
+
+/// The flattened image shrunk for the frame preview, for what it was made from.
+struct FrameThumbnail {
+    image: Weak<RgbaImage>,
+    crop: Option<Rect>,
+    annotations: Vec<Annotation>,
+    thumbnail: RgbaImage,
+    /// How much it was shrunk.
+    scale: f32,
+}
+
+/// A capture or opened image with its own annotations and undo history, shown in a tab.
+pub struct Document {
+    pub info: CaptureInfo,
+    /// The image as captured, resized or rotated. Crops don't modify it, so they can be
+    /// changed later. Shared with the undo history.
+    pub image: Arc<RgbaImage>,
+    pub texture: egui::TextureHandle,
+    /// The kept part of `image`, in its pixels.
+    pub crop: Option<Rect>,
+    pub crop_edit: Option<CropEdit>,
+    pub annotations: Vec<Annotation>,
+    pub active_annotation_index: Option<usize>,
+    pub history: History<Snapshot>,
+    /// The state before the selected annotation is moved or restyled, recorded once it
+    /// actually changes.
+    pub move_checkpoint: Option<Snapshot>,
+    pub view: View,
+    /// The frame preview, for the style it was framed with.
+    pub frame_preview: Option<(FrameStyle, egui::TextureHandle)>,
+    /// Edited since it was last copied or saved.
+    pub dirty: bool,
+    /// Textures for image annotations, dropped once nothing refers to the image anymore.
+    layer_textures: Vec<(Weak<RgbaImage>, egui::TextureHandle)>,
+    /// What each desaturating spotlight paints, for the image and the annotations up to and
+    /// including the spotlight it was rendered for.
+    spotlight_textures: Vec<(Weak<RgbaImage>, Vec<Annotation>, egui::TextureHandle)>,
+    /// Darkening spotlights, for their areas and the region of the image on screen, at the
+    /// size they were drawn.
+    spotlight_masks: Vec<(Vec<SpotArea>, Rect, [u32; 2], egui::TextureHandle)>,
+    /// Stickers drawn at the size and in the colour they are used at.
+    sticker_textures: Vec<(Sticker, Vec2, Color32, egui::TextureHandle)>,
+    /// The edges of the UI in `image`, found the first time something snaps to them.
+    ui_edges: Option<(Weak<RgbaImage>, Guides)>,
+    /// The UI elements in `image`, found the first time boxes are drawn.
+    ui_elements: Option<(Weak<RgbaImage>, Vec<Rect>)>,
+    /// The text last recognized in `image`, forgotten once the image changes.
+    text: Option<(Weak<RgbaImage>, Vec<ocr::Line>)>,
+    /// The QR codes and barcodes in `image`, found when they are first asked for.
+    codes: Option<(Weak<RgbaImage>, Vec<Code>)>,
+    /// Made again once the image, crop or annotations change.
+    frame_thumbnail: Option<FrameThumbnail>,
+    /// Blurred redactions, for the image and the rect they cover.
+    redact_textures: Vec<(Weak<RgbaImage>, Rect, egui::TextureHandle)>,
+    /// What auto-redact found, waiting to be accepted or dismissed.
+    pub redactions: Option<Vec<Proposal>>,
+}
+
+pub fn load_texture(ctx: &egui::Context, name: &str, img: &RgbaImage) -> egui::TextureHandle {
+    let color_img = egui::ColorImage::from_rgba_unmultiplied(
+        [img.width() as usize, img.height() as usize],
+        img.as_flat_samples().as_slice(),
+    );
+    ctx.load_texture(name, color_img, egui::TextureOptions::default())
+}
+
+pub fn image_bounds(img: &RgbaImage) -> Rect {
+    Rect::from_min_size(
+        Pos2::ZERO,
+        Vec2::new(img.width() as f32, img.height() as f32),
+    )
+}
+
+impl Document {
+    pub fn new(image: RgbaImage, info: CaptureInfo, ctx: &egui::Context) -> Self {
+        Self {
+            info,
+            texture: load_texture(ctx, "screenshot", &image),
+            image: Arc::new(image),
+            crop: None,
+            crop_edit: None,
+            annotations: Vec::new(),
+            active_annotation_index: None,
+            history: History::default(),
+            move_checkpoint: None,
+            view: View::default(),
+            frame_preview: None,
+            frame_thumbnail: None,
+            dirty: false,
+            layer_textures: Vec::new(),
+            spotlight_textures: Vec::new(),
+            spotlight_masks: Vec::new(),
+            sticker_textures: Vec::new(),
+            ui_edges: None,
+            ui_elements: None,
+            text: None,
+            codes: None,
+            redact_textures: Vec::new(),
+            redactions: None,
+        }
+    }
+
+    /// The part of `image` shown on the canvas, in its pixels: the crop, unless the crop is
+    /// being edited.
+    pub fn visible_region(&self) -> Rect {
+        match (&self.crop_edit, self.crop) {
+            (None, Some(crop)) => crop,
+            _ => image_bounds(&self.image),
+        }
+    }
+
+    pub fn ui_to_image(&self, ui_pos: Pos2, image_rect: Rect) -> Pos2 {
+        let region = self.visible_region();
+        let normalized = (ui_pos - image_rect.min) / image_rect.size();
+        region.min + normalized * region.size()
+    }
+
+    pub fn image_to_ui(&self, img_pos: Pos2, image_rect: Rect) -> Pos2 {
+        let region = self.visible_region();
+        let normalized = (img_pos - region.min) / region.size();
+        image_rect.min + normalized * image_rect.size()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            image: self.image.clone(),
+            crop: self.crop,
+            annotations: self.annotations.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot, ctx: &egui::Context) {
+        if !Arc::ptr_eq(&self.image, &snapshot.image) {
+            self.texture = load_texture(ctx, "screenshot", &snapshot.image);
+            self.view.set_zoom(Zoom::Fit);
+        }
+        self.image = snapshot.image;
+        self.crop = snapshot.crop;
+        self.annotations = snapshot.annotations;
+        self.active_annotation_index = None;
+        self.dirty = true;
+    }
+
+    /// Records the current state for undo; call right before changing it.
+    pub fn checkpoint(&mut self) {
+        let snapshot = self.snapshot();
+        self.history.push(snapshot);
+        self.dirty = true;
+    }
+
+    /// Records the state from before the selected annotation started moving.
+    pub fn commit_move(&mut self) {
+        if let Some(snapshot) = self.move_checkpoint.take() {
+            self.history.push(snapshot);
+            self.dirty = true;
+        }
+    }
+
+    pub fn undo(&mut self, ctx: &egui::Context) {
+        if let Some(previous) = self.history.undo(self.snapshot()) {
+            self.restore(previous, ctx);
+        }
+    }
+
+    pub fn redo(&mut self, ctx: &egui::Context) {
+        if let Some(next) = self.history.redo(self.snapshot()) {
+            self.restore(next, ctx);
+        }
+    }
+
+    /// Applies `transforms` to the image, its crop and its annotations as one undoable step.
+    pub fn apply_transforms(&mut self, transforms: &[Transform], ctx: &egui::Context) {
+        self.checkpoint();
+        self.crop_edit = None;
+        let mut img = (*self.image).clone();
+        for transform in transforms {
+            let size = image_bounds(&img).size();
+            let scale = transform.scale(size);
+            img = transform.apply(&img);
+            let bounds = image_bounds(&img);
+            self.crop = self
+                .crop
+                .map(|crop| {
+                    let r = transform.map_rect(crop, size).intersect(bounds);
+                    Rect::from_min_max(r.min.round(), r.max.round())
+                })
+                .filter(|crop| *crop != bounds);
+            for ann in &mut self.annotations {
+                ann.transform(|p| transform.map(p, size), scale);
+            }
+        }
+        self.texture = load_texture(ctx, "screenshot", &img);
+        self.image = Arc::new(img);
+        self.view.set_zoom(Zoom::Fit);
+    }
+
+    /// Layers `img` over the middle of the visible image, shrunk to fit if needed.
+    pub fn insert_layer(&mut self, img: RgbaImage) {
+        let region = self.visible_region();
+        let size = Vec2::new(img.width() as f32, img.height() as f32);
+        let fit = (region.size() * 0.8 / size).min_elem().min(1.0);
+        self.checkpoint();
+        self.annotations.push(Annotation::Image {
+            rect: Rect::from_center_size(region.center(), size * fit),
+            image: Arc::new(img),
+        });
+        self.active_annotation_index = Some(self.annotations.len() - 1);
+    }
+
+    /// Puts `img` next to the visible image, growing the canvas to fit both and filling any
+    /// gap with `fill`. The appended image becomes a layer, so it can still be moved afterwards.
+    pub fn append_image(&mut self, img: RgbaImage, side: Side, fill: Color32, ctx: &egui::Context) {
+        let current = self.visible_region().size();
+        let (width, height) = (img.width(), img.height());
+        let (right, bottom, pos) = match side {
+            Side::Right => (
+                width,
+                height.saturating_sub(current.y as u32),
+                Pos2::new(current.x, 0.0),
+            ),
+            Side::Below => (
+                width.saturating_sub(current.x as u32),
+                height,
+                Pos2::new(0.0, current.y),
+            ),
+        };
+        let mut transforms: Vec<Transform> = self
+            .crop
+            .map(|rect| Transform::Crop { rect })
+            .into_iter()
+            .collect();
+        transforms.push(Transform::Expand {
+            left: 0,
+            top: 0,
+            right,
+            bottom,
+            fill,
+        });
+        // One undo step for the expansion and the new layer.
+        self.apply_transforms(&transforms, ctx);
+        self.annotations.push(Annotation::Image {
+            rect: Rect::from_min_size(pos, Vec2::new(width as f32, height as f32)),
+            image: Arc::new(img),
+        });
+    }
+
+    pub fn sync_layer_textures(&mut self, ctx: &egui::Context) {
+        self.layer_textures
+            .retain(|(img, _)| img.strong_count() > 0);
+        for ann in &self.annotations {
+            let Annotation::Image { image, .. } = ann else {
+                continue;
+            };
+            if self
+                .layer_textures
+                .iter()
+                .any(|(img, _)| img.as_ptr() == Arc::as_ptr(image))
+            {
+                continue;
+            }
+            let texture = load_texture(ctx, "layer", image);
+            self.layer_textures.push((Arc::downgrade(image), texture));
+        }
+    }
+
+    /// Finds the edges of the UI in the image again if it changed since they were found.
+    pub fn sync_ui_edges(&mut self) {
+        let image = Arc::downgrade(&self.image);
+        if !self
+            .ui_edges
+            .as_ref()
+            .is_some_and(|(img, _)| img.ptr_eq(&image))
+        {
+            self.ui_edges = Some((image, snap::detect_edges(&self.image, snap::MIN_EDGE)));
+        }
+    }
+
+    /// Finds the UI elements in the image again if it changed since they were found.
+    pub fn sync_ui_elements(&mut self) {
+        let image = Arc::downgrade(&self.image);
+        if !self
+            .ui_elements
+            .as_ref()
+            .is_some_and(|(img, _)| img.ptr_eq(&image))
+        {
+            self.ui_elements = Some((image, detect::find_rects(&self.image)));
+        }
+    }
+
+    /// The smallest UI element under `p` in the image, if they have been found.
+    pub fn ui_element_at(&self, p: Pos2) -> Option<Rect> {
+        let (_, rects) = self.ui_elements.as_ref()?;
+        detect::rect_at(rects, p)
+    }
+
+    pub fn set_text(&mut self, lines: Vec<ocr::Line>) {
+        self.text = Some((Arc::downgrade(&self.image), lines));
+    }
+
+    /// The text recognized in the image, unless the image changed since.
+    pub fn text_lines(&self) -> &[ocr::Line] {
+        match &self.text {
+            Some((img, lines)) if img.ptr_eq(&Arc::downgrade(&self.image)) => lines,
+            _ => &[],
+        }
+    }
+
+    /// Looks for QR codes and barcodes in the image again if it changed since they were
+    /// looked for.
+    pub fn sync_codes(&mut self) {
+        let image = Arc::downgrade(&self.image);
+        if !self
+            .codes
+            .as_ref()
+            .is_some_and(|(img, _)| img.ptr_eq(&image))
+        {
+            self.codes = Some((image, barcode::scan(&self.image)));
+        }
+    }
+
+    /// The QR codes and barcodes in the visible part of the image, if they have been looked
+    /// for since it last changed.
+    pub fn codes(&self) -> Vec<&Code> {
+        let (image, visible) = (Arc::downgrade(&self.image), self.visible_region());
+        self.codes
+            .iter()
+            .filter(|(img, _)| img.ptr_eq(&image))
+            .flat_map(|(_, codes)| codes)
+            .filter(|c| visible.intersects(c.rect))
+            .collect()
+    }
+
+    /// Marks parts of the image, like where text was found, in a see-through `color`.
+    pub fn draw_highlights(
+        &self,
+        painter: &Painter,
+        highlights: impl IntoIterator<Item = Rect>,
+        color: Color32,
+        rect: Rect,
+    ) {
+        for r in highlights {
+            let r =
+                Rect::from_min_max(self.image_to_ui(r.min, rect), self.image_to_ui(r.max, rect))
+                    .expand(2.0);
+            painter.rect(
+                r,
+                2.0,
+                color.gamma_multiply(0.3),
+                Stroke::new(1.5, color),
+                StrokeKind::Outside,
+            );
+        }
+    }
+
+    /// What to snap to: the edges and centre of the visible image, the edges and centres of
+    /// the annotations except the one at `skip`, and the edges of the UI if they have been
+    /// found.
+    pub fn guides(&self, skip: Option<usize>) -> Guides {
+        let mut guides = Guides::new(self.visible_region());
+        for (i, ann) in self.annotations.iter().enumerate() {
+            if Some(i) != skip {
+                guides.add_rect(ann.bounds());
+            }
+        }
+        if let Some((_, edges)) = &self.ui_edges {
+            guides.extend(edges);
+        }
+        guides
+    }
+
+    /// Draws the guides something snapped to.
+    pub fn draw_snap_lines(&self, painter: &Painter, lines: &[[Pos2; 2]], rect: Rect) {
+        for [from, to] in lines {
+            painter.line_segment(
+                [self.image_to_ui(*from, rect), self.image_to_ui(*to, rect)],
+                Stroke::new(1.0, Color32::from_rgb(0xFF, 0x2D, 0xC8)),
+            );
+        }
+    }
+
+    /// Draws darkening spotlights again once their areas or the view change, at screen
+    /// resolution for the part of the canvas `rect` inside `clip`. Desaturating ones depend
+    /// on the pixels below, so they are rendered again like the export once anything
+    /// underneath them changes, but not in the middle of a drag.
+    pub fn sync_spotlight_textures(&mut self, ctx: &egui::Context, rect: Rect, clip: Rect) {
+        let view = rect.intersect(clip);
+        let region = Rect::from_min_max(
+            self.ui_to_image(view.min, rect),
+            self.ui_to_image(view.max, rect),
+        );
+        // No finer than the image itself when zoomed in.
+        let size = (view.size() * ctx.pixels_per_point())
+            .min(region.size())
+            .ceil();
+        let size = [size.x.max(1.0) as u32, size.y.max(1.0) as u32];
+        let darkened: Vec<&[SpotArea]> = self
+            .annotations
+            .iter()
+            .filter_map(|ann| match ann {
+                Annotation::Spotlight {
+                    areas,
+                    effect: SpotEffect::Darken,
+                    ..
+                } => Some(areas.as_slice()),
+                _ => None,
+            })
+            .collect();
+        self.spotlight_masks.retain(|(areas, r, s, _)| {
+            *r == region && *s == size && darkened.contains(&areas.as_slice())
+        });
+        if view.is_positive() {
+            for areas in darkened {
+                if self.spotlight_masks.iter().any(|(a, ..)| a == areas) {
+                    continue;
+                }
+                let mask = spotlight::mask(areas, region, size[0], size[1]);
+                let texture = load_texture(ctx, "spotlight mask", &mask);
+                self.spotlight_masks
+                    .push((areas.to_vec(), region, size, texture));
+            }
+        }
+        if self.move_checkpoint.is_some() {
+            return;
+        }
+        let image = Arc::downgrade(&self.image);
+        let annotations = &self.annotations;
+        self.spotlight_textures
+            .retain(|(img, below, _)| img.ptr_eq(&image) && annotations.starts_with(below));
+        for (i, ann) in annotations.iter().enumerate() {
+            let Annotation::Spotlight {
+                areas,
+                effect: effect @ SpotEffect::Desaturate,
+                strength,
+            } = ann
+            else {
+                continue;
+            };
+            let below = &annotations[..=i];
+            if self.spotlight_textures.iter().any(|(_, b, _)| b == below) {
+                continue;
+            }
+            let overlay = spotlight::overlay(
+                &render::render_annotations(&self.image, &annotations[..i]),
+                areas,
+                *effect,
+                *strength,
+            );
+            let texture = load_texture(ctx, "spotlight", &overlay);
+            self.spotlight_textures
+                .push((image.clone(), below.to_vec(), texture));
+        }
+    }
+
+    /// Draws stickers again at their new size or colour, the same way the export does.
+    pub fn sync_sticker_textures(&mut self, ctx: &egui::Context) {
+        let used: Vec<(&Sticker, Vec2, Color32)> = self
+            .annotations
+            .iter()
+            .filter_map(|ann| match ann {
+                Annotation::Sticker {
+                    sticker,
+                    rect,
+                    color,
+                    ..
+                } => Some((sticker, rect.size(), *color)),
+                _ => None,
+            })
+            .collect();
+        self.sticker_textures
+            .retain(|(s, size, color, _)| used.contains(&(s, *size, *color)));
+        for (sticker, size, color) in used {
+            if self
+                .sticker_textures
+                .iter()
+                .any(|(s, sz, c, _)| (s, *sz, *c) == (sticker, size, color))
+            {
+                continue;
+            }
+            if let Some(pixmap) = sticker.render(size, color) {
+                let texture = load_texture(ctx, "sticker", &render::from_pixmap(&pixmap));
+                self.sticker_textures
+                    .push((sticker.clone(), size, color, texture));
+            }
+        }
+    }
+
+    /// Blurs the image again under redactions that were moved or resized, the same way the
+    /// export does.
+    pub fn sync_redact_textures(&mut self, ctx: &egui::Context) {
+        let image = Arc::downgrade(&self.image);
+        let used: Vec<Rect> = self
+            .annotations
+            .iter()
+            .filter_map(|ann| match ann {
+                Annotation::Redact {
+                    rect,
+                    style: RedactStyle::Blur,
+                } => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        self.redact_textures
+            .retain(|(img, r, _)| img.ptr_eq(&image) && used.contains(r));
+        let bounds = image_bounds(&self.image);
+        for rect in used {
+            if self.redact_textures.iter().any(|(_, r, _)| *r == rect) {
+                continue;
+            }
+            let Some(part) =
+                redact::pixel_rect(rect, bounds).and_then(|area| capture::crop(&self.image, area))
+            else {
+                continue;
+            };
+            let texture = load_texture(ctx, "redaction", &redact::blur(&part));
+            self.redact_textures.push((image.clone(), rect, texture));
+        }
+    }
+
+    fn sticker_texture(
+        &self,
+        sticker: &Sticker,
+        size: Vec2,
+        color: Color32,
+    ) -> Option<&egui::TextureHandle> {
+        self.sticker_textures
+            .iter()
+            .find(|(s, sz, c, _)| (s, *sz, *c) == (sticker, size, color))
+            .map(|(_, _, _, texture)| texture)
+    }
+
+    /// What a desaturating spotlight paints. In the middle of a drag, what it painted before
+    /// anything started moving.
+    fn spotlight_texture(&self, ann: &Annotation) -> Option<&egui::TextureHandle> {
+        let index = self.annotations.iter().position(|a| std::ptr::eq(a, ann));
+        self.spotlight_textures
+            .iter()
+            .find(|(_, below, _)| below.last() == Some(ann) && self.annotations.starts_with(below))
+            .or_else(|| {
+                let index = index.filter(|_| self.move_checkpoint.is_some())?;
+                self.spotlight_textures
+                    .iter()
+                    .find(|(_, below, _)| below.len() == index + 1)
+            })
+            .map(|(_, _, texture)| texture)
+    }
+
+    fn spotlight_mask(&self, areas: &[SpotArea]) -> Option<(Rect, &egui::TextureHandle)> {
+        self.spotlight_masks
+            .iter()
+            .find(|(a, ..)| a == areas)
+            .map(|(_, region, _, texture)| (*region, texture))
+    }
+
+    fn layer_texture(&self, image: &Arc<RgbaImage>) -> Option<&egui::TextureHandle> {
+        self.layer_textures
+            .iter()
+            .find(|(img, _)| img.as_ptr() == Arc::as_ptr(image))
+            .map(|(_, texture)| texture)
+    }
+
+    /// The cropped image with annotations baked in.
+    pub fn flattened_image(&self) -> Option<RgbaImage> {
+        let img = render::render_annotations(&self.image, &self.annotations);
+        match self.crop {
+            Some(crop) => capture::crop(&img, crop),
+            None => Some(img),
+        }
+    }
+
+    /// Shrinks the flattened image to fit in `size` pixels for the frame preview again once
+    /// the image, crop or annotations change, but not in the middle of a drag. The preview
+    /// is framed again from the new thumbnail.
+    pub fn sync_frame_thumbnail(&mut self, size: u32) {
+        let image = Arc::downgrade(&self.image);
+        let current = self.frame_thumbnail.as_ref().is_some_and(|t| {
+            t.image.ptr_eq(&image) && t.crop == self.crop && t.annotations == self.annotations
+        });
+        if current || (self.frame_thumbnail.is_some() && self.move_checkpoint.is_some()) {
+            return;
+        }
+        self.frame_preview = None;
+        self.frame_thumbnail = self.flattened_image().map(|flat| {
+            let scale = (size as f32 / flat.width().max(flat.height()) as f32).min(1.0);
+            let thumbnail = imageops::thumbnail(
+                &flat,
+                (flat.width() as f32 * scale).round().max(1.0) as u32,
+                (flat.height() as f32 * scale).round().max(1.0) as u32,
+            );
+            FrameThumbnail {
+                image,
+                crop: self.crop,
+                annotations: self.annotations.clone(),
+                thumbnail,
+                scale,
+            }
+        });
+    }
+
+    /// The thumbnail the frame preview is made from, and how much it was shrunk.
+    pub fn frame_thumbnail(&self) -> Option<(&RgbaImage, f32)> {
+        self.frame_thumbnail
+            .as_ref()
+            .map(|t| (&t.thumbnail, t.scale))
+    }
+
+    /// Highlights `ann` as the selected annotation, underneath the annotation itself.
+    fn draw_selection(&self, painter: &Painter, ann: &Annotation, rect: Rect, scale: f32) {
+        match ann {
+            Annotation::Arrow {
+                start,
+                end,
+                control,
+                ..
+            } => {
+                painter.add(egui::Shape::line(
+                    arrow::shaft_points(*start, *end, *control)
+                        .into_iter()
+                        .map(|p| self.image_to_ui(p, rect))
+                        .collect(),
+                    Stroke::new(10.0 * scale, Color32::from_white_alpha(30)),
+                ));
+            }
+            Annotation::Rect { rect: r, angle, .. } => {
+                self.fill_rotated(
+                    painter,
+                    (*r, *angle),
+                    2.0,
+                    Color32::from_white_alpha(20),
+                    rect,
+                );
+            }
+            Annotation::Text {
+                pos,
+                text,
+                size,
+                style,
+                angle,
+                ..
+            } => {
+                let r = text_bounds(*pos, text, *size, style);
+                self.fill_rotated(
+                    painter,
+                    (r, *angle),
+                    4.0,
+                    Color32::from_white_alpha(30),
+                    rect,
+                );
+            }
+            Annotation::Callout {
+                rect: r,
+                tail,
+                shape,
+                angle,
+                ..
+            } => {
+                let outline = callout::geometry(*r, *shape, *tail, *angle).outline;
+                painter.add(egui::Shape::closed_line(
+                    outline.iter().map(|p| self.image_to_ui(*p, rect)).collect(),
+                    Stroke::new(6.0, Color32::from_white_alpha(40)),
+                ));
+            }
+            Annotation::Image { rect: r, .. } | Annotation::Redact { rect: r, .. } => {
+                painter.rect_stroke(
+                    Rect::from_min_max(
+                        self.image_to_ui(r.min, rect),
+                        self.image_to_ui(r.max, rect),
+                    ),
+                    0.0,
+                    Stroke::new(2.0, Color32::from_white_alpha(120)),
+                    StrokeKind::Outside,
+                );
+            }
+            Annotation::Magnifier {
+                source,
+                centre,
+                zoom,
+                ..
+            } => {
+                for r in [*source, magnifier::inset(*source, *centre, *zoom)] {
+                    painter.rect_filled(
+                        Rect::from_min_max(
+                            self.image_to_ui(r.min, rect),
+                            self.image_to_ui(r.max, rect),
+                        )
+                        .expand(2.0),
+                        0.0,
+                        Color32::from_white_alpha(20),
+                    );
+                }
+            }
+            Annotation::Sticker { rect: r, angle, .. } => {
+                painter.add(egui::Shape::closed_line(
+                    annotation::corners(*r, *angle)
+                        .iter()
+                        .map(|&p| self.image_to_ui(p, rect))
+                        .collect(),
+                    Stroke::new(1.5, Color32::from_white_alpha(120)),
+                ));
+            }
+            // The overlay would hide a highlight underneath; areas are outlined on top.
+            Annotation::Spotlight { .. } => {}
+        }
+        if let Some(frame) = ann.frame() {
+            self.draw_rotate_arm(painter, frame, rect);
+        }
+    }
+
+    /// The line from the top edge of the upright rect `r`, turned by `angle` radians, to its
+    /// rotate handle.
+    fn draw_rotate_arm(&self, painter: &Painter, (r, angle): (Rect, f32), rect: Rect) {
+        painter.line_segment(
+            [
+                self.image_to_ui(annotation::rotate(r.center_top(), r.center(), angle), rect),
+                self.image_to_ui(annotation::rotate_handle(r, angle), rect),
+            ],
+            Stroke::new(1.5, Color32::from_white_alpha(120)),
+        );
+    }
+
+    /// Fills the upright rect `r` grown by `margin` points, turned by `angle` radians around
+    /// its centre.
+    fn fill_rotated(
+        &self,
+        painter: &Painter,
+        (r, angle): (Rect, f32),
+        margin: f32,
+        color: Color32,
+        rect: Rect,
+    ) {
+        let r = Rect::from_min_max(self.image_to_ui(r.min, rect), self.image_to_ui(r.max, rect));
+        painter.add(egui::Shape::convex_polygon(
+            annotation::corners(r.expand(margin), angle).to_vec(),
+            color,
+            Stroke::NONE,
+        ));
+    }
+
+    /// Outlines the edge of a spotlight area.
+    pub fn draw_spot_area(&self, painter: &Painter, area: &SpotArea, rect: Rect) {
+        let r = Rect::from_min_max(
+            self.image_to_ui(area.rect.min, rect),
+            self.image_to_ui(area.rect.max, rect),
+        );
+        let stroke = Stroke::new(1.5, Color32::from_white_alpha(160));
+        match area.shape {
+            SpotShape::Rect => {
+                painter.rect_stroke(r, 0.0, stroke, StrokeKind::Middle);
+            }
+            SpotShape::Ellipse => {
+                painter.add(egui::Shape::ellipse_stroke(
+                    r.center(),
+                    r.size() / 2.0,
+                    stroke,
+                ));
+            }
+        }
+    }
+
+    /// Paints text like `render::draw_annotation` does: box, outline, glyphs, then the
+    /// stroke that makes them bold, all turned by `angle` radians around `centre`. egui
+    /// draws the glyphs; the strokes follow the outlines of the same layout.
+    fn draw_text(
+        &self,
+        painter: &Painter,
+        (pos, text): (Pos2, &str),
+        layout: &TextLayout,
+        color: Color32,
+        (centre, angle): (Pos2, f32),
+        rect: Rect,
+    ) {
+        let scale = rect.width() / self.visible_region().width();
+        let to_ui = |p: Pos2| self.image_to_ui(annotation::rotate(p, centre, angle), rect);
+        let (size, style) = (layout.font_size(), layout.style());
+        if let Some(fill) = style.background {
+            let mut outline = Vec::new();
+            tessellator::path::rounded_rectangle(
+                &mut outline,
+                layout.outer_rect(pos),
+                TextStyle::box_rounding(size).into(),
+            );
+            painter.add(egui::Shape::convex_polygon(
+                outline.into_iter().map(to_ui).collect(),
+                fill,
+                Stroke::NONE,
+            ));
+        }
+        let contours: Vec<Vec<Pos2>> = if style.outline.is_some() || style.bold {
+            layout
+                .path(Fonts::get(), pos)
+                .map_or_else(Vec::new, |path| {
+                    text::contours(&path)
+                        .into_iter()
+                        .map(|c| c.into_iter().map(to_ui).collect())
+                        .collect()
+                })
+        } else {
+            Vec::new()
+        };
+        let stroke_contours = |width: f32, color: Color32| {
+            for contour in &contours {
+                painter.add(egui::Shape::closed_line(
+                    contour.clone(),
+                    Stroke::new(width * scale, color),
+                ));
+            }
+        };
+        if let Some(outline) = style.outline {
+            stroke_contours(TextStyle::outline_width(size), outline);
+        }
+        for (line, start) in layout.lines(text) {
+            let format = egui::TextFormat {
+                font_id: egui::FontId::new(size * scale, style.font.family()),
+                color,
+                italics: style.italic,
+                ..egui::TextFormat::default()
+            };
+            let galley = painter.layout_job(egui::text::LayoutJob::single_section(
+                line.to_string(),
+                format,
+            ));
+            painter.add(
+                egui::epaint::TextShape::new(to_ui(pos + start.to_vec2()), galley, color)
+                    .with_angle(angle),
+            );
+        }
+        if let Some(width) = style.bold_width(size) {
+            stroke_contours(width, color);
+        }
+    }
+
+    /// Draws the pieces of a stroked shape, shared with export. Ribbons are tessellated by
+    /// hand, as egui strokes only have butt caps and miter joins.
+    fn draw_pieces(
+        &self,
+        painter: &Painter,
+        pieces: Vec<Piece>,
+        width: f32,
+        color: Color32,
+        rect: Rect,
+    ) {
+        let scale = rect.width() / self.visible_region().width();
+        let feathering = 1.0 / painter.ctx().pixels_per_point();
+        let stroke = PathStroke::new(width * scale, color);
+        for piece in pieces {
+            match piece {
+                Piece::Ribbon { sections, closed } => {
+                    let mut path = tessellator::Path::default();
+                    for (centre, offset) in sections {
+                        path.add_point(self.image_to_ui(centre, rect), offset / (width / 2.0));
+                    }
+                    let mut mesh = egui::Mesh::default();
+                    if closed {
+                        path.stroke_closed(feathering, &stroke, &mut mesh);
+                    } else {
+                        path.stroke_open(feathering, &stroke, &mut mesh);
+                    }
+                    painter.add(mesh);
+                }
+                Piece::Fill(points) => {
+                    painter.add(egui::Shape::convex_polygon(
+                        points
+                            .into_iter()
+                            .map(|p| self.image_to_ui(p, rect))
+                            .collect(),
+                        color,
+                        Stroke::NONE,
+                    ));
+                }
+                Piece::Dot(centre) => {
+                    painter.circle_filled(
+                        self.image_to_ui(centre, rect),
+                        width * scale / 2.0,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Fills the body and tail separately, as egui only fills convex shapes, then outlines
+    /// them together.
+    fn draw_callout(&self, painter: &Painter, ann: &Annotation, rect: Rect) {
+        let Annotation::Callout {
+            rect: r,
+            tail,
+            text,
+            shape,
+            color,
+            fill,
+            size,
+            angle,
+        } = ann
+        else {
+            return;
+        };
+        let scale = rect.width() / self.visible_region().width();
+        let to_ui = |points: &[Pos2]| -> Vec<Pos2> {
+            points.iter().map(|p| self.image_to_ui(*p, rect)).collect()
+        };
+        let geometry = callout::geometry(*r, *shape, *tail, *angle);
+        painter.add(egui::Shape::convex_polygon(
+            to_ui(&geometry.body),
+            *fill,
+            Stroke::NONE,
+        ));
+        if let Some(tail) = geometry.tail {
+            painter.add(egui::Shape::convex_polygon(
+                to_ui(&tail),
+                *fill,
+                Stroke::NONE,
+            ));
+        }
+        painter.add(egui::Shape::closed_line(
+            to_ui(&geometry.outline),
+            Stroke::new(callout::border_width(*size) * scale, *color),
+        ));
+        let (pos, layout) = callout::layout_text(*r, *shape, text, *size);
+        self.draw_text(
+            painter,
+            (pos, text),
+            &layout,
+            *color,
+            (r.center(), *angle),
+            rect,
+        );
+    }
+
+    /// Draws the enlarged source into the inset, then the borders and connector.
+    fn draw_magnifier(&self, painter: &Painter, ann: &Annotation, rect: Rect) {
+        let Annotation::Magnifier {
+            source,
+            centre,
+            zoom,
+            ..
+        } = ann
+        else {
+            return;
+        };
+        let bounds = image_bounds(&self.image);
+        let inset = magnifier::inset(*source, *centre, *zoom);
+        if let Some((shown, r)) = magnifier::visible(*source, inset, bounds) {
+            painter.image(
+                self.texture.id(),
+                Rect::from_min_max(self.image_to_ui(r.min, rect), self.image_to_ui(r.max, rect)),
+                Rect::from_min_max(
+                    (shown.min.to_vec2() / bounds.size()).to_pos2(),
+                    (shown.max.to_vec2() / bounds.size()).to_pos2(),
+                ),
+                Color32::WHITE,
+            );
+        }
+        if let Some((pieces, width, color)) = ann.stroke_pieces() {
+            self.draw_pieces(painter, pieces, width, color, rect);
+        }
+    }
+
+    /// Draws a sticker's cached texture, turned by its angle.
+    fn draw_sticker(&self, painter: &Painter, ann: &Annotation, rect: Rect) {
+        let Annotation::Sticker {
+            sticker,
+            rect: r,
+            angle,
+            color,
+        } = ann
+        else {
+            return;
+        };
+        let Some(texture) = self.sticker_texture(sticker, r.size(), *color) else {
+            return;
+        };
+        let r_ui = Rect::from_min_max(self.image_to_ui(r.min, rect), self.image_to_ui(r.max, rect));
+        let mut mesh = egui::Mesh::with_texture(texture.id());
+        mesh.add_rect_with_uv(
+            r_ui,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+        mesh.rotate(Rot2::from_angle(*angle), r_ui.center());
+        painter.add(egui::Shape::mesh(mesh));
+    }
+
+    /// Draws a redaction as a black box or its cached blur.
+    fn draw_redaction(&self, painter: &Painter, ann: &Annotation, rect: Rect) {
+        let Annotation::Redact { rect: r, style } = ann else {
+            return;
+        };
+        match style {
+            RedactStyle::Black => {
+                painter.rect_filled(
+                    Rect::from_min_max(
+                        self.image_to_ui(r.min, rect),
+                        self.image_to_ui(r.max, rect),
+                    ),
+                    0.0,
+                    Color32::BLACK,
+                );
+            }
+            RedactStyle::Blur => {
+                let area = redact::pixel_rect(*r, image_bounds(&self.image));
+                let texture = self
+                    .redact_textures
+                    .iter()
+                    .find(|(_, cached, _)| cached == r)
+                    .map(|(_, _, texture)| texture);
+                if let (Some(area), Some(texture)) = (area, texture) {
+                    painter.image(
+                        texture.id(),
+                        Rect::from_min_max(
+                            self.image_to_ui(area.min, rect),
+                            self.image_to_ui(area.max, rect),
+                        ),
+                        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn draw_annotation(&self, painter: &Painter, ann: &Annotation, rect: Rect, active: bool) {
+        let scale = rect.width() / self.visible_region().width();
+        if active {
+            self.draw_selection(painter, ann, rect, scale);
+        }
+        match ann {
+            Annotation::Arrow { .. } | Annotation::Rect { .. } => {
+                if let Some((pieces, width, color)) = ann.stroke_pieces() {
+                    self.draw_pieces(painter, pieces, width, color, rect);
+                }
+            }
+            Annotation::Magnifier { .. } => self.draw_magnifier(painter, ann, rect),
+            Annotation::Text {
+                pos,
+                text,
+                color,
+                size,
+                style,
+                angle,
+            } => {
+                let layout = Fonts::get().layout(text, *size, style);
+                let centre = text_bounds(*pos, text, *size, style).center();
+                self.draw_text(
+                    painter,
+                    (*pos, text),
+                    &layout,
+                    *color,
+                    (centre, *angle),
+                    rect,
+                );
+            }
+            Annotation::Callout { .. } => self.draw_callout(painter, ann, rect),
+            Annotation::Image { rect: r, image } => {
+                let r = Rect::from_min_max(
+                    self.image_to_ui(r.min, rect),
+                    self.image_to_ui(r.max, rect),
+                );
+                if let Some(texture) = self.layer_texture(image) {
+                    painter.image(
+                        texture.id(),
+                        r,
+                        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+                }
+            }
+            Annotation::Sticker { .. } => self.draw_sticker(painter, ann, rect),
+            Annotation::Redact { .. } => self.draw_redaction(painter, ann, rect),
+            Annotation::Spotlight {
+                areas,
+                effect: SpotEffect::Darken,
+                strength,
+            } => {
+                if let Some((region, texture)) = self.spotlight_mask(areas) {
+                    painter.image(
+                        texture.id(),
+                        Rect::from_min_max(
+                            self.image_to_ui(region.min, rect),
+                            self.image_to_ui(region.max, rect),
+                        ),
+                        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                        Color32::WHITE.gamma_multiply(strength.clamp(0.0, 1.0)),
+                    );
+                }
+                if active {
+                    for area in areas {
+                        self.draw_spot_area(painter, area, rect);
+                    }
+                }
+            }
+            Annotation::Spotlight { areas, .. } => {
+                let size = image_bounds(&self.image).size();
+                let region = self.visible_region();
+                if let Some(texture) = self.spotlight_texture(ann) {
+                    painter.image(
+                        texture.id(),
+                        rect,
+                        Rect::from_min_max(
+                            (region.min.to_vec2() / size).to_pos2(),
+                            (region.max.to_vec2() / size).to_pos2(),
+                        ),
+                        Color32::WHITE,
+                    );
+                }
+                if active {
+                    for area in areas {
+                        self.draw_spot_area(painter, area, rect);
+                    }
+                }
+            }
+        }
+        if active {
+            for handle in ann.handles() {
+                painter.circle(
+                    self.image_to_ui(handle, rect),
+                    HANDLE_RADIUS,
+                    Color32::WHITE,
+                    Stroke::new(1.0, Color32::BLACK),
+                );
+            }
+        }
+    }
+
+    /// The handle of the active annotation under `pos_ui`, if any.
+    pub fn handle_at(&self, pos_ui: Pos2, rect: Rect) -> Option<usize> {
+        let ann = self.annotations.get(self.active_annotation_index?)?;
+        ann.handles()
+            .into_iter()
+            .position(|h| self.image_to_ui(h, rect).distance(pos_ui) <= HANDLE_RADIUS + 3.0)
+    }
+}
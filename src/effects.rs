@@ -0,0 +1,64 @@
+use eframe::egui::{Color32, Vec2};
+use xcap::image::{imageops, Rgba, RgbaImage};
+
+/// Coverage of the pixel centred at `(x, y)` by a `w`×`h` rounded rectangle, in `0..=1`.
+fn rounded_rect_coverage(x: f32, y: f32, w: f32, h: f32, radius: f32) -> f32 {
+    let (cx, cy) = (x.clamp(radius, w - radius), y.clamp(radius, h - radius));
+    let dist = (x - cx).hypot(y - cy);
+    (radius - dist + 0.5).clamp(0.0, 1.0)
+}
+
+/// Makes the corners of `img` transparent with an anti-aliased edge.
+pub fn round_corners(img: &mut RgbaImage, radius: f32) {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let radius = radius.min(w / 2.0).min(h / 2.0);
+    if radius <= 0.0 {
+        return;
+    }
+    let r = radius.ceil() as u32;
+    let corners = [
+        (0, 0),
+        (img.width().saturating_sub(r), 0),
+        (0, img.height().saturating_sub(r)),
+        (
+            img.width().saturating_sub(r),
+            img.height().saturating_sub(r),
+        ),
+    ];
+    for (x0, y0) in corners {
+        for y in y0..(y0 + r).min(img.height()) {
+            for x in x0..(x0 + r).min(img.width()) {
+                let coverage = rounded_rect_coverage(x as f32 + 0.5, y as f32 + 0.5, w, h, radius);
+                let p = img.get_pixel_mut(x, y);
+                p[3] = (f32::from(p[3]) * coverage).round() as u8;
+            }
+        }
+    }
+}
+
+/// Returns `img` on a larger transparent canvas with a blurred shadow of its alpha behind it.
+pub fn drop_shadow(img: &RgbaImage, blur: f32, offset: Vec2, color: Color32) -> RgbaImage {
+    let margin = (blur * 3.0 + offset.x.abs().max(offset.y.abs())).ceil() as u32;
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    // Transparent pixels carry the shadow colour so blurring doesn't darken the edges.
+    let mut shadow = RgbaImage::from_pixel(
+        img.width() + 2 * margin,
+        img.height() + 2 * margin,
+        Rgba([r, g, b, 0]),
+    );
+    let (dx, dy) = (
+        (margin as f32 + offset.x).round() as u32,
+        (margin as f32 + offset.y).round() as u32,
+    );
+    for (x, y, p) in img.enumerate_pixels() {
+        let alpha = (u16::from(p[3]) * u16::from(a) / 255) as u8;
+        shadow.put_pixel(x + dx, y + dy, Rgba([r, g, b, alpha]));
+    }
+    let mut out = if blur > 0.0 {
+        imageops::fast_blur(&shadow, blur)
+    } else {
+        shadow
+    };
+    imageops::overlay(&mut out, img, i64::from(margin), i64::from(margin));
+    out
+}
@@ -0,0 +1,263 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui::{Color32, Vec2};
+use serde::{Deserialize, Serialize};
+use tiny_skia::{
+    GradientStop, LinearGradient, Paint, PathBuilder, Pixmap, PixmapPaint, Point, Rect, Shader,
+    SpreadMode, Transform,
+};
+use xcap::image::{self, imageops, RgbaImage};
+
+use crate::{effects, render};
+
+const CHROME_HEIGHT: f32 = 32.0;
+const FALLBACK_BACKGROUND: Color32 = Color32::from_rgb(40, 44, 52);
+const SHADOW_OFFSET: Vec2 = Vec2::new(0.0, 10.0);
+const SHADOW_COLOR: Color32 = Color32::from_black_alpha(110);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Background {
+    Solid {
+        color: Color32,
+    },
+    /// Linear gradient; `angle` is in degrees, clockwise from left-to-right.
+    Gradient {
+        from: Color32,
+        to: Color32,
+        angle: f32,
+    },
+    /// Scaled to cover the whole canvas.
+    Image {
+        path: PathBuf,
+    },
+}
+
+impl Background {
+    pub const KINDS: [(&str, Self); 3] = [
+        (
+            "Solid",
+            Self::Solid {
+                color: FALLBACK_BACKGROUND,
+            },
+        ),
+        (
+            "Gradient",
+            Self::Gradient {
+                from: Color32::from_rgb(106, 90, 205),
+                to: Color32::from_rgb(255, 126, 179),
+                angle: 45.0,
+            },
+        ),
+        (
+            "Image",
+            Self::Image {
+                path: PathBuf::new(),
+            },
+        ),
+    ];
+}
+
+/// Fake window title bar drawn above the screenshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Chrome {
+    None,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameStyle {
+    pub padding: f32,
+    pub background: Background,
+    pub corner_radius: f32,
+    /// Blur radius of the drop shadow; `0` disables it.
+    pub shadow: f32,
+    pub chrome: Chrome,
+}
+
+impl Default for FrameStyle {
+    fn default() -> Self {
+        Self {
+            padding: 64.0,
+            background: Background::KINDS[1].1.clone(),
+            corner_radius: 10.0,
+            shadow: 24.0,
+            chrome: Chrome::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FramePreset {
+    pub name: String,
+    pub style: FrameStyle,
+}
+
+/// The background image last drawn, decoded once and kept at the size it was last drawn at,
+/// so previews don't read the file again whenever the style changes.
+#[derive(Default)]
+pub struct BackgroundCache {
+    /// The file decoded, even if it couldn't be.
+    path: Option<PathBuf>,
+    decoded: Option<image::DynamicImage>,
+    filled: Option<RgbaImage>,
+}
+
+impl BackgroundCache {
+    /// The image at `path` scaled to cover `width` × `height`, or `None` if it can't be read.
+    fn fill(&mut self, path: &Path, width: u32, height: u32) -> Option<RgbaImage> {
+        if self.path.as_deref() != Some(path) {
+            *self = Self {
+                path: Some(path.to_owned()),
+                decoded: image::open(path).ok(),
+                filled: None,
+            };
+        }
+        let decoded = self.decoded.as_ref()?;
+        if self
+            .filled
+            .as_ref()
+            .is_none_or(|filled| filled.dimensions() != (width, height))
+        {
+            self.filled = Some(
+                decoded
+                    .resize_to_fill(width, height, imageops::FilterType::Triangle)
+                    .to_rgba8(),
+            );
+        }
+        self.filled.clone()
+    }
+}
+
+fn with_chrome(img: &RgbaImage, chrome: Chrome, scale: f32) -> RgbaImage {
+    let bar = match chrome {
+        Chrome::None => return img.clone(),
+        Chrome::Light => Color32::from_rgb(232, 232, 232),
+        Chrome::Dark => Color32::from_rgb(43, 43, 43),
+    };
+    let height = (CHROME_HEIGHT * scale).round();
+    let (Some(mut pixmap), Some(content)) = (
+        Pixmap::new(img.width(), img.height() + height as u32),
+        render::to_pixmap(img),
+    ) else {
+        return img.clone();
+    };
+    if let Some(r) = Rect::from_xywh(0.0, 0.0, img.width() as f32, height) {
+        pixmap.fill_rect(r, &render::paint(bar), Transform::identity(), None);
+    }
+    let traffic_lights = [
+        Color32::from_rgb(255, 95, 87),
+        Color32::from_rgb(254, 188, 46),
+        Color32::from_rgb(40, 200, 64),
+    ];
+    for (i, color) in traffic_lights.into_iter().enumerate() {
+        let cx = (20.0 + 20.0 * i as f32) * scale;
+        if let Some(circle) = PathBuilder::from_circle(cx, height / 2.0, 6.0 * scale) {
+            pixmap.fill_path(
+                &circle,
+                &render::paint(color),
+                tiny_skia::FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+    pixmap.draw_pixmap(
+        0,
+        height as i32,
+        content.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+    render::from_pixmap(&pixmap)
+}
+
+fn background(
+    width: u32,
+    height: u32,
+    bg: &Background,
+    backgrounds: &mut BackgroundCache,
+) -> RgbaImage {
+    let Some(mut pixmap) = Pixmap::new(width, height) else {
+        return RgbaImage::new(width, height);
+    };
+    let (w, h) = (width as f32, height as f32);
+    let shader = match bg {
+        Background::Solid { color } => render::paint(*color).shader,
+        Background::Gradient { from, to, angle } => {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            // Half the extent of the canvas along the gradient direction.
+            let half = (w / 2.0 * cos).abs() + (h / 2.0 * sin).abs();
+            let (cx, cy) = (w / 2.0, h / 2.0);
+            LinearGradient::new(
+                Point::from_xy(cx - cos * half, cy - sin * half),
+                Point::from_xy(cx + cos * half, cy + sin * half),
+                vec![
+                    GradientStop::new(0.0, render::skia_color(*from)),
+                    GradientStop::new(1.0, render::skia_color(*to)),
+                ],
+                SpreadMode::Pad,
+                Transform::identity(),
+            )
+            .unwrap_or(Shader::SolidColor(render::skia_color(*from)))
+        }
+        Background::Image { path } => match backgrounds.fill(path, width, height) {
+            Some(img) => return img,
+            None => render::paint(FALLBACK_BACKGROUND).shader,
+        },
+    };
+    let paint = Paint {
+        shader,
+        ..Paint::default()
+    };
+    if let Some(r) = Rect::from_xywh(0.0, 0.0, w, h) {
+        pixmap.fill_rect(r, &paint, Transform::identity(), None);
+    }
+    render::from_pixmap(&pixmap)
+}
+
+impl FrameStyle {
+    /// Frames `img` with padding, background, rounded corners, shadow and chrome.
+    pub fn apply(&self, img: &RgbaImage) -> RgbaImage {
+        self.apply_scaled(img, 1.0, &mut BackgroundCache::default())
+    }
+
+    /// Like [`Self::apply`], with every pixel measurement multiplied by `scale`.
+    /// Used to render previews from a downscaled image, with the background image from
+    /// `backgrounds`.
+    pub fn apply_scaled(
+        &self,
+        img: &RgbaImage,
+        scale: f32,
+        backgrounds: &mut BackgroundCache,
+    ) -> RgbaImage {
+        let mut window = with_chrome(img, self.chrome, scale);
+        effects::round_corners(&mut window, self.corner_radius * scale);
+        let pad = (self.padding * scale).max(0.0).round() as u32;
+        let mut canvas = background(
+            window.width() + 2 * pad,
+            window.height() + 2 * pad,
+            &self.background,
+            backgrounds,
+        );
+        let (layer, margin) = if self.shadow > 0.0 {
+            let shadowed = effects::drop_shadow(
+                &window,
+                self.shadow * scale,
+                SHADOW_OFFSET * scale,
+                SHADOW_COLOR,
+            );
+            let margin = (shadowed.width() - window.width()) / 2;
+            (shadowed, margin)
+        } else {
+            (window, 0)
+        };
+        let offset = i64::from(pad) - i64::from(margin);
+        imageops::overlay(&mut canvas, &layer, offset, offset);
+        canvas
+    }
+}
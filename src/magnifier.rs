@@ -0,0 +1,83 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{Pos2, Rect, Vec2};
+
+use crate::stroke::{self, Piece, StrokeStyle};
+
+pub const ZOOM_RANGE: RangeInclusive<f32> = 1.0..=8.0;
+/// Space between a new inset and its source, relative to the source's width.
+const GAP: f32 = 0.25;
+
+/// Where `source` shows enlarged by `zoom`, centred on `centre`.
+pub fn inset(source: Rect, centre: Pos2, zoom: f32) -> Rect {
+    Rect::from_center_size(centre, source.size() * zoom)
+}
+
+/// The centre of the inset for a new magnifier: beside `source`, on the right unless it only
+/// fits within `bounds` on the left.
+pub fn place(source: Rect, zoom: f32, bounds: Rect) -> Pos2 {
+    let offset = source.width() * (0.5 + GAP + zoom / 2.0);
+    let right = source.center() + Vec2::new(offset, 0.0);
+    let left = source.center() - Vec2::new(offset, 0.0);
+    if inset(source, right, zoom).max.x > bounds.max.x
+        && inset(source, left, zoom).min.x >= bounds.min.x
+    {
+        left
+    } else {
+        right
+    }
+}
+
+/// The zoom that puts the bottom-right corner of the inset around `centre` nearest to
+/// `corner`.
+pub fn zoom_for_corner(source: Rect, centre: Pos2, corner: Pos2) -> f32 {
+    let half = source.size() / 2.0;
+    ((corner - centre).dot(half) / half.length_sq().max(f32::EPSILON))
+        .clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end())
+}
+
+/// The part of `source` within `bounds`, and the part of `inset` it fills. Nothing is shown
+/// for source pixels outside the image.
+pub fn visible(source: Rect, inset: Rect, bounds: Rect) -> Option<(Rect, Rect)> {
+    let shown = source.intersect(bounds);
+    if !shown.is_positive() {
+        return None;
+    }
+    let map = |p: Pos2| inset.min + (p - source.min) / source.size() * inset.size();
+    Some((shown, Rect::from_min_max(map(shown.min), map(shown.max))))
+}
+
+/// The point where the ray from the centre of `rect` along `dir` leaves it.
+fn exit(rect: Rect, dir: Vec2) -> Pos2 {
+    let half = rect.size() / 2.0;
+    let t = (half.x / dir.x.abs()).min(half.y / dir.y.abs());
+    rect.center() + dir * t
+}
+
+/// The borders around the source and the inset, stroked on the outside like boxes, and the
+/// line between them if `connector` is set and they don't overlap.
+pub fn pieces(source: Rect, inset: Rect, thickness: f32, connector: bool) -> Vec<Piece> {
+    let (source, inset) = (
+        source.expand(thickness / 2.0),
+        inset.expand(thickness / 2.0),
+    );
+    let style = StrokeStyle::default();
+    let mut pieces = Vec::new();
+    for r in [source, inset] {
+        let corners = [
+            r.left_top(),
+            r.right_top(),
+            r.right_bottom(),
+            r.left_bottom(),
+        ];
+        pieces.extend(stroke::pieces(&corners, true, thickness, &style));
+    }
+    let dir = (inset.center() - source.center()).normalized();
+    if connector && dir.is_finite() {
+        let (from, to) = (exit(source, dir), exit(inset, -dir));
+        if (to - from).dot(dir) > 0.0 {
+            pieces.extend(stroke::pieces(&[from, to], false, thickness, &style));
+        }
+    }
+    pieces
+}
@@ -6,17 +6,121 @@
     clippy::cast_sign_loss
 )]
 
+mod annotation;
+mod arrow;
+mod autosave;
+mod barcode;
+mod callout;
+mod capture;
+mod cli;
+mod crop;
+mod detect;
+mod document;
+mod effects;
+mod frame;
+mod history;
+mod magnifier;
+mod ocr;
+mod qr;
+mod recent;
+mod redact;
+mod render;
+mod settings;
+mod snap;
+mod spotlight;
+mod sticker;
+mod stroke;
+mod svg;
+mod text;
+mod transform;
+mod view;
+
+use std::{path::Path, sync::Arc};
+
 use arboard::Clipboard;
 use color_eyre::eyre::{eyre, Result};
 use eframe::egui;
-use egui::{Color32, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2};
-use xcap::{image, Monitor, Window};
+use egui::{
+    Color32, Key, KeyboardShortcut, Modifiers, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2,
+};
+use xcap::{image, Window};
+
+use annotation::Annotation;
+use arrow::{ArrowHead, ArrowShaft, ArrowStyle};
+use autosave::{Timestamp, PLACEHOLDERS};
+use callout::CalloutShape;
+use capture::CaptureInfo;
+use cli::{CliArgs, StartupCapture};
+use crop::{Aspect, CropEdit, Handle};
+use document::{image_bounds, load_texture, Document, Side};
+use frame::{Background, BackgroundCache, Chrome, FramePreset, FrameStyle};
+use ocr::OcrEngine;
+use recent::{RecentCapture, RecentCaptures};
+use redact::RedactStyle;
+use settings::{
+    AutoSave, AutoSaveTrigger, CapturePreset, PresetTarget, Settings, WindowMatch, WindowRule,
+};
+use spotlight::{SpotArea, SpotEffect, SpotShape};
+use sticker::{Stamp, Sticker, StickerFile, EMOJI};
+use stroke::{Cap, Dash, Join, StrokeStyle};
+use text::{TextAlign, TextFont, TextStyle};
+use transform::{Filter, ResizeSpec, Transform};
+use view::Zoom;
+
+/// Formats offered by "Open" and accepted by drag and drop.
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "bmp"];
+const OPEN_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::O);
+const CLOSE_TAB_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::W);
+/// Longer tab titles are shortened with an ellipsis.
+const TAB_TITLE_LEN: usize = 28;
+/// Sizes the text and callout tools offer, in image pixels.
+const TEXT_SIZE_RANGE: std::ops::RangeInclusive<f32> = 8.0..=160.0;
+const TEXT_EDITOR_ID: &str = "text_editor";
+/// Height of the thumbnails in the history panel, in UI points.
+const HISTORY_THUMBNAIL_HEIGHT: f32 = 56.0;
+const STICKER_THUMBNAIL_SIZE: f32 = 40.0;
+const STICKER_SIZE_RANGE: std::ops::RangeInclusive<f32> = 16.0..=1024.0;
+const PASTE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::V);
+const LAST_AREA_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::R);
+const FRAME_PREVIEW_SIZE: u32 = 360;
+const PASTE_LAYER_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::V);
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUTS: [KeyboardShortcut; 2] = [
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z),
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Y),
+];
+const ZOOM_FIT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Num0);
+const ZOOM_IN_SHORTCUTS: [KeyboardShortcut; 2] = [
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Plus),
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Equals),
+];
+const ZOOM_OUT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Minus);
+const ZOOM_STEP: f32 = 1.25;
+const ZOOM_PRESETS: [(&str, f32); 4] = [("50%", 0.5), ("100%", 1.0), ("200%", 2.0), ("400%", 4.0)];
+const PRESET_KEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tool {
     Arrow,
     Rect,
     Text,
+    Callout,
+    Spotlight,
+    Magnifier,
+    Sticker,
+    Redact,
+    Ocr,
     Crop,
 }
 
@@ -27,166 +131,353 @@ enum AppState {
     PickingArea,
 }
 
-#[derive(Debug, Clone)]
-enum Annotation {
-    Arrow {
-        start: Pos2,
-        end: Pos2,
-        color: Color32,
-        thickness: f32,
-    },
-    Rect {
-        rect: Rect,
-        color: Color32,
-        thickness: f32,
-    },
-    Text {
-        pos: Pos2,
-        text: String,
-        color: Color32,
-        size: f32,
-    },
+/// Where a newly captured or imported image goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Destination {
+    Replace,
+    Layer,
+    Append(Side),
+}
+
+/// Text or a callout being typed on the canvas. It takes on the tool's current color,
+/// size and style.
+struct TextDraft {
+    /// The annotation with the text typed so far.
+    ann: Annotation,
+    /// The annotation being edited, which is hidden meanwhile; `None` for a new one.
+    index: Option<usize>,
+}
+
+/// What is waiting for the user to confirm discarding unsaved changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Closing {
+    /// The active tab.
+    Tab,
+    App,
 }
 
 struct WindowInfo {
+    id: u32,
+    z: i32,
     rect: Rect,
     title: String,
     app_name: String,
 }
 
-impl Annotation {
-    fn label(&self) -> String {
-        match self {
-            Self::Arrow { .. } => "↗ Arrow".to_string(),
-            Self::Rect { .. } => "⬜ Box".to_string(),
-            Self::Text { text, .. } => format!("T \"{text}\""),
-        }
-    }
-
-    fn hit_test(&self, p: Pos2, threshold: f32) -> bool {
-        match self {
-            Self::Arrow { start, end, .. } => {
-                let line_vec = *end - *start;
-                let len_sq = line_vec.length_sq();
-                if len_sq < 1.0 {
-                    return p.distance(*start) < threshold;
-                }
-                let t = ((p - *start).dot(line_vec) / len_sq).clamp(0.0, 1.0);
-                p.distance(*start + line_vec * t) < threshold
-            }
-            Self::Rect { rect, .. } => {
-                rect.expand(threshold).contains(p)
-                    && (!rect.shrink(threshold).contains(p) || rect.contains(p))
-            }
-            Self::Text {
-                pos, text, size, ..
-            } => Rect::from_min_size(*pos, Vec2::new(text.len() as f32 * *size * 0.6, *size))
-                .expand(threshold)
-                .contains(p),
-        }
-    }
-
-    fn translate(&mut self, delta: Vec2) {
-        match self {
-            Self::Arrow { start, end, .. } => {
-                *start += delta;
-                *end += delta;
-            }
-            Self::Rect { rect, .. } => *rect = rect.translate(delta),
-            Self::Text { pos, .. } => *pos += delta,
-        }
-    }
-}
-
+#[allow(clippy::struct_excessive_bools)]
 struct SnapCrabApp {
-    image: Option<egui::TextureHandle>,
-    original_image: Option<image::RgbaImage>,
-    annotations: Vec<Annotation>,
+    tabs: Vec<Document>,
+    active_tab: usize,
+    closing: Option<Closing>,
+    /// Set once the user agreed to quit, so the next close request goes through.
+    quit_confirmed: bool,
+    recent: RecentCaptures,
+    show_history: bool,
+    /// History panel thumbnails by capture id; `None` if the thumbnail couldn't be loaded.
+    recent_thumbnails: Vec<(String, Option<egui::TextureHandle>)>,
     current_tool: Tool,
     current_color: Color32,
     stroke_thickness: f32,
+    arrow_style: ArrowStyle,
     text_size: f32,
+    text_style: TextStyle,
     drag_start: Option<Pos2>,
-    active_annotation_index: Option<usize>,
     show_layers: bool,
-    temp_text: String,
-    editing_text_pos: Option<Pos2>,
+    text_draft: Option<TextDraft>,
+    callout_shape: CalloutShape,
+    callout_fill: Color32,
+    spot_shape: SpotShape,
+    spot_effect: SpotEffect,
+    spot_strength: f32,
+    magnifier_zoom: f32,
+    magnifier_connector: bool,
+    sticker: Sticker,
+    /// Longer side of stickers placed with a click.
+    sticker_size: f32,
+    /// Stickers from the sticker folder, loaded when the picker is first shown.
+    folder_stickers: Option<Vec<Arc<StickerFile>>>,
+    sticker_thumbnails: Vec<(Sticker, Option<egui::TextureHandle>)>,
+    /// The handle of the selected annotation being dragged.
+    dragged_handle: Option<usize>,
+    /// The selected annotation as it was when dragging it started. Drags move it from there,
+    /// so snapping to a guide doesn't swallow small movements away from it.
+    drag_original: Option<Annotation>,
+    /// The guides the dragged annotation snapped to, in image pixels.
+    snap_lines: Vec<[Pos2; 2]>,
     state: AppState,
     fullscreen_bg: Option<egui::TextureHandle>,
     fullscreen_bg_image: Option<image::RgbaImage>,
     windows: Vec<WindowInfo>,
     hovered_window_index: Option<usize>,
+    controls: Vec<capture::ControlInfo>,
+    controls_window: Option<u32>,
+    settings: Settings,
+    show_settings: bool,
+    new_preset_name: String,
+    show_frame: bool,
+    new_frame_preset_name: String,
+    /// The frame's background image, kept for previews.
+    frame_backgrounds: BackgroundCache,
+    show_resize: bool,
+    resize: ResizeSpec,
+    resize_filter: Filter,
+    show_expand: bool,
+    /// Left, top, right and bottom margins for "Expand Canvas".
+    expand_margins: [u32; 4],
+    /// Fills expanded margins and the gaps left when combining images of different sizes.
+    canvas_fill: Color32,
+    /// Where the capture currently being picked goes.
+    capture_destination: Destination,
+    /// Reads text in captures; `None` if this build comes without OCR.
+    ocr: Option<Box<dyn OcrEngine>>,
+    show_text: bool,
+    /// What to highlight in the recognized text while it is shown.
+    text_query: String,
+    /// Why the text couldn't be read.
+    ocr_error: Option<String>,
+    /// How new redactions hide what is underneath them.
+    redact_style: RedactStyle,
+    show_redact: bool,
+    /// Why auto-redact couldn't look for sensitive text.
+    redact_error: Option<String>,
+    show_codes: bool,
 }
 
 impl SnapCrabApp {
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, cli: &CliArgs) -> Self {
         cc.egui_ctx.set_visuals(egui::Visuals::dark());
+        // Ctrl +/-/0 zoom the image instead of the UI.
+        cc.egui_ctx.options_mut(|o| o.zoom_with_keyboard = false);
         egui_extras::install_image_loaders(&cc.egui_ctx);
-        Self {
-            image: None,
-            original_image: None,
-            annotations: Vec::new(),
+        let mut app = Self {
+            tabs: Vec::new(),
+            active_tab: 0,
+            closing: None,
+            quit_confirmed: false,
+            recent: RecentCaptures::load(),
+            show_history: false,
+            recent_thumbnails: Vec::new(),
             current_tool: Tool::Arrow,
             current_color: Color32::RED,
             stroke_thickness: 4.0,
+            arrow_style: ArrowStyle::default(),
             text_size: 24.0,
+            text_style: TextStyle::default(),
             drag_start: None,
-            active_annotation_index: None,
             show_layers: true,
-            temp_text: String::new(),
-            editing_text_pos: None,
+            text_draft: None,
+            callout_shape: CalloutShape::default(),
+            callout_fill: Color32::WHITE,
+            spot_shape: SpotShape::default(),
+            spot_effect: SpotEffect::default(),
+            // As dark as the overlay when picking an area.
+            spot_strength: 0.7,
+            magnifier_zoom: 2.0,
+            magnifier_connector: true,
+            sticker: Sticker::default(),
+            sticker_size: 96.0,
+            folder_stickers: None,
+            sticker_thumbnails: Vec::new(),
+            dragged_handle: None,
+            drag_original: None,
+            snap_lines: Vec::new(),
             state: AppState::Normal,
             fullscreen_bg: None,
             fullscreen_bg_image: None,
             windows: Vec::new(),
             hovered_window_index: None,
+            controls: Vec::new(),
+            controls_window: None,
+            settings: Settings::load(),
+            show_settings: false,
+            new_preset_name: String::new(),
+            show_frame: false,
+            new_frame_preset_name: String::new(),
+            frame_backgrounds: BackgroundCache::default(),
+            show_resize: false,
+            resize: ResizeSpec::default(),
+            resize_filter: Filter::default(),
+            show_expand: false,
+            expand_margins: [32; 4],
+            canvas_fill: Color32::WHITE,
+            capture_destination: Destination::Replace,
+            ocr: ocr::engine(),
+            show_text: false,
+            text_query: String::new(),
+            ocr_error: None,
+            redact_style: RedactStyle::default(),
+            show_redact: false,
+            redact_error: None,
+            show_codes: false,
+        };
+        // The window isn't shown until the first frame, so there's no need to hide it here.
+        let startup = match &cli.capture {
+            Some(StartupCapture::Fullscreen) => {
+                capture::fullscreen().map(|img| (img, CaptureInfo::screen("Fullscreen")))
+            }
+            Some(StartupCapture::File(path)) => {
+                if let Some(img) = open_image(path) {
+                    let info = CaptureInfo::named(file_title(path));
+                    app.open_tab(Document::new(cli.transform(img), info, &cc.egui_ctx));
+                }
+                None
+            }
+            Some(StartupCapture::LastArea) => app
+                .settings
+                .last_area
+                .and_then(capture::region)
+                .map(|img| (img, CaptureInfo::screen("Last Area"))),
+            Some(StartupCapture::Preset(name)) => app.settings.find_preset(name).and_then(|p| {
+                let (img, info) = p.target.capture(&app.settings.window_capture)?;
+                let title = p.name.clone();
+                Some((img, CaptureInfo { title, ..info }))
+            }),
+            None => None,
+        };
+        if let Some((img, info)) = startup {
+            app.load_captured_image(cli.transform(img), info, &cc.egui_ctx);
         }
+        app
     }
 
-    fn ui_to_image(&self, ui_pos: Pos2, image_rect: Rect) -> Pos2 {
-        let Some(ref original) = self.original_image else {
-            return ui_pos;
-        };
-        let normalized = (ui_pos - image_rect.min) / image_rect.size();
-        Pos2::new(
-            normalized.x * original.width() as f32,
-            normalized.y * original.height() as f32,
-        )
+    fn doc(&self) -> Option<&Document> {
+        self.tabs.get(self.active_tab)
     }
 
-    fn image_to_ui(&self, img_pos: Pos2, image_rect: Rect) -> Pos2 {
-        let Some(ref original) = self.original_image else {
-            return img_pos;
-        };
-        let normalized = Vec2::new(
-            img_pos.x / original.width() as f32,
-            img_pos.y / original.height() as f32,
-        );
-        image_rect.min + normalized * image_rect.size()
+    fn doc_mut(&mut self) -> Option<&mut Document> {
+        self.tabs.get_mut(self.active_tab)
     }
 
-    fn load_captured_image(&mut self, img: image::RgbaImage, ctx: &egui::Context) {
-        let color_img = egui::ColorImage::from_rgba_unmultiplied(
-            [img.width() as usize, img.height() as usize],
-            img.as_flat_samples().as_slice(),
-        );
-        self.image = Some(ctx.load_texture("screenshot", color_img, Default::default()));
-        self.original_image = Some(img);
-        self.annotations.clear();
+    /// Applies `transforms` to the active image as one undoable step.
+    fn apply_transforms(&mut self, transforms: &[Transform], ctx: &egui::Context) {
+        self.commit_text();
+        self.current_tool = Tool::Arrow;
+        if let Some(doc) = self.doc_mut() {
+            doc.apply_transforms(transforms, ctx);
+        }
+    }
+
+    /// Opens a new capture in its own tab, keeps a copy in the capture history and
+    /// auto-saves it if enabled.
+    fn load_captured_image(
+        &mut self,
+        img: image::RgbaImage,
+        info: CaptureInfo,
+        ctx: &egui::Context,
+    ) {
+        let _ = self.recent.add(&img, &info);
+        self.open_tab(Document::new(img, info, ctx));
+        self.auto_save(AutoSaveTrigger::Capture);
+    }
+
+    fn open_tab(&mut self, doc: Document) {
+        self.tabs.push(doc);
+        self.select_tab(self.tabs.len() - 1);
+    }
+
+    /// Drops in-progress crops and drags and finishes text entry, which belong to the active tab.
+    fn reset_tool_state(&mut self) {
+        self.commit_text();
+        if let Some(doc) = self.doc_mut() {
+            doc.crop_edit = None;
+        }
+        if self.current_tool == Tool::Crop {
+            self.current_tool = Tool::Arrow;
+        }
+        self.drag_start = None;
+    }
+
+    fn select_tab(&mut self, index: usize) {
+        if index != self.active_tab {
+            self.reset_tool_state();
+            self.active_tab = index;
+        }
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        if index >= self.tabs.len() {
+            return;
+        }
+        if index == self.active_tab {
+            self.reset_tool_state();
+        }
+        self.tabs.remove(index);
+        if self.active_tab > index || self.active_tab >= self.tabs.len() {
+            self.active_tab = self.active_tab.saturating_sub(1);
+        }
+    }
+
+    /// Closes a tab, asking first if it has unsaved changes.
+    fn request_close_tab(&mut self, index: usize) {
+        match self.tabs.get(index) {
+            Some(doc) if doc.dirty => {
+                self.select_tab(index);
+                self.closing = Some(Closing::Tab);
+            }
+            Some(_) => self.close_tab(index),
+            None => {}
+        }
+    }
+
+    fn receive_image(
+        &mut self,
+        img: image::RgbaImage,
+        info: CaptureInfo,
+        dest: Destination,
+        ctx: &egui::Context,
+    ) {
+        match (dest, self.tabs.get_mut(self.active_tab)) {
+            (Destination::Layer, Some(doc)) => doc.insert_layer(img),
+            (Destination::Append(side), Some(doc)) => {
+                self.current_tool = Tool::Arrow;
+                doc.append_image(img, side, self.canvas_fill, ctx);
+            }
+            // With nothing to add to, the image simply becomes a new capture.
+            _ => self.load_captured_image(img, info, ctx),
+        }
+    }
+
+    fn receive_capture(&mut self, img: image::RgbaImage, info: CaptureInfo, ctx: &egui::Context) {
+        self.receive_image(img, info, self.capture_destination, ctx);
+    }
+
+    /// Menu entries for getting an image from a capture, a file or the clipboard into `dest`.
+    fn draw_image_sources(&mut self, ui: &mut egui::Ui, dest: Destination, ctx: &egui::Context) {
+        if ui.button("🪟 Capture Window").clicked() {
+            self.capture_destination = dest;
+            self.enter_pick_mode(AppState::PickingWindow, ctx);
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui.button("✂ Capture Area").clicked() {
+            self.capture_destination = dest;
+            self.enter_pick_mode(AppState::PickingArea, ctx);
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui.button("📂 Image File…").clicked() {
+            if let Some((img, title)) = pick_image_file() {
+                self.receive_image(img, CaptureInfo::named(title), dest, ctx);
+            }
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        let paste = egui::Button::new("📋 Clipboard");
+        let paste = if dest == Destination::Layer {
+            paste.shortcut_text(ctx.format_shortcut(&PASTE_LAYER_SHORTCUT))
+        } else {
+            paste
+        };
+        if ui.add(paste).clicked() {
+            if let Some(img) = clipboard_image() {
+                self.receive_image(img, CaptureInfo::named("Clipboard"), dest, ctx);
+            }
+            ui.close_kind(egui::UiKind::Menu);
+        }
     }
 
     fn enter_pick_mode(&mut self, state: AppState, ctx: &egui::Context) {
         ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
         std::thread::sleep(std::time::Duration::from_millis(350));
-        if let Ok(Some(img)) =
-            Monitor::all().map(|m| m.first().and_then(|f| f.capture_image().ok()))
-        {
-            let color_img = egui::ColorImage::from_rgba_unmultiplied(
-                [img.width() as usize, img.height() as usize],
-                img.as_flat_samples().as_slice(),
-            );
-            self.fullscreen_bg =
-                Some(ctx.load_texture("fullscreen_bg", color_img, Default::default()));
+        if let Some(img) = capture::fullscreen() {
+            self.fullscreen_bg = Some(load_texture(ctx, "fullscreen_bg", &img));
             self.fullscreen_bg_image = Some(img);
         }
         if state == AppState::PickingWindow {
@@ -213,30 +504,47 @@ impl SnapCrabApp {
                 || title.is_empty()
                 || title == "SnapCrab"
                 || app == "SnapCrab"
-                || title == "Program Manager"
-                || title.starts_with("ms-")
+                || !self.settings.window_picking.allows(&title, &app)
                 || w.width().unwrap_or(0) <= 10
                 || w.height().unwrap_or(0) <= 10
             {
                 continue;
             }
-            self.windows.push(WindowInfo {
-                rect: Rect::from_min_size(
-                    Pos2::new(w.x().unwrap_or(0) as f32, w.y().unwrap_or(0) as f32),
-                    Vec2::new(
-                        w.width().unwrap_or(0) as f32,
-                        w.height().unwrap_or(0) as f32,
-                    ),
+            let id = w.id().unwrap_or_default();
+            let client = Rect::from_min_size(
+                Pos2::new(w.x().unwrap_or(0) as f32, w.y().unwrap_or(0) as f32),
+                Vec2::new(
+                    w.width().unwrap_or(0) as f32,
+                    w.height().unwrap_or(0) as f32,
                 ),
+            );
+            let rect = if self.settings.window_capture.include_frame {
+                capture::frame_bounds(id).unwrap_or(client)
+            } else {
+                client
+            };
+            self.windows.push(WindowInfo {
+                id,
+                z: w.z().unwrap_or_default(),
+                rect,
                 title,
                 app_name: app,
             });
         }
-        self.windows.sort_by(|a, b| {
-            (a.rect.width() * a.rect.height())
-                .partial_cmp(&(b.rect.width() * b.rect.height()))
-                .unwrap()
-        });
+        // Topmost first, so the first window containing the pointer is the visible one.
+        self.windows.sort_by_key(|w| std::cmp::Reverse(w.z));
+    }
+
+    /// Returns the smallest child control of `window_id` under `ptr`.
+    fn hovered_control(&mut self, window_id: u32, ptr: Pos2) -> Option<&capture::ControlInfo> {
+        if self.controls_window != Some(window_id) {
+            self.controls = capture::child_controls(window_id);
+            self.controls_window = Some(window_id);
+        }
+        self.controls
+            .iter()
+            .filter(|c| c.rect.contains(ptr))
+            .min_by(|a, b| a.rect.area().total_cmp(&b.rect.area()))
     }
 
     fn exit_pick_mode(&mut self, ctx: &egui::Context) {
@@ -244,6 +552,9 @@ impl SnapCrabApp {
         self.fullscreen_bg = None;
         self.fullscreen_bg_image = None;
         self.windows.clear();
+        self.controls.clear();
+        self.controls_window = None;
+        self.capture_destination = Destination::Replace;
         ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
         ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(true));
     }
@@ -266,10 +577,10 @@ impl SnapCrabApp {
                 let ptr = ctx.pointer_latest_pos().unwrap_or_default();
                 match self.state {
                     AppState::PickingWindow => {
-                        self.handle_picking_window(ctx, &resp, &painter, ptr)
+                        self.handle_picking_window(ctx, &resp, &painter, ptr);
                     }
                     AppState::PickingArea => {
-                        self.handle_picking_area(ctx, &resp, &painter, ptr, screen_rect)
+                        self.handle_picking_area(ctx, &resp, &painter, ptr, screen_rect);
                     }
                     AppState::Normal => {}
                 }
@@ -286,59 +597,83 @@ impl SnapCrabApp {
         painter: &Painter,
         ptr: Pos2,
     ) {
-        self.hovered_window_index = self
-            .windows
-            .iter()
-            .enumerate()
-            .find(|(_, w)| w.rect.contains(ptr))
-            .map(|(i, _)| i);
+        self.hovered_window_index = self.windows.iter().position(|w| w.rect.contains(ptr));
         let Some(w) = self
             .hovered_window_index
             .and_then(|idx| self.windows.get(idx))
         else {
             return;
         };
+        let (id, rect, title, app) = (w.id, w.rect, w.title.clone(), w.app_name.clone());
+        // Holding Ctrl flips between picking whole windows and their controls.
+        let pick_controls =
+            self.settings.window_picking.pick_controls != ctx.input(|i| i.modifiers.command);
+        let control = if pick_controls {
+            self.hovered_control(id, ptr)
+                .map(|c| (c.rect, c.label.clone()))
+        } else {
+            None
+        };
+        let (highlight, label) = control
+            .clone()
+            .unwrap_or_else(|| (rect, format!("{title} ({app})")));
         painter.rect_filled(
-            w.rect,
+            highlight,
             0.0,
             Color32::from_rgba_unmultiplied(0, 100, 255, 60),
         );
         painter.rect_stroke(
-            w.rect,
+            highlight,
             0.0,
             Stroke::new(2.5, Color32::from_rgb(0, 200, 255)),
             StrokeKind::Outside,
         );
         painter.text(
-            w.rect.left_top() + Vec2::new(10.0, 10.0),
+            highlight.left_top() + Vec2::new(10.0, 10.0),
             egui::Align2::LEFT_TOP,
-            format!("{} ({})", w.title, w.app_name),
+            label,
             egui::FontId::proportional(16.0),
             Color32::WHITE,
         );
         if !resp.clicked() {
             return;
         }
-        let (rect, title, app) = (w.rect, w.title.clone(), w.app_name.clone());
-        if let Ok(Some(img)) = Window::all().map(|v| {
-            v.into_iter()
-                .find(|w| {
-                    w.title().unwrap_or_default() == title
-                        && w.app_name().unwrap_or_default() == app
-                })
-                .and_then(|t| t.capture_image().ok())
-        }) {
-            self.load_captured_image(img, ctx);
-        } else if let Some(ref bg) = self.fullscreen_bg_image {
-            let (x, y) = (rect.min.x.max(0.0) as u32, rect.min.y.max(0.0) as u32);
-            let (w, h) = (
-                (rect.width() as u32).min(bg.width().saturating_sub(x)),
-                (rect.height() as u32).min(bg.height().saturating_sub(y)),
-            );
-            if w > 0 && h > 0 {
-                self.load_captured_image(image::imageops::crop_imm(bg, x, y, w, h).to_image(), ctx);
+        if let Some((control_rect, label)) = control {
+            if let Some(img) = self
+                .fullscreen_bg_image
+                .as_ref()
+                .and_then(|bg| capture::crop(bg, control_rect))
+            {
+                let info = CaptureInfo {
+                    title: label,
+                    ..CaptureInfo::window(id, &title, &app)
+                };
+                self.receive_capture(img, info, ctx);
             }
+            self.exit_pick_mode(ctx);
+            return;
+        }
+        // The frame can only be cut out of the screen capture taken before the overlay,
+        // while the client area is captured directly and may be covered by other windows.
+        let cropped = || {
+            self.fullscreen_bg_image
+                .as_ref()
+                .and_then(|bg| capture::crop(bg, rect))
+        };
+        let opts = &self.settings.window_capture;
+        let img = if opts.include_frame {
+            cropped()
+        } else {
+            capture::window_by_id(id).or_else(cropped)
+        };
+        if let Some(img) = img.map(|img| capture::style_window(img, opts)) {
+            self.receive_capture(img, CaptureInfo::window(id, &title, &app), ctx);
         }
+        self.settings.last_window = Some(WindowMatch {
+            title,
+            app_name: app,
+        });
+        let _ = self.settings.save();
         self.exit_pick_mode(ctx);
     }
 
@@ -398,127 +733,27 @@ impl SnapCrabApp {
             &self.fullscreen_bg_image,
         ) {
             let rect = Rect::from_two_pos(start, end);
-            if rect.width() > 5.0 && rect.height() > 5.0 {
-                let (x, y) = (rect.min.x.max(0.0) as u32, rect.min.y.max(0.0) as u32);
-                let (w, h) = (
-                    (rect.width() as u32).min(bg.width().saturating_sub(x)),
-                    (rect.height() as u32).min(bg.height().saturating_sub(y)),
-                );
-                if w > 0 && h > 0 {
-                    self.load_captured_image(
-                        image::imageops::crop_imm(bg, x, y, w, h).to_image(),
-                        ctx,
-                    );
-                }
+            if rect.width() > 5.0
+                && rect.height() > 5.0
+                && let Some(img) = capture::crop(bg, rect)
+            {
+                self.receive_capture(img, CaptureInfo::screen("Area"), ctx);
+                self.settings.last_area = Some(rect);
+                let _ = self.settings.save();
             }
         }
         self.drag_start = None;
         self.exit_pick_mode(ctx);
     }
 
-    fn draw_annotation(&self, painter: &Painter, ann: &Annotation, rect: Rect, active: bool) {
-        let scale = rect.width()
-            / self
-                .original_image
-                .as_ref()
-                .map_or(1.0, |i| i.width() as f32);
-        if active {
-            match ann {
-                Annotation::Arrow { start, end, .. } => {
-                    painter.line_segment(
-                        [self.image_to_ui(*start, rect), self.image_to_ui(*end, rect)],
-                        Stroke::new(10.0 * scale, Color32::from_white_alpha(30)),
-                    );
-                }
-                Annotation::Rect { rect: r, .. } => {
-                    painter.rect_filled(
-                        Rect::from_min_max(
-                            self.image_to_ui(r.min, rect),
-                            self.image_to_ui(r.max, rect),
-                        )
-                        .expand(2.0),
-                        0.0,
-                        Color32::from_white_alpha(20),
-                    );
-                }
-                Annotation::Text {
-                    pos, text, size, ..
-                } => {
-                    let p = self.image_to_ui(*pos, rect);
-                    let s = *size * scale;
-                    painter.rect_filled(
-                        Rect::from_min_size(p, Vec2::new(text.len() as f32 * s * 0.6, s))
-                            .expand(4.0),
-                        0.0,
-                        Color32::from_white_alpha(30),
-                    );
-                }
-            }
-        }
-        match ann {
-            Annotation::Arrow {
-                start,
-                end,
-                color,
-                thickness,
-            } => {
-                let (s_ui, e_ui) = (self.image_to_ui(*start, rect), self.image_to_ui(*end, rect));
-                let thick = *thickness * scale;
-                painter.line_segment([s_ui, e_ui], Stroke::new(thick, *color));
-                let dir = (e_ui - s_ui).normalized();
-                if dir.is_finite() {
-                    let (side, head) = (Vec2::new(-dir.y, dir.x), thick * 3.0);
-                    painter.line_segment(
-                        [e_ui, e_ui - dir * head + side * head],
-                        Stroke::new(thick, *color),
-                    );
-                    painter.line_segment(
-                        [e_ui, e_ui - dir * head - side * head],
-                        Stroke::new(thick, *color),
-                    );
-                }
-            }
-            Annotation::Rect {
-                rect: r,
-                color,
-                thickness,
-            } => {
-                painter.rect_stroke(
-                    Rect::from_min_max(
-                        self.image_to_ui(r.min, rect),
-                        self.image_to_ui(r.max, rect),
-                    ),
-                    0.0,
-                    Stroke::new(*thickness * scale, *color),
-                    StrokeKind::Outside,
-                );
-            }
-            Annotation::Text {
-                pos,
-                text,
-                color,
-                size,
-            } => {
-                painter.text(
-                    self.image_to_ui(*pos, rect),
-                    egui::Align2::LEFT_TOP,
-                    text,
-                    egui::FontId::proportional(*size * scale),
-                    *color,
-                );
-            }
-        }
-    }
-
     fn draw_top_panel(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
+                ui.menu_button("📁 File", |ui| self.draw_file_menu(ui, ctx));
                 ui.menu_button("📸 Capture", |ui| {
                     if ui.button("🖥 Fullscreen").clicked() {
-                        if let Ok(Some(img)) =
-                            Monitor::all().map(|m| m.first().and_then(|f| f.capture_image().ok()))
-                        {
-                            self.load_captured_image(img, ctx);
+                        if let Some(img) = capture::fullscreen() {
+                            self.load_captured_image(img, CaptureInfo::screen("Fullscreen"), ctx);
                         }
                         ui.close_kind(egui::UiKind::Menu);
                     }
@@ -530,313 +765,2974 @@ impl SnapCrabApp {
                         self.enter_pick_mode(AppState::PickingArea, ctx);
                         ui.close_kind(egui::UiKind::Menu);
                     }
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            self.settings.last_area.is_some(),
+                            egui::Button::new("🔁 Last Area")
+                                .shortcut_text(ctx.format_shortcut(&LAST_AREA_SHORTCUT)),
+                        )
+                        .clicked()
+                    {
+                        self.capture_last_area(ctx);
+                        ui.close_kind(egui::UiKind::Menu);
+                    }
+                    ui.menu_button("⭐ Presets", |ui| self.draw_presets_menu(ui, ctx));
                 });
                 ui.separator();
                 ui.selectable_value(&mut self.current_tool, Tool::Arrow, "↗ Arrow");
                 ui.selectable_value(&mut self.current_tool, Tool::Rect, "⬜ Box");
                 ui.selectable_value(&mut self.current_tool, Tool::Text, "T Text");
-                if self.image.is_some() {
+                ui.selectable_value(&mut self.current_tool, Tool::Callout, "💬 Callout");
+                ui.selectable_value(&mut self.current_tool, Tool::Spotlight, "🔦 Spotlight");
+                ui.selectable_value(&mut self.current_tool, Tool::Magnifier, "🔎 Magnifier");
+                ui.selectable_value(&mut self.current_tool, Tool::Sticker, "🏷 Sticker");
+                ui.selectable_value(&mut self.current_tool, Tool::Redact, "⬛ Redact");
+                let has_image = !self.tabs.is_empty();
+                if has_image && self.ocr.is_some() {
+                    ui.selectable_value(&mut self.current_tool, Tool::Ocr, "📝 Read Text");
+                }
+                if has_image {
                     ui.selectable_value(&mut self.current_tool, Tool::Crop, "✂ Crop");
                 }
+                let mut snapping = !self.settings.disable_snapping;
+                if ui
+                    .toggle_value(&mut snapping, "🧲")
+                    .on_hover_text(
+                        "Snap to the image, other annotations and edges in the screenshot",
+                    )
+                    .changed()
+                {
+                    self.settings.disable_snapping = !snapping;
+                    let _ = self.settings.save();
+                }
                 ui.separator();
-                ui.color_edit_button_srgba(&mut self.current_color);
-                ui.add(egui::Slider::new(&mut self.stroke_thickness, 1.0..=20.0).text("Size"));
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.toggle_value(&mut self.show_layers, "🗂 Layers");
+                self.draw_tool_options(ui);
+                if has_image {
                     ui.separator();
-                    if ui.button("💾 Save").clicked() {
-                        self.save_to_file();
-                    }
-                    if ui.button("📋 Copy").clicked() {
-                        self.copy_to_clipboard();
-                    }
-                    if ui.button("🗑 Clear").clicked() {
-                        self.annotations.clear();
-                    }
+                    ui.menu_button("🖼 Image", |ui| self.draw_image_menu(ui, ctx));
+                    self.draw_zoom_menu(ui, ctx);
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    self.draw_top_panel_actions(ui, ctx);
                 });
             });
         });
     }
 
-    fn save_to_file(&self) {
-        let (Some(ref original), Some(path)) = (
-            self.original_image.as_ref(),
-            rfd::FileDialog::new()
-                .add_filter("PNG", &["png"])
-                .set_file_name("screenshot.png")
-                .save_file(),
-        ) else {
+    /// The colour, size and style controls for the current tool.
+    fn draw_tool_options(&mut self, ui: &mut egui::Ui) {
+        if self.current_tool == Tool::Ocr {
+            ui.weak("Drag over text to copy it, or click to copy all of it");
             return;
-        };
-        let _ = original.save(path);
+        }
+        if self.current_tool == Tool::Redact {
+            redact_style_ui(ui, &mut self.redact_style);
+            return;
+        }
+        ui.color_edit_button_srgba(&mut self.current_color);
+        if matches!(self.current_tool, Tool::Text | Tool::Callout) {
+            ui.add(
+                egui::Slider::new(&mut self.text_size, TEXT_SIZE_RANGE)
+                    .logarithmic(true)
+                    .text("Size"),
+            );
+            ui.separator();
+            if self.current_tool == Tool::Text {
+                text_style_ui(ui, &mut self.text_style);
+            } else {
+                callout_style_ui(ui, &mut self.callout_shape, &mut self.callout_fill);
+            }
+        } else if self.current_tool == Tool::Sticker {
+            ui.add(
+                egui::Slider::new(&mut self.sticker_size, STICKER_SIZE_RANGE)
+                    .logarithmic(true)
+                    .text("Size"),
+            );
+        } else if self.current_tool == Tool::Spotlight {
+            for shape in SpotShape::ALL {
+                ui.selectable_value(&mut self.spot_shape, shape, shape.name());
+            }
+            ui.separator();
+            spotlight_style_ui(ui, &mut self.spot_effect, &mut self.spot_strength);
+        } else {
+            ui.add(egui::Slider::new(&mut self.stroke_thickness, 1.0..=20.0).text("Size"));
+            if matches!(self.current_tool, Tool::Arrow | Tool::Rect) {
+                ui.menu_button("〰 Stroke", |ui| {
+                    let before = self.settings.stroke;
+                    stroke_style_ui(ui, &mut self.settings.stroke);
+                    if self.settings.stroke != before {
+                        let _ = self.settings.save();
+                    }
+                });
+            }
+            if self.current_tool == Tool::Arrow {
+                ui.separator();
+                arrow_style_ui(ui, &mut self.arrow_style);
+            }
+            if self.current_tool == Tool::Magnifier {
+                ui.separator();
+                magnifier_style_ui(ui, &mut self.magnifier_zoom, &mut self.magnifier_connector);
+            }
+        }
     }
 
-    fn copy_to_clipboard(&self) {
-        let (Some(ref original), Ok(mut clipboard)) =
-            (self.original_image.as_ref(), Clipboard::new())
-        else {
+    fn draw_top_panel_actions(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.toggle_value(&mut self.show_settings, "⚙");
+        ui.toggle_value(&mut self.show_frame, "🖼 Frame");
+        ui.toggle_value(&mut self.show_layers, "🗂 Layers");
+        ui.toggle_value(&mut self.show_history, "🕘 History");
+        ui.separator();
+        if ui.button("💾 Save").clicked() {
+            self.save_to_file();
+        }
+        if ui.button("📋 Copy").clicked() {
+            self.copy_to_clipboard();
+        }
+        let Some(doc) = self.doc_mut() else {
             return;
         };
-        let _ = clipboard.set_image(arboard::ImageData {
-            width: original.width() as usize,
-            height: original.height() as usize,
-            bytes: std::borrow::Cow::Borrowed(original.as_raw()),
+        if ui.button("🗑 Clear").clicked() && !doc.annotations.is_empty() {
+            doc.checkpoint();
+            doc.annotations.clear();
+            doc.active_annotation_index = None;
+        }
+        ui.separator();
+        if ui
+            .add_enabled(doc.history.can_redo(), egui::Button::new("↷"))
+            .on_hover_text(format!(
+                "Redo ({})",
+                ctx.format_shortcut(&REDO_SHORTCUTS[0])
+            ))
+            .clicked()
+        {
+            doc.redo(ctx);
+        }
+        if ui
+            .add_enabled(doc.history.can_undo(), egui::Button::new("↶"))
+            .on_hover_text(format!("Undo ({})", ctx.format_shortcut(&UNDO_SHORTCUT)))
+            .clicked()
+        {
+            doc.undo(ctx);
+        }
+    }
+
+    fn draw_tab_bar(&mut self, ctx: &egui::Context) {
+        let (mut select, mut close) = (None, None);
+        egui::TopBottomPanel::top("tab_bar").show(ctx, |ui| {
+            egui::ScrollArea::horizontal().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (i, doc) in self.tabs.iter().enumerate() {
+                        let mut label: String =
+                            doc.info.title.chars().take(TAB_TITLE_LEN).collect();
+                        if doc.info.title.chars().count() > TAB_TITLE_LEN {
+                            label.push('…');
+                        }
+                        if doc.dirty {
+                            label.push_str(" ●");
+                        }
+                        let tab = ui
+                            .selectable_label(i == self.active_tab, label)
+                            .on_hover_text(&doc.info.title);
+                        if tab.clicked() {
+                            select = Some(i);
+                        }
+                        if tab.middle_clicked() || ui.small_button("✖").clicked() {
+                            close = Some(i);
+                        }
+                        ui.separator();
+                    }
+                });
+            });
         });
+        if let Some(i) = select {
+            self.select_tab(i);
+        }
+        if let Some(i) = close {
+            self.request_close_tab(i);
+        }
     }
 
-    fn draw_layers_panel(&mut self, ctx: &egui::Context) {
-        egui::SidePanel::right("layers_panel")
-            .default_width(200.0)
+    fn draw_history_panel(&mut self, ctx: &egui::Context) {
+        let (mut to_open, mut to_remove, mut clear) = (None, None, false);
+        egui::SidePanel::left("history_panel")
+            .default_width(220.0)
             .show(ctx, |ui| {
-                ui.heading("Layers");
+                ui.horizontal(|ui| {
+                    ui.heading("History");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        clear = ui
+                            .add_enabled(
+                                !self.recent.entries.is_empty(),
+                                egui::Button::new("Clear"),
+                            )
+                            .clicked();
+                    });
+                });
                 ui.separator();
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    let mut to_remove = None;
-                    for (i, ann) in self.annotations.iter().enumerate().rev() {
+                    for (i, entry) in self.recent.entries.iter().enumerate() {
                         ui.horizontal(|ui| {
-                            if ui
-                                .selectable_label(
-                                    self.active_annotation_index == Some(i),
-                                    ann.label(),
-                                )
-                                .clicked()
+                            if let Some(tex) =
+                                recent_thumbnail(&mut self.recent_thumbnails, entry, ctx)
                             {
-                                self.active_annotation_index = Some(i);
+                                let size = tex.size_vec2()
+                                    * (HISTORY_THUMBNAIL_HEIGHT / tex.size_vec2().y);
+                                let thumb =
+                                    egui::Image::new((tex.id(), size)).sense(egui::Sense::click());
+                                if ui.add(thumb).clicked() {
+                                    to_open = Some(i);
+                                }
                             }
-                            ui.with_layout(
-                                egui::Layout::right_to_left(egui::Align::Center),
-                                |ui| {
-                                    if ui.button("🗑").clicked() {
-                                        to_remove = Some(i);
-                                    }
-                                },
-                            );
+                            ui.vertical(|ui| {
+                                if ui.link(&entry.info.title).clicked() {
+                                    to_open = Some(i);
+                                }
+                                ui.weak(entry.age());
+                                if ui.small_button("🗑").clicked() {
+                                    to_remove = Some(i);
+                                }
+                            });
                         });
+                        ui.separator();
                     }
-                    if let Some(i) = to_remove {
-                        self.annotations.remove(i);
-                        self.active_annotation_index = None;
+                    if self.recent.entries.is_empty() {
+                        ui.weak("Captures show up here");
                     }
                 });
             });
+        if let Some(entry) = to_open.and_then(|i| self.recent.entries.get(i))
+            && let Some(img) = entry.load()
+        {
+            let doc = Document::new(img, entry.info.clone(), ctx);
+            self.open_tab(doc);
+        }
+        if let Some(i) = to_remove {
+            let _ = self.recent.remove(i);
+        }
+        if clear {
+            let _ = self.recent.clear();
+        }
+        if to_remove.is_some() || clear {
+            let recent = &self.recent;
+            self.recent_thumbnails
+                .retain(|(id, _)| recent.entries.iter().any(|e| &e.id == id));
+        }
     }
 
-    fn draw_main_canvas(&mut self, ui: &mut egui::Ui) {
-        let Some(texture) = &self.image else {
-            self.draw_empty_state(ui);
+    /// Asks what to do with unsaved changes before closing the active tab or quitting.
+    fn draw_close_prompt(&mut self, ctx: &egui::Context) {
+        let Some(closing) = self.closing else {
             return;
         };
-        let available = ui.available_size();
-        let scale = (available.x / texture.size_vec2().x)
-            .min(available.y / texture.size_vec2().y)
-            .min(1.0);
-        let (rect, resp) = ui.allocate_at_least(texture.size_vec2() * scale, egui::Sense::drag());
-        let mut mesh = egui::Mesh::with_texture(texture.id());
-        mesh.add_rect_with_uv(
-            rect,
-            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
-            Color32::WHITE,
-        );
-        ui.painter().add(egui::Shape::mesh(mesh));
-        self.handle_canvas_interactions(&resp, rect, scale, ui.ctx());
-        let painter = ui.painter_at(rect);
-        for (i, ann) in self.annotations.iter().enumerate() {
-            self.draw_annotation(&painter, ann, rect, self.active_annotation_index == Some(i));
+        let message = match closing {
+            Closing::Tab => {
+                let Some(doc) = self.doc() else {
+                    self.closing = None;
+                    return;
+                };
+                format!("\"{}\" has unsaved changes.", doc.info.title)
+            }
+            Closing::App => match self.tabs.iter().filter(|d| d.dirty).count() {
+                1 => "A capture has unsaved changes.".to_string(),
+                n => format!("{n} captures have unsaved changes."),
+            },
+        };
+        let (mut save, mut discard, mut cancel) = (false, false, false);
+        let modal = egui::Modal::new(egui::Id::new("close_prompt")).show(ctx, |ui| {
+            ui.heading("Unsaved changes");
+            ui.label(message);
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if closing == Closing::Tab {
+                    save = ui.button("💾 Save…").clicked();
+                    discard = ui.button("Discard").clicked();
+                } else {
+                    discard = ui.button("Quit without saving").clicked();
+                }
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+        if (save && self.save_to_file()) || (discard && closing == Closing::Tab) {
+            self.close_tab(self.active_tab);
+            self.closing = None;
+        } else if discard {
+            self.quit_confirmed = true;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        } else if cancel || modal.should_close() {
+            self.closing = None;
         }
-        self.draw_drawing_preview(ui.ctx(), &painter, rect);
-        self.handle_text_editing(ui.ctx(), rect);
     }
 
-    fn draw_empty_state(&mut self, ui: &mut egui::Ui) {
-        ui.centered_and_justified(|ui| {
-            ui.vertical(|ui| {
-                ui.heading("SnapCrab");
-                ui.label("Select a capture mode to begin");
-                ui.add_space(10.0);
-                ui.horizontal(|ui| {
-                    if ui.button("🖥 Fullscreen").clicked() {
-                        if let Ok(Some(img)) =
-                            Monitor::all().map(|m| m.first().and_then(|f| f.capture_image().ok()))
-                        {
-                            self.load_captured_image(img, ui.ctx());
-                        }
-                    }
-                    if ui.button("🪟 Window").clicked() {
-                        self.enter_pick_mode(AppState::PickingWindow, ui.ctx());
+    /// Keeps the window open while any tab has unsaved changes, asking the user instead.
+    fn handle_close_request(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.viewport().close_requested())
+            && !self.quit_confirmed
+            && self.tabs.iter().any(|d| d.dirty)
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.closing = Some(Closing::App);
+        }
+    }
+
+    fn draw_file_menu(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        if ui
+            .add(egui::Button::new("📂 Open…").shortcut_text(ctx.format_shortcut(&OPEN_SHORTCUT)))
+            .clicked()
+        {
+            self.open_file(ctx);
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui
+            .add(
+                egui::Button::new("📋 Paste Image")
+                    .shortcut_text(ctx.format_shortcut(&PASTE_SHORTCUT)),
+            )
+            .clicked()
+        {
+            self.paste_image(ctx);
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        ui.separator();
+        if ui
+            .add_enabled(!self.tabs.is_empty(), egui::Button::new("💾 Save As…"))
+            .clicked()
+        {
+            self.save_as();
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui
+            .add_enabled(
+                !self.tabs.is_empty(),
+                egui::Button::new("✖ Close Tab")
+                    .shortcut_text(ctx.format_shortcut(&CLOSE_TAB_SHORTCUT)),
+            )
+            .clicked()
+        {
+            self.request_close_tab(self.active_tab);
+            ui.close_kind(egui::UiKind::Menu);
+        }
+    }
+
+    fn draw_image_menu(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        if ui.button("↔ Resize…").clicked() {
+            self.show_resize = true;
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui.button("⛶ Expand Canvas…").clicked() {
+            self.show_expand = true;
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if self.ocr.is_some() && ui.button("📝 Copy Text").clicked() {
+            if let Some(region) = self.doc().map(Document::visible_region) {
+                self.recognize_text(region);
+            }
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui.button("🔳 Scan Codes").clicked() {
+            self.show_codes = true;
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui.button("⬛ Auto-Redact…").clicked() {
+            self.show_redact = true;
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        ui.separator();
+        ui.menu_button("🧩 Insert as Layer", |ui| {
+            self.draw_image_sources(ui, Destination::Layer, ctx);
+        });
+        ui.menu_button("➡ Combine Right", |ui| {
+            self.draw_image_sources(ui, Destination::Append(Side::Right), ctx);
+        });
+        ui.menu_button("⬇ Combine Below", |ui| {
+            self.draw_image_sources(ui, Destination::Append(Side::Below), ctx);
+        });
+        ui.separator();
+        for (label, transform) in [
+            ("⟳ Rotate 90° Clockwise", Transform::Rotate90),
+            ("⟲ Rotate 90° Counter-clockwise", Transform::Rotate270),
+            ("↻ Rotate 180°", Transform::Rotate180),
+            ("⬌ Flip Horizontal", Transform::FlipHorizontal),
+            ("⬍ Flip Vertical", Transform::FlipVertical),
+        ] {
+            if ui.button(label).clicked() {
+                self.apply_transforms(&[transform], ctx);
+                ui.close_kind(egui::UiKind::Menu);
+            }
+        }
+    }
+
+    fn draw_resize_window(&mut self, ctx: &egui::Context) {
+        let Some(size) = self.doc().map(|doc| doc.visible_region().size()) else {
+            return;
+        };
+        let mut apply = false;
+        egui::Window::new("↔ Resize")
+            .open(&mut self.show_resize)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let percent = matches!(self.resize, ResizeSpec::Percent(_));
+                    if ui.selectable_label(percent, "Percentage").clicked() && !percent {
+                        self.resize = ResizeSpec::default();
+                    }
+                    if ui.selectable_label(!percent, "Max size").clicked() && percent {
+                        self.resize = ResizeSpec::Fit {
+                            max_width: Some(size.x as u32),
+                            max_height: None,
+                        };
+                    }
+                });
+                match &mut self.resize {
+                    ResizeSpec::Percent(p) => {
+                        ui.add(egui::Slider::new(p, 1.0..=400.0).suffix("%"));
+                    }
+                    ResizeSpec::Fit {
+                        max_width,
+                        max_height,
+                    } => {
+                        egui::Grid::new("resize_limits")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for (label, limit, len) in [
+                                    ("Max width", max_width, size.x),
+                                    ("Max height", max_height, size.y),
+                                ] {
+                                    let mut enabled = limit.is_some();
+                                    if ui.checkbox(&mut enabled, label).changed() {
+                                        *limit = enabled.then_some(len as u32);
+                                    }
+                                    if let Some(px) = limit {
+                                        ui.add(
+                                            egui::DragValue::new(px).range(1..=16384).suffix(" px"),
+                                        );
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    }
+                }
+                egui::ComboBox::from_label("Filter")
+                    .selected_text(self.resize_filter.name())
+                    .show_ui(ui, |ui| {
+                        for filter in Filter::ALL {
+                            ui.selectable_value(&mut self.resize_filter, filter, filter.name());
+                        }
+                    });
+                let new_size = (size * self.resize.factor(size))
+                    .round()
+                    .max(Vec2::splat(1.0));
+                ui.weak(format!(
+                    "{} × {} → {} × {}",
+                    size.x, size.y, new_size.x, new_size.y
+                ));
+                apply = ui.button("✔ Apply").clicked();
+            });
+        if apply {
+            let transform = Transform::Resize {
+                factor: self.resize.factor(size),
+                filter: self.resize_filter,
+            };
+            self.apply_transforms(&[transform], ctx);
+            self.show_resize = false;
+        }
+    }
+
+    fn draw_expand_window(&mut self, ctx: &egui::Context) {
+        let Some(crop) = self.doc().map(|doc| doc.crop) else {
+            return;
+        };
+        let mut apply = false;
+        egui::Window::new("⛶ Expand Canvas")
+            .open(&mut self.show_expand)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let [left, top, right, bottom] = &mut self.expand_margins;
+                egui::Grid::new("expand_margins")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (label, margin) in [
+                            ("Top", top),
+                            ("Left", left),
+                            ("Right", right),
+                            ("Bottom", bottom),
+                        ] {
+                            ui.label(label);
+                            ui.add(egui::DragValue::new(margin).range(0..=4096).suffix(" px"));
+                            ui.end_row();
+                        }
+                    });
+                if ui.button("Same on all sides").clicked() {
+                    self.expand_margins = [self.expand_margins[0]; 4];
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Fill");
+                    ui.color_edit_button_srgba(&mut self.canvas_fill);
+                });
+                apply = ui.button("✔ Apply").clicked();
+            });
+        if apply {
+            let [left, top, right, bottom] = self.expand_margins;
+            let mut transforms: Vec<Transform> = crop
+                .map(|rect| Transform::Crop { rect })
+                .into_iter()
+                .collect();
+            transforms.push(Transform::Expand {
+                left,
+                top,
+                right,
+                bottom,
+                fill: self.canvas_fill,
+            });
+            self.apply_transforms(&transforms, ctx);
+            self.show_expand = false;
+        }
+    }
+
+    /// Reads the text in `region` of the image, without the annotations, and copies it.
+    fn recognize_text(&mut self, region: Rect) {
+        let (Some(engine), Some(doc)) = (&self.ocr, self.tabs.get_mut(self.active_tab)) else {
+            return;
+        };
+        let region = region.intersect(doc.visible_region());
+        match ocr::recognize(engine.as_ref(), &doc.image, region) {
+            Ok(lines) => {
+                if let Ok(mut clipboard) = Clipboard::new() {
+                    let _ = clipboard.set_text(ocr::text(&lines));
+                }
+                doc.set_text(lines);
+                self.ocr_error = None;
+            }
+            Err(err) => self.ocr_error = Some(err.to_string()),
+        }
+        self.show_text = true;
+    }
+
+    /// The text read last, with a search that highlights where it is in the image.
+    fn draw_text_window(&mut self, ctx: &egui::Context) {
+        let Some(text) = self.doc().map(|doc| ocr::text(doc.text_lines())) else {
+            return;
+        };
+        let matches = self
+            .doc()
+            .map_or(0, |doc| ocr::find(doc.text_lines(), &self.text_query).len());
+        egui::Window::new("📝 Text")
+            .open(&mut self.show_text)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                if let Some(err) = &self.ocr_error {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                } else if text.is_empty() {
+                    ui.weak("No text found");
+                } else {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.text_query)
+                                .hint_text("🔍 Find in image"),
+                        );
+                        if !self.text_query.trim().is_empty() {
+                            ui.weak(match matches {
+                                1 => "1 match".to_owned(),
+                                n => format!("{n} matches"),
+                            });
+                        }
+                    });
+                    egui::ScrollArea::vertical()
+                        .max_height(240.0)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::TextEdit::multiline(&mut text.as_str())
+                                    .desired_width(f32::INFINITY),
+                            );
+                        });
+                    if ui.button("📋 Copy").clicked()
+                        && let Ok(mut clipboard) = Clipboard::new()
+                    {
+                        let _ = clipboard.set_text(text.clone());
+                    }
+                }
+            });
+    }
+
+    /// Reads the text in the image and proposes redactions for the sensitive parts, and for
+    /// the QR codes and barcodes if those are redacted too. Without OCR only codes are
+    /// looked for.
+    fn find_redactions(&mut self) {
+        let finder = match redact::Finder::new(&self.settings.redact_patterns) {
+            Ok(finder) => finder,
+            Err(err) => {
+                self.redact_error = Some(err.to_string());
+                return;
+            }
+        };
+        let with_codes = self.settings.redact_codes || self.ocr.is_none();
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        let mut proposals = Vec::new();
+        if let Some(engine) = &self.ocr {
+            match ocr::recognize(engine.as_ref(), &doc.image, doc.visible_region()) {
+                Ok(lines) => {
+                    proposals = finder.find(&lines);
+                    doc.set_text(lines);
+                }
+                Err(err) => {
+                    self.redact_error = Some(err.to_string());
+                    return;
+                }
+            }
+        }
+        if with_codes {
+            doc.sync_codes();
+            proposals.extend(doc.codes().into_iter().map(|code| {
+                redact::Proposal::new(redact::Pii::Code, code.text.clone(), code.rect)
+            }));
+        }
+        doc.redactions = Some(proposals);
+        self.redact_error = None;
+    }
+
+    /// Adds the accepted redactions as annotations, in one undo step.
+    fn apply_redactions(&mut self) {
+        let style = self.redact_style;
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        let Some(proposals) = doc.redactions.take() else {
+            return;
+        };
+        let accepted: Vec<Annotation> = proposals
+            .into_iter()
+            .filter(|p| p.accepted)
+            .map(|p| Annotation::Redact {
+                rect: p.rect,
+                style,
+            })
+            .collect();
+        if !accepted.is_empty() {
+            doc.checkpoint();
+            doc.annotations.extend(accepted);
+        }
+    }
+
+    /// Finds sensitive text and lists it for review, highlighted on the image, before it is
+    /// redacted.
+    fn draw_redact_window(&mut self, ctx: &egui::Context) {
+        let (mut find, mut apply, mut changed) = (false, false, false);
+        egui::Window::new("⬛ Auto-Redact")
+            .open(&mut self.show_redact)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                if self.ocr.is_some() {
+                    ui.label(
+                        "Finds email addresses, IP addresses, keys and tokens, card numbers and \
+                         your own patterns in the text of the image.",
+                    );
+                    ui.collapsing("Custom patterns", |ui| {
+                        changed = redact_patterns_ui(ui, &mut self.settings.redact_patterns);
+                    });
+                    changed |= ui
+                        .checkbox(
+                            &mut self.settings.redact_codes,
+                            "Redact QR codes and barcodes too",
+                        )
+                        .changed();
+                } else {
+                    ui.label(
+                        "This build can't read text, so only QR codes and barcodes are found.",
+                    );
+                }
+                ui.horizontal(|ui| {
+                    redact_style_ui(ui, &mut self.redact_style);
+                    ui.separator();
+                    find = ui.button("🔍 Find").clicked();
+                });
+                if let Some(err) = &self.redact_error {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                let proposals = self
+                    .tabs
+                    .get_mut(self.active_tab)
+                    .and_then(|doc| doc.redactions.as_mut());
+                match proposals {
+                    None => {}
+                    Some(proposals) if proposals.is_empty() => {
+                        ui.weak("Nothing sensitive found");
+                    }
+                    Some(proposals) => {
+                        ui.separator();
+                        egui::ScrollArea::vertical()
+                            .max_height(240.0)
+                            .show(ui, |ui| {
+                                for p in proposals.iter_mut() {
+                                    ui.checkbox(
+                                        &mut p.accepted,
+                                        format!("{}: {}", p.kind.name(), p.text),
+                                    );
+                                }
+                            });
+                        let count = proposals.iter().filter(|p| p.accepted).count();
+                        apply = ui
+                            .add_enabled(count > 0, egui::Button::new(format!("✔ Redact {count}")))
+                            .clicked();
+                    }
+                }
+            });
+        if changed {
+            let _ = self.settings.save();
+        }
+        if find {
+            self.find_redactions();
+        }
+        if apply {
+            self.apply_redactions();
+        }
+    }
+
+    /// Lists the QR codes and barcodes in the image, marked on it while the list is shown,
+    /// with what they say.
+    fn draw_codes_window(&mut self, ctx: &egui::Context) {
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        doc.sync_codes();
+        let codes: Vec<_> = doc.codes().into_iter().cloned().collect();
+        let mut to_redact = Vec::new();
+        egui::Window::new("🔳 Codes")
+            .open(&mut self.show_codes)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                if codes.is_empty() {
+                    ui.weak("No QR codes or barcodes found");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for code in &codes {
+                            ui.horizontal(|ui| {
+                                ui.strong(code.symbology.name());
+                                if ui.button("📋 Copy").clicked()
+                                    && let Ok(mut clipboard) = Clipboard::new()
+                                {
+                                    let _ = clipboard.set_text(code.text.clone());
+                                }
+                                if ui.button("⬛ Redact").clicked() {
+                                    to_redact.push(code.rect);
+                                }
+                            });
+                            ui.add(
+                                egui::TextEdit::multiline(&mut code.text.as_str())
+                                    .desired_rows(1)
+                                    .desired_width(f32::INFINITY),
+                            );
+                            ui.separator();
+                        }
+                    });
+                if ui.button("⬛ Redact All").clicked() {
+                    to_redact.extend(codes.iter().map(|c| c.rect));
+                }
+            });
+        if !to_redact.is_empty() {
+            let style = self.redact_style;
+            if let Some(doc) = self.tabs.get_mut(self.active_tab) {
+                doc.checkpoint();
+                doc.annotations
+                    .extend(to_redact.into_iter().map(|rect| Annotation::Redact {
+                        rect: rect.expand(redact::MARGIN),
+                        style,
+                    }));
+            }
+        }
+    }
+
+    fn draw_zoom_menu(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let Some(doc) = self.doc_mut() else {
+            return;
+        };
+        let view = &mut doc.view;
+        let label = format!("🔍 {:.0}%", view.zoom_factor() * 100.0);
+        ui.menu_button(label, |ui| {
+            if ui
+                .add(
+                    egui::Button::new("Fit")
+                        .selected(view.zoom == Zoom::Fit)
+                        .shortcut_text(ctx.format_shortcut(&ZOOM_FIT_SHORTCUT)),
+                )
+                .clicked()
+            {
+                view.set_zoom(Zoom::Fit);
+                ui.close_kind(egui::UiKind::Menu);
+            }
+            for (label, zoom) in ZOOM_PRESETS {
+                if ui
+                    .add(egui::Button::new(label).selected(view.zoom == Zoom::Fixed(zoom)))
+                    .clicked()
+                {
+                    view.set_zoom(Zoom::Fixed(zoom));
+                    ui.close_kind(egui::UiKind::Menu);
+                }
+            }
+            ui.separator();
+            if ui
+                .add(
+                    egui::Button::new("Zoom In")
+                        .shortcut_text(ctx.format_shortcut(&ZOOM_IN_SHORTCUTS[0])),
+                )
+                .clicked()
+            {
+                view.zoom_by(ZOOM_STEP);
+            }
+            if ui
+                .add(
+                    egui::Button::new("Zoom Out")
+                        .shortcut_text(ctx.format_shortcut(&ZOOM_OUT_SHORTCUT)),
+                )
+                .clicked()
+            {
+                view.zoom_by(1.0 / ZOOM_STEP);
+            }
+        });
+    }
+
+    fn handle_edit_shortcuts(&mut self, ctx: &egui::Context) {
+        // Text fields have their own undo.
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let Some(doc) = self.doc_mut() else {
+            return;
+        };
+        if ctx.input_mut(|i| i.consume_shortcut(&PASTE_LAYER_SHORTCUT))
+            && let Some(img) = clipboard_image()
+        {
+            doc.insert_layer(img);
+        }
+        // Redo first: the undo shortcut would also match with Shift held.
+        if ctx.input_mut(|i| REDO_SHORTCUTS.iter().any(|s| i.consume_shortcut(s))) {
+            doc.redo(ctx);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+            doc.undo(ctx);
+        }
+        if doc.crop_edit.is_some() {
+            if ctx.input(|i| i.key_pressed(Key::Enter)) {
+                self.apply_crop();
+            } else if ctx.input(|i| i.key_pressed(Key::Escape)) {
+                self.current_tool = Tool::Arrow;
+            }
+        }
+    }
+
+    fn handle_view_shortcuts(&mut self, ctx: &egui::Context) {
+        let Some(doc) = self.doc_mut() else {
+            return;
+        };
+        ctx.input_mut(|i| {
+            if i.consume_shortcut(&ZOOM_FIT_SHORTCUT) {
+                doc.view.set_zoom(Zoom::Fit);
+            }
+            if ZOOM_IN_SHORTCUTS.iter().any(|s| i.consume_shortcut(s)) {
+                doc.view.zoom_by(ZOOM_STEP);
+            }
+            if i.consume_shortcut(&ZOOM_OUT_SHORTCUT) {
+                doc.view.zoom_by(1.0 / ZOOM_STEP);
+            }
+        });
+    }
+
+    fn draw_presets_menu(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let mut to_capture = None;
+        let mut to_remove = None;
+        for (i, preset) in self.settings.presets.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let mut button =
+                    egui::Button::new(format!("{} {}", preset.target.icon(), preset.name));
+                if let Some(&key) = PRESET_KEYS.get(i) {
+                    button = button.shortcut_text(
+                        ctx.format_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, key)),
+                    );
+                }
+                if ui.add(button).clicked() {
+                    to_capture = Some(i);
+                }
+                if ui.small_button("🗑").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+        }
+        if self.settings.presets.is_empty() {
+            ui.weak("No presets yet");
+        }
+        ui.separator();
+        ui.add(
+            egui::TextEdit::singleline(&mut self.new_preset_name)
+                .hint_text("Preset name")
+                .desired_width(160.0),
+        );
+        let name = self.new_preset_name.trim().to_string();
+        let last_area = self.settings.last_area;
+        let last_window = self.settings.last_window.clone();
+        let mut new_target = None;
+        if ui
+            .add_enabled(
+                !name.is_empty() && last_area.is_some(),
+                egui::Button::new("Save last area"),
+            )
+            .clicked()
+        {
+            new_target = last_area.map(|rect| PresetTarget::Region { rect });
+        }
+        if ui
+            .add_enabled(
+                !name.is_empty() && last_window.is_some(),
+                egui::Button::new("Save last window"),
+            )
+            .clicked()
+        {
+            new_target = last_window.map(PresetTarget::Window);
+        }
+        if let Some(target) = new_target {
+            self.settings.upsert_preset(CapturePreset { name, target });
+            self.new_preset_name.clear();
+            let _ = self.settings.save();
+        }
+        if let Some(i) = to_remove {
+            self.settings.presets.remove(i);
+            let _ = self.settings.save();
+        }
+        if let Some(i) = to_capture {
+            ui.close_kind(egui::UiKind::Menu);
+            self.capture_preset(i, ctx);
+        }
+    }
+
+    fn capture_target(&mut self, target: &PresetTarget, title: String, ctx: &egui::Context) {
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        std::thread::sleep(std::time::Duration::from_millis(350));
+        if let Some((img, info)) = target.capture(&self.settings.window_capture) {
+            self.load_captured_image(img, CaptureInfo { title, ..info }, ctx);
+        }
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+    }
+
+    fn capture_last_area(&mut self, ctx: &egui::Context) {
+        if let Some(rect) = self.settings.last_area {
+            self.capture_target(&PresetTarget::Region { rect }, "Last Area".to_string(), ctx);
+        }
+    }
+
+    fn capture_preset(&mut self, index: usize, ctx: &egui::Context) {
+        if let Some(preset) = self.settings.presets.get(index).cloned() {
+            self.capture_target(&preset.target, preset.name, ctx);
+        }
+    }
+
+    fn open_file(&mut self, ctx: &egui::Context) {
+        if let Some((img, title)) = pick_image_file() {
+            self.open_tab(Document::new(img, CaptureInfo::named(title), ctx));
+        }
+    }
+
+    fn paste_image(&mut self, ctx: &egui::Context) {
+        if let Some(img) = clipboard_image() {
+            self.load_captured_image(img, CaptureInfo::named("Clipboard"), ctx);
+        }
+    }
+
+    fn handle_file_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.input_mut(|i| i.consume_shortcut(&OPEN_SHORTCUT)) {
+            self.open_file(ctx);
+        }
+        if !self.tabs.is_empty() && ctx.input_mut(|i| i.consume_shortcut(&CLOSE_TAB_SHORTCUT)) {
+            self.request_close_tab(self.active_tab);
+        }
+        // Text fields paste text themselves.
+        if !ctx.wants_keyboard_input() && ctx.input_mut(|i| i.consume_shortcut(&PASTE_SHORTCUT)) {
+            self.paste_image(ctx);
+        }
+    }
+
+    /// Opens images dropped onto the window in new tabs and hints at dropping while hovering.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let (hovering, dropped) =
+            ctx.input(|i| (!i.raw.hovered_files.is_empty(), i.raw.dropped_files.clone()));
+        if hovering {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("drop_overlay"),
+            ));
+            let screen = ctx.content_rect();
+            painter.rect_filled(screen, 0.0, Color32::from_black_alpha(160));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop images to open",
+                egui::FontId::proportional(28.0),
+                Color32::WHITE,
+            );
+        }
+        for file in &dropped {
+            let img = match (&file.path, &file.bytes) {
+                (Some(path), _) => open_image(path).map(|img| (img, file_title(path))),
+                (None, Some(bytes)) => image::load_from_memory(bytes)
+                    .ok()
+                    .map(|img| (img.to_rgba8(), file.name.clone())),
+                (None, None) => None,
+            };
+            if let Some((img, title)) = img {
+                self.open_tab(Document::new(img, CaptureInfo::named(title), ctx));
+            }
+        }
+    }
+
+    fn handle_capture_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.input_mut(|i| i.consume_shortcut(&LAST_AREA_SHORTCUT)) {
+            self.capture_last_area(ctx);
+        }
+        let preset = PRESET_KEYS.iter().position(|&key| {
+            ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, key)))
+        });
+        if let Some(i) = preset {
+            self.capture_preset(i, ctx);
+        }
+    }
+
+    fn draw_settings_window(&mut self, ctx: &egui::Context) {
+        let mut changed = false;
+        egui::Window::new("Settings")
+            .open(&mut self.show_settings)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let picking = &mut self.settings.window_picking;
+                ui.heading("Window picking");
+                changed |= ui
+                    .checkbox(
+                        &mut picking.pick_controls,
+                        "Pick UI elements inside windows",
+                    )
+                    .on_hover_text("Hold Ctrl while picking to toggle temporarily")
+                    .changed();
+                ui.label("Patterns are case-insensitive; * matches anything.");
+                changed |= window_rules_ui(ui, "Include (empty = all)", &mut picking.include);
+                changed |= window_rules_ui(ui, "Exclude", &mut picking.exclude);
+                ui.separator();
+                ui.heading("Window capture");
+                let capture = &mut self.settings.window_capture;
+                changed |= ui
+                    .checkbox(&mut capture.include_frame, "Include title bar and borders")
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut capture.corner_radius, 0.0..=32.0)
+                            .text("Rounded corners"),
+                    )
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut capture.drop_shadow, "Add drop shadow")
+                    .changed();
+                ui.separator();
+                ui.heading("Auto-save");
+                changed |= auto_save_ui(ui, &mut self.settings.auto_save);
+            });
+        if changed {
+            let _ = self.settings.save();
+        }
+    }
+
+    fn draw_frame_window(&mut self, ctx: &egui::Context) {
+        let mut changed = false;
+        egui::Window::new("Frame")
+            .open(&mut self.show_frame)
+            .resizable(false)
+            .show(ctx, |ui| {
+                changed |= ui
+                    .checkbox(
+                        &mut self.settings.frame_enabled,
+                        "Frame copied and saved images",
+                    )
+                    .changed();
+                ui.separator();
+                changed |= frame_style_ui(ui, &mut self.settings.frame);
+                ui.separator();
+                ui.label("Presets");
+                let mut to_remove = None;
+                for (i, preset) in self.settings.frame_presets.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .selectable_label(self.settings.frame == preset.style, &preset.name)
+                            .clicked()
+                        {
+                            self.settings.frame = preset.style.clone();
+                            changed = true;
+                        }
+                        if ui.small_button("🗑").clicked() {
+                            to_remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = to_remove {
+                    self.settings.frame_presets.remove(i);
+                    changed = true;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_frame_preset_name)
+                            .hint_text("Preset name")
+                            .desired_width(120.0),
+                    );
+                    let name = self.new_frame_preset_name.trim().to_string();
+                    if ui
+                        .add_enabled(!name.is_empty(), egui::Button::new("Save preset"))
+                        .clicked()
+                    {
+                        let style = self.settings.frame.clone();
+                        match self
+                            .settings
+                            .frame_presets
+                            .iter_mut()
+                            .find(|p| p.name == name)
+                        {
+                            Some(existing) => existing.style = style,
+                            None => self
+                                .settings
+                                .frame_presets
+                                .push(FramePreset { name, style }),
+                        }
+                        self.new_frame_preset_name.clear();
+                        changed = true;
+                    }
+                });
+                if let Some(tex) = self
+                    .tabs
+                    .get(self.active_tab)
+                    .and_then(|doc| doc.frame_preview.as_ref())
+                    .map(|(_, tex)| tex)
+                {
+                    ui.separator();
+                    ui.image((tex.id(), tex.size_vec2()));
+                }
+            });
+        if changed {
+            let _ = self.settings.save();
+        }
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        doc.sync_frame_thumbnail(FRAME_PREVIEW_SIZE);
+        let style = &self.settings.frame;
+        // Other tabs are framed again once they are shown.
+        if doc.frame_preview.as_ref().is_none_or(|(s, _)| s != style)
+            && let Some((thumbnail, scale)) = doc.frame_thumbnail()
+        {
+            let preview = style.apply_scaled(thumbnail, scale, &mut self.frame_backgrounds);
+            doc.frame_preview = Some((style.clone(), load_texture(ctx, "frame_preview", &preview)));
+        }
+    }
+
+    /// The active image as it gets copied or saved: annotations baked in, framed if enabled.
+    fn export_image(&self) -> Option<image::RgbaImage> {
+        let img = self.doc()?.flattened_image()?;
+        Some(if self.settings.frame_enabled {
+            self.settings.frame.apply(&img)
+        } else {
+            img
+        })
+    }
+
+    /// Saves to the auto-save folder if auto-save is on for exports, otherwise asks where.
+    /// Returns whether the image was saved.
+    fn save_to_file(&mut self) -> bool {
+        if self.settings.auto_save.applies(AutoSaveTrigger::Export) {
+            self.auto_save(AutoSaveTrigger::Export)
+        } else {
+            self.save_as()
+        }
+    }
+
+    fn save_as(&mut self) -> bool {
+        let (Some(original), Some(path)) = (
+            self.export_image(),
+            rfd::FileDialog::new()
+                .add_filter("PNG", &["png"])
+                .set_file_name("screenshot.png")
+                .save_file(),
+        ) else {
+            return false;
+        };
+        let saved = original.save(path).is_ok();
+        if saved && let Some(doc) = self.doc_mut() {
+            doc.dirty = false;
+        }
+        saved
+    }
+
+    fn copy_to_clipboard(&mut self) {
+        let (Some(original), Ok(mut clipboard)) = (self.export_image(), Clipboard::new()) else {
+            return;
+        };
+        let copied = clipboard.set_image(arboard::ImageData {
+            width: original.width() as usize,
+            height: original.height() as usize,
+            bytes: std::borrow::Cow::Borrowed(original.as_raw()),
+        });
+        if copied.is_ok()
+            && !self.auto_save(AutoSaveTrigger::Export)
+            && let Some(doc) = self.doc_mut()
+        {
+            doc.dirty = false;
+        }
+    }
+
+    /// Writes the active image to the auto-save folder if auto-save is on for `trigger`.
+    /// Returns whether it was written.
+    fn auto_save(&mut self, trigger: AutoSaveTrigger) -> bool {
+        if !self.settings.auto_save.applies(trigger) {
+            return false;
+        }
+        let (Some(img), Some(info)) = (self.export_image(), self.doc().map(|d| d.info.clone()))
+        else {
+            return false;
+        };
+        let saved = self.settings.auto_save.save(&img, &info).is_ok();
+        // The counter may have moved on.
+        let _ = self.settings.save();
+        if saved && let Some(doc) = self.doc_mut() {
+            doc.dirty = false;
+        }
+        saved
+    }
+
+    fn draw_layers_panel(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("layers_panel")
+            .default_width(200.0)
+            .show(ctx, |ui| {
+                ui.heading("Layers");
+                ui.separator();
+                let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+                    return;
+                };
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let mut to_remove = None;
+                    for (i, ann) in doc.annotations.iter().enumerate().rev() {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .selectable_label(
+                                    doc.active_annotation_index == Some(i),
+                                    ann.label(),
+                                )
+                                .clicked()
+                            {
+                                doc.active_annotation_index = Some(i);
+                            }
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if ui.button("🗑").clicked() {
+                                        to_remove = Some(i);
+                                    }
+                                },
+                            );
+                        });
+                    }
+                    if let Some(i) = to_remove {
+                        doc.checkpoint();
+                        doc.annotations.remove(i);
+                        doc.active_annotation_index = None;
+                    }
+                });
+                annotation_properties_ui(ui, doc);
+            });
+    }
+
+    fn draw_main_canvas(&mut self, ui: &mut egui::Ui) {
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            self.draw_empty_state(ui);
+            return;
+        };
+        let (texture_id, texture_size) = (doc.texture.id(), doc.texture.size_vec2());
+        doc.sync_layer_textures(ui.ctx());
+        let region = doc.visible_region();
+        let cropping = doc.crop_edit.is_some();
+        let (canvas, resp) =
+            ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
+        doc.view
+            .layout(canvas, region.size(), ui.ctx().pixels_per_point());
+        let panning = self.handle_view_input(ui.ctx(), &resp);
+        let Some(view) = self.doc().map(|doc| doc.view) else {
+            return;
+        };
+        let (rect, scale) = (view.image_rect(), view.scale());
+        let canvas_painter = ui.painter_at(canvas);
+        let mut mesh = egui::Mesh::with_texture(texture_id);
+        mesh.add_rect_with_uv(
+            rect,
+            Rect::from_min_max(
+                (region.min.to_vec2() / texture_size).to_pos2(),
+                (region.max.to_vec2() / texture_size).to_pos2(),
+            ),
+            Color32::WHITE,
+        );
+        canvas_painter.add(egui::Shape::mesh(mesh));
+        if !panning {
+            if cropping {
+                self.handle_crop_interactions(ui.ctx(), &resp, rect);
+            } else {
+                self.handle_canvas_interactions(&resp, rect, scale);
+            }
+        }
+        let painter = ui.painter_at(rect.intersect(canvas));
+        if let Some(doc) = self.tabs.get_mut(self.active_tab) {
+            // After the interactions, so spotlights and stickers show this frame's edits.
+            doc.sync_spotlight_textures(ui.ctx(), rect, painter.clip_rect());
+            doc.sync_sticker_textures(ui.ctx());
+            doc.sync_redact_textures(ui.ctx());
+            if self.current_tool == Tool::Rect {
+                doc.sync_ui_elements();
+            }
+            if self.show_codes {
+                doc.sync_codes();
+            }
+        }
+        if let Some(doc) = self.doc() {
+            let editing = self.text_draft.as_ref().and_then(|d| d.index);
+            for (i, ann) in doc.annotations.iter().enumerate() {
+                if editing != Some(i) {
+                    let active = doc.active_annotation_index == Some(i);
+                    doc.draw_annotation(&painter, ann, rect, active);
+                }
+            }
+            doc.draw_snap_lines(&painter, &self.snap_lines, rect);
+            if self.show_text {
+                let matches = ocr::find(doc.text_lines(), &self.text_query);
+                doc.draw_highlights(&painter, matches, Color32::from_rgb(0xFF, 0xC0, 0x00), rect);
+            }
+            if self.show_redact
+                && let Some(proposals) = &doc.redactions
+            {
+                for (accepted, color) in [(false, Color32::GRAY), (true, Color32::RED)] {
+                    let rects = proposals.iter().filter(|p| p.accepted == accepted);
+                    doc.draw_highlights(&painter, rects.map(|p| p.rect), color, rect);
+                }
+            }
+            if self.show_codes {
+                let codes = doc.codes().into_iter().map(|c| c.rect);
+                doc.draw_highlights(&painter, codes, Color32::from_rgb(0x40, 0x90, 0xFF), rect);
+            }
+        }
+        if cropping {
+            self.draw_crop_overlay(&painter, rect);
+        } else {
+            self.draw_drawing_preview(ui.ctx(), &painter, rect);
+            self.draw_element_hint(ui.ctx(), &painter, rect, scale);
+            self.handle_text_editing(ui, &painter, rect);
+        }
+        let zoom_label = canvas_painter.layout_no_wrap(
+            format!("{:.0}%", view.zoom_factor() * 100.0),
+            egui::FontId::proportional(13.0),
+            Color32::WHITE,
+        );
+        let label_rect = Rect::from_min_size(
+            canvas.right_bottom() - zoom_label.size() - Vec2::splat(12.0),
+            zoom_label.size(),
+        );
+        canvas_painter.rect_filled(label_rect.expand(4.0), 4.0, Color32::from_black_alpha(160));
+        canvas_painter.galley(label_rect.min, zoom_label, Color32::WHITE);
+    }
+
+    /// Mouse-wheel zoom around the cursor and space/middle-drag panning.
+    /// Returns whether the canvas is being panned, in which case tools must ignore the drag.
+    fn handle_view_input(&mut self, ctx: &egui::Context, resp: &egui::Response) -> bool {
+        let space = !ctx.wants_keyboard_input() && ctx.input(|i| i.key_down(Key::Space));
+        let middle = resp.dragged_by(egui::PointerButton::Middle);
+        if space || middle {
+            ctx.set_cursor_icon(if resp.dragged() {
+                egui::CursorIcon::Grabbing
+            } else {
+                egui::CursorIcon::Grab
+            });
+        }
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return false;
+        };
+        if middle || (space && resp.dragged_by(egui::PointerButton::Primary)) {
+            doc.view.pan_by(resp.drag_delta());
+        }
+        // Zooming mid-stroke would move the stroke's start point under the cursor.
+        if let Some(hover) = resp.hover_pos()
+            && self.drag_start.is_none()
+        {
+            let (scroll, pinch) = ctx.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            let factor = pinch * (scroll * 0.002).exp();
+            if (factor - 1.0).abs() > f32::EPSILON {
+                doc.view.zoom_at(factor, hover);
+            }
+        }
+        space || middle
+    }
+
+    fn draw_empty_state(&mut self, ui: &mut egui::Ui) {
+        ui.centered_and_justified(|ui| {
+            ui.vertical(|ui| {
+                ui.heading("SnapCrab");
+                ui.label("Select a capture mode, or open, paste or drop an image to begin");
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button("🖥 Fullscreen").clicked()
+                        && let Some(img) = capture::fullscreen()
+                    {
+                        let info = CaptureInfo::screen("Fullscreen");
+                        self.load_captured_image(img, info, ui.ctx());
+                    }
+                    if ui.button("🪟 Window").clicked() {
+                        self.enter_pick_mode(AppState::PickingWindow, ui.ctx());
                     }
                     if ui.button("✂ Area").clicked() {
                         self.enter_pick_mode(AppState::PickingArea, ui.ctx());
                     }
+                    if ui.button("📂 Open").clicked() {
+                        self.open_file(ui.ctx());
+                    }
+                });
+            });
+        });
+    }
+
+    fn handle_canvas_interactions(&mut self, resp: &egui::Response, rect: Rect, scale: f32) {
+        if resp.clicked() || resp.double_clicked() || resp.drag_started() {
+            self.commit_text();
+        }
+        let text_tool = matches!(self.current_tool, Tool::Text | Tool::Callout);
+        if (resp.clicked() && text_tool) || resp.double_clicked() {
+            self.start_text_editing(resp, rect, scale);
+        }
+        if resp.clicked() && self.current_tool == Tool::Sticker {
+            self.place_sticker(resp, rect);
+        }
+        if resp.clicked() && self.current_tool == Tool::Rect {
+            self.box_element(resp, rect, scale);
+        }
+        if resp.clicked()
+            && self.current_tool == Tool::Ocr
+            && let Some(region) = self.doc().map(Document::visible_region)
+        {
+            self.recognize_text(region);
+        }
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        if resp.drag_started_by(egui::PointerButton::Primary) {
+            let Some(pos_ui) = resp.interact_pointer_pos() else {
+                return;
+            };
+            if !self.settings.disable_snapping {
+                doc.sync_ui_edges();
+            }
+            self.dragged_handle = doc.handle_at(pos_ui, rect);
+            if self.dragged_handle.is_none() {
+                let pos_img = doc.ui_to_image(pos_ui, rect);
+                doc.active_annotation_index = doc
+                    .annotations
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(_, ann)| ann.hit_test(pos_img, 10.0 / scale))
+                    .map(|(i, _)| i);
+            }
+            self.drag_original = doc
+                .active_annotation_index
+                .and_then(|i| doc.annotations.get(i))
+                .cloned();
+            if self.drag_original.is_some() {
+                doc.move_checkpoint = Some(doc.snapshot());
+            } else {
+                self.drag_start = Some(pos_ui);
+            }
+        }
+        if resp.dragged_by(egui::PointerButton::Primary) {
+            if resp.drag_delta() != Vec2::ZERO {
+                doc.commit_move();
+            }
+            let (Some(idx), Some(original), Some(origin), Some(pointer)) = (
+                doc.active_annotation_index,
+                &self.drag_original,
+                resp.ctx.input(|i| i.pointer.press_origin()),
+                resp.interact_pointer_pos(),
+            ) else {
+                return;
+            };
+            let guides = (!self.settings.disable_snapping).then(|| doc.guides(Some(idx)));
+            let (ann, lines) = dragged_annotation(
+                original,
+                self.dragged_handle,
+                (
+                    doc.ui_to_image(origin, rect),
+                    doc.ui_to_image(pointer, rect),
+                ),
+                guides.as_ref().map(|g| (g, snap::SNAP_DISTANCE / scale)),
+                resp.ctx.input(|i| i.modifiers.shift),
+            );
+            self.snap_lines = lines;
+            if let Some(slot) = doc.annotations.get_mut(idx) {
+                *slot = ann;
+            }
+        }
+        if resp.drag_stopped_by(egui::PointerButton::Primary) {
+            doc.move_checkpoint = None;
+            self.dragged_handle = None;
+            self.drag_original = None;
+            self.snap_lines.clear();
+            if let (Some(start), Some(end)) = (self.drag_start, resp.interact_pointer_pos()) {
+                self.finalize_drawing(start, end, rect, &resp.ctx);
+            }
+            self.drag_start = None;
+        }
+    }
+
+    fn finalize_drawing(&mut self, start_ui: Pos2, end_ui: Pos2, rect: Rect, ctx: &egui::Context) {
+        let Some((start, end, _)) = self
+            .doc()
+            .map(|doc| self.drawing_points(doc, (start_ui, end_ui), rect, ctx))
+        else {
+            return;
+        };
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        if start.distance(end) <= 1.0 {
+            return;
+        }
+        let ann = match self.current_tool {
+            Tool::Arrow => Annotation::Arrow {
+                start,
+                end,
+                control: None,
+                color: self.current_color,
+                thickness: self.stroke_thickness,
+                style: self.arrow_style,
+                stroke: self.settings.stroke,
+            },
+            Tool::Rect => Annotation::Rect {
+                rect: Rect::from_two_pos(start, end),
+                angle: 0.0,
+                color: self.current_color,
+                thickness: self.stroke_thickness,
+                stroke: self.settings.stroke,
+            },
+            // The callout is added once its text has been typed.
+            Tool::Callout => {
+                let ann = self.new_callout(Rect::from_two_pos(start, end));
+                self.open_text_draft(TextDraft { ann, index: None }, ctx);
+                return;
+            }
+            // New areas join the topmost spotlight, unless Shift starts another one.
+            Tool::Spotlight => {
+                let area = SpotArea {
+                    rect: Rect::from_two_pos(start, end),
+                    shape: self.spot_shape,
+                };
+                let existing = doc
+                    .annotations
+                    .iter()
+                    .rposition(|ann| matches!(ann, Annotation::Spotlight { .. }))
+                    .filter(|_| !ctx.input(|i| i.modifiers.shift));
+                doc.checkpoint();
+                if let Some(i) = existing
+                    && let Annotation::Spotlight { areas, .. } = &mut doc.annotations[i]
+                {
+                    areas.push(area);
+                    doc.active_annotation_index = Some(i);
+                    return;
+                }
+                Annotation::Spotlight {
+                    areas: vec![area],
+                    effect: self.spot_effect,
+                    strength: self.spot_strength,
+                }
+            }
+            Tool::Magnifier => {
+                let source = Rect::from_two_pos(start, end);
+                Annotation::Magnifier {
+                    source,
+                    centre: magnifier::place(source, self.magnifier_zoom, image_bounds(&doc.image)),
+                    zoom: self.magnifier_zoom,
+                    color: self.current_color,
+                    thickness: self.stroke_thickness,
+                    connector: self.magnifier_connector,
+                }
+            }
+            Tool::Sticker => {
+                let dragged = Rect::from_two_pos(start, end);
+                Annotation::Sticker {
+                    rect: Rect::from_center_size(
+                        dragged.center(),
+                        fit_aspect(dragged.size(), self.sticker.aspect()),
+                    ),
+                    sticker: self.sticker.clone(),
+                    angle: 0.0,
+                    color: self.current_color,
+                }
+            }
+            Tool::Redact => Annotation::Redact {
+                rect: Rect::from_two_pos(start, end),
+                style: self.redact_style,
+            },
+            Tool::Ocr => {
+                self.recognize_text(Rect::from_two_pos(start, end));
+                return;
+            }
+            Tool::Text | Tool::Crop => return,
+        };
+        doc.checkpoint();
+        doc.annotations.push(ann);
+        if matches!(self.current_tool, Tool::Spotlight | Tool::Sticker) {
+            doc.active_annotation_index = Some(doc.annotations.len() - 1);
+        }
+    }
+
+    /// Where a drag from `start_ui` to `end_ui` draws from and to in the image, each end
+    /// snapped to the guides unless snapping is off, and the guides they snapped to. With
+    /// Shift, arrows point in steps of 45° instead.
+    fn drawing_points(
+        &self,
+        doc: &Document,
+        (start_ui, end_ui): (Pos2, Pos2),
+        rect: Rect,
+        ctx: &egui::Context,
+    ) -> (Pos2, Pos2, Vec<[Pos2; 2]>) {
+        let (start, end) = (
+            doc.ui_to_image(start_ui, rect),
+            doc.ui_to_image(end_ui, rect),
+        );
+        if self.current_tool == Tool::Arrow && ctx.input(|i| i.modifiers.shift) {
+            return (start, snap::snap_angle(start, end), Vec::new());
+        }
+        if self.settings.disable_snapping {
+            return (start, end, Vec::new());
+        }
+        let guides = doc.guides(None);
+        let distance = snap::SNAP_DISTANCE * doc.visible_region().width() / rect.width();
+        let (from, to) = (
+            guides.snap_point(start, distance),
+            guides.snap_point(end, distance),
+        );
+        let mut lines = from.lines;
+        lines.extend(to.lines);
+        (start + from.offset, end + to.offset, lines)
+    }
+
+    /// A callout filling `rect`, with its tail below it, in the tool's current style.
+    fn new_callout(&self, rect: Rect) -> Annotation {
+        Annotation::Callout {
+            rect,
+            tail: rect.left_bottom() + Vec2::new(rect.width() * 0.2, rect.height() * 0.6),
+            text: String::new(),
+            shape: self.callout_shape,
+            color: self.current_color,
+            fill: self.callout_fill,
+            size: self.text_size,
+            angle: 0.0,
+        }
+    }
+
+    /// Puts the chosen sticker where the canvas was clicked, at the tool's size.
+    fn place_sticker(&mut self, resp: &egui::Response, rect: Rect) {
+        let (Some(doc), Some(pos_ui)) = (
+            self.tabs.get_mut(self.active_tab),
+            resp.interact_pointer_pos(),
+        ) else {
+            return;
+        };
+        let size = fit_aspect(Vec2::splat(self.sticker_size), self.sticker.aspect());
+        doc.checkpoint();
+        doc.annotations.push(Annotation::Sticker {
+            sticker: self.sticker.clone(),
+            rect: Rect::from_center_size(doc.ui_to_image(pos_ui, rect), size),
+            angle: 0.0,
+            color: self.current_color,
+        });
+        doc.active_annotation_index = Some(doc.annotations.len() - 1);
+    }
+
+    /// The box a click with the box tool at `pos_ui` would draw around the UI element under
+    /// it, unless it is over an annotation.
+    fn element_box(&self, pos_ui: Pos2, rect: Rect, scale: f32) -> Option<Annotation> {
+        let doc = self.doc().filter(|_| self.current_tool == Tool::Rect)?;
+        let pos = doc.ui_to_image(pos_ui, rect);
+        if doc
+            .annotations
+            .iter()
+            .any(|ann| ann.hit_test(pos, 10.0 / scale))
+        {
+            return None;
+        }
+        Some(Annotation::Rect {
+            rect: doc.ui_element_at(pos)?,
+            angle: 0.0,
+            color: self.current_color,
+            thickness: self.stroke_thickness,
+            stroke: self.settings.stroke,
+        })
+    }
+
+    /// Boxes the UI element that was clicked with the box tool.
+    fn box_element(&mut self, resp: &egui::Response, rect: Rect, scale: f32) {
+        let Some(ann) = resp
+            .interact_pointer_pos()
+            .and_then(|pos_ui| self.element_box(pos_ui, rect, scale))
+        else {
+            return;
+        };
+        if let Some(doc) = self.tabs.get_mut(self.active_tab) {
+            doc.checkpoint();
+            doc.annotations.push(ann);
+        }
+    }
+
+    /// Shows faintly the box a click would draw around the UI element under the pointer.
+    fn draw_element_hint(&self, ctx: &egui::Context, painter: &Painter, rect: Rect, scale: f32) {
+        if self.drag_start.is_some() || ctx.dragged_id().is_some() {
+            return;
+        }
+        let (Some(doc), Some(pos_ui)) = (self.doc(), ctx.pointer_hover_pos()) else {
+            return;
+        };
+        if !painter.clip_rect().contains(pos_ui) {
+            return;
+        }
+        if let Some(mut ann) = self.element_box(pos_ui, rect, scale) {
+            if let Annotation::Rect { color, .. } = &mut ann {
+                *color = color.gamma_multiply(0.5);
+            }
+            doc.draw_annotation(painter, &ann, rect, false);
+        }
+    }
+
+    fn draw_sticker_panel(&mut self, ctx: &egui::Context) {
+        let folder_stickers = self
+            .folder_stickers
+            .get_or_insert_with(|| sticker::load_folder(&self.settings.sticker_folder))
+            .clone();
+        let (mut choose_folder, mut reload) = (false, false);
+        egui::SidePanel::left("sticker_panel")
+            .default_width(200.0)
+            .show(ctx, |ui| {
+                ui.heading("Stickers");
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.label("Stamps");
+                    ui.horizontal_wrapped(|ui| {
+                        for stamp in Stamp::ALL {
+                            self.sticker_button(ui, Sticker::Stamp(stamp));
+                        }
+                    });
+                    ui.separator();
+                    ui.label("Emoji");
+                    ui.horizontal_wrapped(|ui| {
+                        for emoji in EMOJI {
+                            ui.selectable_value(
+                                &mut self.sticker,
+                                Sticker::Emoji(emoji),
+                                egui::RichText::new(emoji).size(22.0),
+                            );
+                        }
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Folder");
+                        choose_folder = ui.button("📂 Folder…").clicked();
+                        reload = ui.button("⟳").on_hover_text("Reload").clicked();
+                    });
+                    if self.settings.sticker_folder.as_os_str().is_empty() {
+                        ui.weak("No folder chosen");
+                    } else {
+                        ui.weak(self.settings.sticker_folder.display().to_string());
+                    }
+                    ui.horizontal_wrapped(|ui| {
+                        for file in &folder_stickers {
+                            self.sticker_button(ui, Sticker::File(file.clone()));
+                        }
+                    });
+                    if folder_stickers.is_empty() {
+                        ui.weak("PNG, JPEG, WebP, BMP and SVG files in it show up here");
+                    }
+                });
+            });
+        if choose_folder && let Some(folder) = rfd::FileDialog::new().pick_folder() {
+            self.settings.sticker_folder = folder;
+            let _ = self.settings.save();
+            reload = true;
+        }
+        if reload {
+            self.folder_stickers = None;
+            self.sticker_thumbnails
+                .retain(|(s, _)| !matches!(s, Sticker::File(_)));
+        }
+    }
+
+    /// A thumbnail button that chooses `sticker`.
+    fn sticker_button(&mut self, ui: &mut egui::Ui, sticker: Sticker) {
+        let selected = self.sticker == sticker;
+        let resp = match sticker_thumbnail(&mut self.sticker_thumbnails, &sticker, ui.ctx()) {
+            Some(texture) => ui.add(
+                egui::Button::image((
+                    texture.id(),
+                    texture.size_vec2() / ui.ctx().pixels_per_point(),
+                ))
+                .selected(selected),
+            ),
+            None => ui.selectable_label(selected, sticker.label()),
+        };
+        if resp.on_hover_text(sticker.label()).clicked() {
+            self.sticker = sticker;
+        }
+    }
+
+    fn draw_drawing_preview(&self, ctx: &egui::Context, painter: &Painter, rect: Rect) {
+        let (Some(doc), Some(start_ui), Some(end_ui)) =
+            (self.doc(), self.drag_start, ctx.pointer_latest_pos())
+        else {
+            return;
+        };
+        let (start, end, lines) = self.drawing_points(doc, (start_ui, end_ui), rect, ctx);
+        if self.current_tool != Tool::Text {
+            doc.draw_snap_lines(painter, &lines, rect);
+        }
+        let temp = match self.current_tool {
+            Tool::Arrow => Some(Annotation::Arrow {
+                start,
+                end,
+                control: None,
+                color: self.current_color,
+                thickness: self.stroke_thickness,
+                style: self.arrow_style,
+                stroke: self.settings.stroke,
+            }),
+            Tool::Rect => Some(Annotation::Rect {
+                rect: Rect::from_two_pos(start, end),
+                angle: 0.0,
+                color: self.current_color,
+                thickness: self.stroke_thickness,
+                stroke: self.settings.stroke,
+            }),
+            Tool::Callout => Some(self.new_callout(Rect::from_two_pos(start, end))),
+            Tool::Sticker | Tool::Redact | Tool::Ocr => {
+                let dragged = Rect::from_two_pos(start, end);
+                let outline = if self.current_tool == Tool::Sticker {
+                    Rect::from_center_size(
+                        dragged.center(),
+                        fit_aspect(dragged.size(), self.sticker.aspect()),
+                    )
+                } else {
+                    dragged
+                };
+                painter.rect_stroke(
+                    Rect::from_min_max(
+                        doc.image_to_ui(outline.min, rect),
+                        doc.image_to_ui(outline.max, rect),
+                    ),
+                    0.0,
+                    Stroke::new(1.5, Color32::from_white_alpha(160)),
+                    egui::StrokeKind::Middle,
+                );
+                None
+            }
+            Tool::Magnifier => {
+                let source = Rect::from_two_pos(start, end);
+                Some(Annotation::Magnifier {
+                    source,
+                    centre: magnifier::place(source, self.magnifier_zoom, image_bounds(&doc.image)),
+                    zoom: self.magnifier_zoom,
+                    color: self.current_color,
+                    thickness: self.stroke_thickness,
+                    connector: self.magnifier_connector,
+                })
+            }
+            Tool::Spotlight => {
+                let area = SpotArea {
+                    rect: Rect::from_two_pos(start, end),
+                    shape: self.spot_shape,
+                };
+                doc.draw_spot_area(painter, &area, rect);
+                None
+            }
+            _ => None,
+        };
+        if let Some(ann) = temp {
+            doc.draw_annotation(painter, &ann, rect, false);
+        }
+    }
+
+    /// Starts or stops editing the crop as the crop tool is selected or deselected.
+    fn sync_crop_tool(&mut self) {
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        match (self.current_tool == Tool::Crop, doc.crop_edit.is_some()) {
+            (true, false) => {
+                let rect = doc.crop.unwrap_or_else(|| image_bounds(&doc.image));
+                doc.crop_edit = Some(CropEdit::new(rect));
+                doc.view.set_zoom(Zoom::Fit);
+                self.drag_start = None;
+                self.commit_text();
+            }
+            (false, true) => {
+                doc.crop_edit = None;
+                doc.view.set_zoom(Zoom::Fit);
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_crop(&mut self) {
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        let Some(edit) = &doc.crop_edit else {
+            return;
+        };
+        let crop = (edit.rect != image_bounds(&doc.image)).then_some(edit.rect);
+        if crop != doc.crop {
+            doc.checkpoint();
+            doc.crop = crop;
+        }
+        self.current_tool = Tool::Arrow;
+        self.sync_crop_tool();
+    }
+
+    fn handle_crop_interactions(&mut self, ctx: &egui::Context, resp: &egui::Response, rect: Rect) {
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        let Some(edit) = &doc.crop_edit else {
+            return;
+        };
+        let bounds = image_bounds(&doc.image);
+        let ratio = self.settings.crop_aspect.ratio(bounds.size());
+        let crop_ui = Rect::from_min_max(
+            doc.image_to_ui(edit.rect.min, rect),
+            doc.image_to_ui(edit.rect.max, rect),
+        );
+        let hovered = resp.hover_pos().and_then(|p| Handle::at(crop_ui, p));
+        let pointer = resp.interact_pointer_pos();
+        let pointer_img = pointer.map(|p| doc.ui_to_image(p, rect));
+        let Some(edit) = &mut doc.crop_edit else {
+            return;
+        };
+        if resp.drag_started_by(egui::PointerButton::Primary)
+            && let (Some(p), Some(p_img)) = (pointer, pointer_img)
+        {
+            edit.begin_drag(Handle::at(crop_ui, p), p_img);
+        }
+        if resp.dragged_by(egui::PointerButton::Primary)
+            && let Some(p) = pointer_img
+        {
+            edit.drag_to(p, ratio, bounds);
+        }
+        if resp.drag_stopped_by(egui::PointerButton::Primary) {
+            edit.end_drag();
+        }
+        if !edit.dragging()
+            && let Some(handle) = hovered
+        {
+            ctx.set_cursor_icon(handle.cursor());
+        }
+        if resp.double_clicked() && hovered == Some(Handle::MOVE) {
+            self.apply_crop();
+        }
+    }
+
+    fn draw_crop_overlay(&self, painter: &Painter, rect: Rect) {
+        let Some(doc) = self.doc() else {
+            return;
+        };
+        let Some(edit) = &doc.crop_edit else {
+            return;
+        };
+        let crop = Rect::from_min_max(
+            doc.image_to_ui(edit.rect.min, rect),
+            doc.image_to_ui(edit.rect.max, rect),
+        );
+        let shade = Color32::from_black_alpha(150);
+        for outside in [
+            Rect::from_min_max(rect.min, Pos2::new(rect.max.x, crop.min.y)),
+            Rect::from_min_max(Pos2::new(rect.min.x, crop.max.y), rect.max),
+            Rect::from_min_max(
+                Pos2::new(rect.min.x, crop.min.y),
+                Pos2::new(crop.min.x, crop.max.y),
+            ),
+            Rect::from_min_max(
+                Pos2::new(crop.max.x, crop.min.y),
+                Pos2::new(rect.max.x, crop.max.y),
+            ),
+        ] {
+            painter.rect_filled(outside, 0.0, shade);
+        }
+        let thirds = Stroke::new(1.0, Color32::from_white_alpha(60));
+        for t in [1.0 / 3.0, 2.0 / 3.0] {
+            let (x, y) = (
+                crop.lerp_inside(Vec2::new(t, 0.0)).x,
+                crop.lerp_inside(Vec2::new(0.0, t)).y,
+            );
+            painter.vline(x, crop.y_range(), thirds);
+            painter.hline(crop.x_range(), y, thirds);
+        }
+        painter.rect_stroke(
+            crop,
+            0.0,
+            Stroke::new(1.0, Color32::WHITE),
+            StrokeKind::Outside,
+        );
+        for handle in Handle::ALL {
+            painter.rect(
+                Rect::from_center_size(handle.pos(crop), Vec2::splat(8.0)),
+                1.0,
+                Color32::WHITE,
+                Stroke::new(1.0, Color32::from_black_alpha(160)),
+                StrokeKind::Outside,
+            );
+        }
+        painter.text(
+            crop.left_top() + Vec2::new(6.0, 6.0),
+            egui::Align2::LEFT_TOP,
+            format!("{} × {}", edit.rect.width(), edit.rect.height()),
+            egui::FontId::proportional(13.0),
+            Color32::WHITE,
+        );
+    }
+
+    fn draw_crop_window(&mut self, ctx: &egui::Context) {
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        let (Some(edit), original) = (&mut doc.crop_edit, &doc.image) else {
+            return;
+        };
+        let bounds = image_bounds(original);
+        let (mut apply, mut cancel, mut aspect_changed) = (false, false, false);
+        egui::Window::new("✂ Crop")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -12.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    aspect_changed =
+                        crop_aspect_ui(ui, &mut self.settings.crop_aspect, edit.rect.size());
+                });
+                let ratio = self.settings.crop_aspect.ratio(bounds.size());
+                if aspect_changed {
+                    let keep_width = ratio.is_none_or(|r| edit.rect.aspect_ratio() <= r);
+                    edit.rect = crop::constrain(edit.rect, ratio, keep_width, bounds);
+                }
+                let [mut x, mut y, mut w, mut h] = [
+                    edit.rect.min.x,
+                    edit.rect.min.y,
+                    edit.rect.width(),
+                    edit.rect.height(),
+                ]
+                .map(|v| v as u32);
+                let (mut moved, mut width_changed, mut height_changed) = (false, false, false);
+                egui::Grid::new("crop_rect").num_columns(4).show(ui, |ui| {
+                    ui.label("X");
+                    moved |= ui
+                        .add(egui::DragValue::new(&mut x).range(0..=original.width() - 1))
+                        .changed();
+                    ui.label("Width");
+                    width_changed |= ui
+                        .add(egui::DragValue::new(&mut w).range(1..=original.width()))
+                        .changed();
+                    ui.end_row();
+                    ui.label("Y");
+                    moved |= ui
+                        .add(egui::DragValue::new(&mut y).range(0..=original.height() - 1))
+                        .changed();
+                    ui.label("Height");
+                    height_changed |= ui
+                        .add(egui::DragValue::new(&mut h).range(1..=original.height()))
+                        .changed();
+                    ui.end_row();
+                });
+                if moved || width_changed || height_changed {
+                    let rect = Rect::from_min_size(
+                        Pos2::new(x as f32, y as f32),
+                        Vec2::new(w as f32, h as f32),
+                    );
+                    edit.rect = crop::constrain(rect, ratio, !height_changed, bounds);
+                }
+                ui.horizontal(|ui| {
+                    apply = ui.button("✔ Apply").clicked();
+                    cancel = ui.button("✖ Cancel").clicked();
+                    if ui.button("↺ Reset").clicked() {
+                        edit.rect = crop::constrain(bounds, ratio, true, bounds);
+                    }
                 });
+                ui.weak("Drag handles to adjust, Enter to apply, Esc to cancel.");
             });
-        });
+        if aspect_changed {
+            let _ = self.settings.save();
+        }
+        if apply {
+            self.apply_crop();
+        } else if cancel {
+            self.current_tool = Tool::Arrow;
+        }
     }
 
-    fn handle_canvas_interactions(
-        &mut self,
-        resp: &egui::Response,
-        rect: Rect,
-        scale: f32,
-        ctx: &egui::Context,
-    ) {
-        if resp.drag_started() {
-            let Some(pos_ui) = resp.interact_pointer_pos() else {
-                return;
-            };
-            let pos_img = self.ui_to_image(pos_ui, rect);
-            if self.current_tool != Tool::Crop {
-                self.active_annotation_index = self
-                    .annotations
-                    .iter()
-                    .enumerate()
-                    .rev()
-                    .find(|(_, ann)| ann.hit_test(pos_img, 10.0 / scale))
-                    .map(|(i, _)| i);
+    /// Opens the editor on the text or callout under the pointer, or on new text there if
+    /// the text tool is selected. Editing picks up the annotation's color, size and style.
+    fn start_text_editing(&mut self, resp: &egui::Response, rect: Rect, scale: f32) {
+        let (Some(doc), Some(pos_ui)) = (
+            self.tabs.get_mut(self.active_tab),
+            resp.interact_pointer_pos(),
+        ) else {
+            return;
+        };
+        let pos = doc.ui_to_image(pos_ui, rect);
+        let hit = doc
+            .annotations
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, ann)| ann.hit_test(pos, 10.0 / scale));
+        let draft = match hit {
+            Some((
+                i,
+                ann @ Annotation::Text {
+                    color, size, style, ..
+                },
+            )) => {
+                self.current_tool = Tool::Text;
+                (self.current_color, self.text_size, self.text_style) = (*color, *size, *style);
+                TextDraft {
+                    ann: ann.clone(),
+                    index: Some(i),
+                }
             }
-            if self.active_annotation_index.is_none() {
-                self.drag_start = Some(pos_ui);
-                if self.current_tool == Tool::Text {
-                    self.editing_text_pos = Some(pos_ui);
-                    self.temp_text.clear();
+            Some((
+                i,
+                ann @ Annotation::Callout {
+                    shape,
+                    color,
+                    fill,
+                    size,
+                    ..
+                },
+            )) => {
+                self.current_tool = Tool::Callout;
+                (self.current_color, self.text_size) = (*color, *size);
+                (self.callout_shape, self.callout_fill) = (*shape, *fill);
+                TextDraft {
+                    ann: ann.clone(),
+                    index: Some(i),
                 }
             }
-        }
-        if resp.dragged() {
-            let (Some(idx), delta) = (self.active_annotation_index, resp.drag_delta() / scale)
-            else {
-                return;
-            };
-            if let Some(ann) = self.annotations.get_mut(idx) {
-                ann.translate(delta);
+            None if self.current_tool == Tool::Text => TextDraft {
+                ann: Annotation::Text {
+                    pos,
+                    text: String::new(),
+                    color: self.current_color,
+                    size: self.text_size,
+                    style: self.text_style,
+                    angle: 0.0,
+                },
+                index: None,
+            },
+            _ => return,
+        };
+        doc.active_annotation_index = draft.index;
+        self.open_text_draft(draft, &resp.ctx);
+    }
+
+    fn open_text_draft(&mut self, draft: TextDraft, ctx: &egui::Context) {
+        self.text_draft = Some(draft);
+        ctx.memory_mut(|m| m.request_focus(egui::Id::new(TEXT_EDITOR_ID)));
+    }
+
+    /// The draft's annotation in the tool's current color, size and style.
+    fn draft_annotation(&self, draft: &TextDraft) -> Annotation {
+        let mut ann = draft.ann.clone();
+        match &mut ann {
+            Annotation::Text {
+                color, size, style, ..
+            } => {
+                (*color, *size, *style) = (self.current_color, self.text_size, self.text_style);
             }
-        }
-        if resp.drag_stopped() {
-            if let (Some(start), Some(end)) = (self.drag_start, resp.interact_pointer_pos()) {
-                self.finalize_drawing(start, end, rect, ctx);
+            Annotation::Callout {
+                shape,
+                color,
+                fill,
+                size,
+                ..
+            } => {
+                (*color, *size) = (self.current_color, self.text_size);
+                (*shape, *fill) = (self.callout_shape, self.callout_fill);
             }
-            self.drag_start = None;
+            _ => {}
         }
+        ann
     }
 
-    fn finalize_drawing(&mut self, start_ui: Pos2, end_ui: Pos2, rect: Rect, ctx: &egui::Context) {
-        let (start, end) = (
-            self.ui_to_image(start_ui, rect),
-            self.ui_to_image(end_ui, rect),
-        );
-        if start.distance(end) <= 1.0 {
+    /// Puts the text being edited on the canvas. Text that was emptied is removed, while
+    /// callouts stay as empty boxes.
+    fn commit_text(&mut self) {
+        let Some(draft) = self.text_draft.take() else {
+            return;
+        };
+        let ann = self.draft_annotation(&draft);
+        let Some(doc) = self.tabs.get_mut(self.active_tab) else {
+            return;
+        };
+        // The annotation may have gone in the meantime, e.g. through undo.
+        let original = draft.index.and_then(|i| doc.annotations.get(i));
+        if original == Some(&ann) {
             return;
         }
-        match self.current_tool {
-            Tool::Arrow => self.annotations.push(Annotation::Arrow {
-                start,
-                end,
-                color: self.current_color,
-                thickness: self.stroke_thickness,
-            }),
-            Tool::Rect => self.annotations.push(Annotation::Rect {
-                rect: Rect::from_two_pos(start, end),
-                color: self.current_color,
-                thickness: self.stroke_thickness,
-            }),
-            Tool::Crop => {
-                if let Some(ref bg) = self.original_image {
-                    let crop = Rect::from_two_pos(start, end);
-                    let (x, y) = (crop.min.x as u32, crop.min.y as u32);
-                    let (w, h) = (
-                        (crop.width() as u32).min(bg.width().saturating_sub(x)),
-                        (rect.height() as u32).min(bg.height().saturating_sub(y)),
-                    );
-                    if w > 0 && h > 0 {
-                        self.load_captured_image(
-                            image::imageops::crop_imm(bg, x, y, w, h).to_image(),
-                            ctx,
-                        );
-                    }
-                    self.current_tool = Tool::Arrow;
-                }
+        let index = draft.index.filter(|_| {
+            original.is_some_and(|o| std::mem::discriminant(o) == std::mem::discriminant(&ann))
+        });
+        let remove = matches!(&ann, Annotation::Text { text, .. } if text.trim().is_empty());
+        if remove && index.is_none() {
+            return;
+        }
+        doc.checkpoint();
+        match index {
+            Some(i) if remove => {
+                doc.annotations.remove(i);
+                doc.active_annotation_index = None;
             }
-            _ => {}
+            Some(i) => doc.annotations[i] = ann,
+            None => doc.annotations.push(ann),
         }
     }
 
-    fn draw_drawing_preview(&self, ctx: &egui::Context, painter: &Painter, rect: Rect) {
-        let (Some(start_ui), Some(end_ui)) = (self.drag_start, ctx.pointer_latest_pos()) else {
+    /// Shows the text being edited as it will look, with an invisible `TextEdit` on top for
+    /// the cursor, selection and IME. Its lines are moved to where the text layout puts them.
+    fn handle_text_editing(&mut self, ui: &mut egui::Ui, painter: &Painter, rect: Rect) {
+        if !matches!(self.current_tool, Tool::Text | Tool::Callout) {
+            self.commit_text();
+            return;
+        }
+        let finish = ui.input(|i| {
+            i.key_pressed(Key::Escape) || (i.modifiers.command && i.key_pressed(Key::Enter))
+        });
+        let Some(mut preview) = self.text_draft.as_ref().map(|d| self.draft_annotation(d)) else {
             return;
         };
-        if self.current_tool == Tool::Crop {
-            painter.rect_stroke(
-                Rect::from_two_pos(start_ui, end_ui),
-                0.0,
-                Stroke::new(2.0, Color32::WHITE),
-                StrokeKind::Outside,
+        // Text is edited upright, so the editor's cursor and selection line up with it.
+        if let Some(angle) = preview.angle_mut() {
+            *angle = 0.0;
+        }
+        let (Some(doc), Some((pos, layout))) = (self.doc(), preview.text_layout()) else {
+            return;
+        };
+        doc.draw_annotation(painter, &preview, rect, false);
+        let scale = rect.width() / doc.visible_region().width();
+        let text_rect = layout.rect(pos);
+        let editor_rect = Rect::from_min_size(
+            doc.image_to_ui(text_rect.min, rect),
+            // Room for the cursor when the text is empty.
+            (text_rect.size() * scale).max(Vec2::new(2.0, layout.font_size() * scale)),
+        );
+        let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, _wrap_width: f32| {
+            let mut ann = preview.clone();
+            if let Some(t) = ann.text_mut() {
+                *t = text.as_str().to_string();
+            }
+            let format = egui::TextFormat {
+                font_id: egui::FontId::new(
+                    layout.font_size() * scale,
+                    layout.style().font.family(),
+                ),
+                color: Color32::TRANSPARENT,
+                italics: layout.style().italic,
+                ..egui::TextFormat::default()
+            };
+            let mut job = egui::text::LayoutJob::single_section(text.as_str().to_string(), format);
+            job.wrap.max_width = layout.wrap_width() * scale;
+            let mut galley = (*ui.fonts_mut(|f| f.layout_job(job))).clone();
+            if let Some((_, layout)) = ann.text_layout() {
+                for (row, (_, start)) in galley.rows.iter_mut().zip(layout.lines(text.as_str())) {
+                    row.pos = (start.to_vec2() * scale).to_pos2();
+                }
+                galley.rect =
+                    Rect::from_min_size(Pos2::ZERO, layout.rect(Pos2::ZERO).size() * scale);
+                galley.mesh_bounds = galley.mesh_bounds.union(galley.rect);
+            }
+            std::sync::Arc::new(galley)
+        };
+        let Some(text) = self.text_draft.as_mut().and_then(|d| d.ann.text_mut()) else {
+            return;
+        };
+        ui.put(
+            editor_rect,
+            egui::TextEdit::multiline(text)
+                .id(egui::Id::new(TEXT_EDITOR_ID))
+                .layouter(&mut layouter)
+                .frame(false)
+                .margin(egui::Margin::ZERO)
+                .desired_rows(1)
+                .desired_width(editor_rect.width())
+                .min_size(Vec2::ZERO),
+        );
+        if finish {
+            self.commit_text();
+        }
+    }
+}
+
+fn open_image(path: &Path) -> Option<image::RgbaImage> {
+    image::open(path).ok().map(|img| img.to_rgba8())
+}
+
+/// The image and its file name, for the tab title.
+fn pick_image_file() -> Option<(image::RgbaImage, String)> {
+    let path = rfd::FileDialog::new()
+        .add_filter("Images", &IMAGE_EXTENSIONS)
+        .pick_file()?;
+    Some((open_image(&path)?, file_title(&path)))
+}
+
+fn file_title(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| "Image".to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+fn clipboard_image() -> Option<image::RgbaImage> {
+    let data = Clipboard::new().ok()?.get_image().ok()?;
+    image::RgbaImage::from_raw(
+        data.width as u32,
+        data.height as u32,
+        data.bytes.into_owned(),
+    )
+}
+
+/// The history panel thumbnail for `entry`, loaded from disk the first time it is shown.
+fn recent_thumbnail<'a>(
+    cache: &'a mut Vec<(String, Option<egui::TextureHandle>)>,
+    entry: &RecentCapture,
+    ctx: &egui::Context,
+) -> Option<&'a egui::TextureHandle> {
+    let i = cache
+        .iter()
+        .position(|(id, _)| *id == entry.id)
+        .unwrap_or_else(|| {
+            let texture = entry
+                .load_thumbnail()
+                .map(|img| load_texture(ctx, "history_thumbnail", &img));
+            cache.push((entry.id.clone(), texture));
+            cache.len() - 1
+        });
+    cache[i].1.as_ref()
+}
+
+/// The thumbnail of `sticker`, drawn the first time it is asked for.
+fn sticker_thumbnail<'a>(
+    cache: &'a mut Vec<(Sticker, Option<egui::TextureHandle>)>,
+    sticker: &Sticker,
+    ctx: &egui::Context,
+) -> Option<&'a egui::TextureHandle> {
+    let i = cache
+        .iter()
+        .position(|(s, _)| s == sticker)
+        .unwrap_or_else(|| {
+            let size = fit_aspect(
+                Vec2::splat(STICKER_THUMBNAIL_SIZE * ctx.pixels_per_point()),
+                sticker.aspect(),
             );
-        } else {
-            let temp = match self.current_tool {
-                Tool::Arrow => Some(Annotation::Arrow {
-                    start: self.ui_to_image(start_ui, rect),
-                    end: self.ui_to_image(end_ui, rect),
-                    color: self.current_color,
-                    thickness: self.stroke_thickness,
-                }),
-                Tool::Rect => Some(Annotation::Rect {
-                    rect: Rect::from_two_pos(
-                        self.ui_to_image(start_ui, rect),
-                        self.ui_to_image(end_ui, rect),
-                    ),
-                    color: self.current_color,
-                    thickness: self.stroke_thickness,
-                }),
-                _ => None,
+            let texture = sticker.render(size, Color32::WHITE).map(|pixmap| {
+                load_texture(ctx, "sticker_thumbnail", &render::from_pixmap(&pixmap))
+            });
+            cache.push((sticker.clone(), texture));
+            cache.len() - 1
+        });
+    cache[i].1.as_ref()
+}
+
+/// `original` dragged by a handle, or moved as a whole if `handle` is `None`, from `from` to
+/// `to`. It snaps to `guides` within their distance, while `shift` turns rotate handles in
+/// steps of 15° and arrow ends in steps of 45° instead. Also returns the guides it snapped to.
+fn dragged_annotation(
+    original: &Annotation,
+    handle: Option<usize>,
+    (from, to): (Pos2, Pos2),
+    guides: Option<(&snap::Guides, f32)>,
+    shift: bool,
+) -> (Annotation, Vec<[Pos2; 2]>) {
+    let mut ann = original.clone();
+    let snapped = |rect: Rect| {
+        guides.map_or_else(snap::Snap::default, |(guides, distance)| {
+            guides.snap(rect, distance)
+        })
+    };
+    let snap = match handle {
+        Some(handle) if ann.is_rotate_handle(handle) => {
+            ann.rotate_towards(to, shift);
+            snap::Snap::default()
+        }
+        Some(handle) => {
+            let handles = original.handles();
+            let (start, mut end) = (handles[handle], handles[handle] + (to - from));
+            let arrow_end = matches!(original, Annotation::Arrow { .. }) && handle < 2;
+            let snap = if shift && arrow_end {
+                end = snap::snap_angle(handles[1 - handle], end);
+                snap::Snap::default()
+            } else {
+                snapped(Rect::from_min_max(end, end))
             };
-            if let Some(ann) = temp {
-                self.draw_annotation(painter, &ann, rect, false);
+            ann.move_handle(handle, end + snap.offset - start);
+            snap
+        }
+        None => {
+            let snap = snapped(original.bounds().translate(to - from));
+            ann.translate(to - from + snap.offset);
+            snap
+        }
+    };
+    (ann, snap.lines)
+}
+
+/// The largest size with `aspect` (width over height) that fits in `size`.
+fn fit_aspect(size: Vec2, aspect: f32) -> Vec2 {
+    if size.x / size.y.max(f32::EPSILON) > aspect {
+        Vec2::new(size.y * aspect, size.y)
+    } else {
+        Vec2::new(size.x, size.x / aspect.max(f32::EPSILON))
+    }
+}
+
+fn window_rules_ui(ui: &mut egui::Ui, heading: &str, rules: &mut Vec<WindowRule>) -> bool {
+    let mut changed = false;
+    ui.separator();
+    ui.label(heading);
+    let mut to_remove = None;
+    egui::Grid::new(heading).num_columns(3).show(ui, |ui| {
+        ui.weak("Title");
+        ui.weak("App");
+        ui.end_row();
+        for (i, rule) in rules.iter_mut().enumerate() {
+            changed |= ui
+                .add(egui::TextEdit::singleline(&mut rule.title).desired_width(160.0))
+                .changed();
+            changed |= ui
+                .add(egui::TextEdit::singleline(&mut rule.app_name).desired_width(100.0))
+                .changed();
+            if ui.small_button("🗑").clicked() {
+                to_remove = Some(i);
             }
+            ui.end_row();
         }
+    });
+    if let Some(i) = to_remove {
+        rules.remove(i);
+        changed = true;
+    }
+    if ui.small_button("➕ Add rule").clicked() {
+        rules.push(WindowRule::default());
+        changed = true;
     }
+    changed
+}
 
-    fn handle_text_editing(&mut self, ctx: &egui::Context, rect: Rect) {
-        let Some(pos_ui) = self.editing_text_pos else {
-            return;
-        };
-        egui::Window::new("Enter Text")
-            .fixed_pos(pos_ui)
-            .title_bar(false)
-            .collapsible(false)
-            .resizable(false)
-            .show(ctx, |ui| {
-                let res = ui.text_edit_singleline(&mut self.temp_text);
-                res.request_focus();
-                if res.lost_focus() || ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
-                    if !self.temp_text.is_empty() {
-                        self.annotations.push(Annotation::Text {
-                            pos: self.ui_to_image(pos_ui, rect),
-                            text: self.temp_text.clone(),
-                            color: self.current_color,
-                            size: self.text_size,
-                        });
-                    }
-                    self.editing_text_pos = None;
+fn text_style_ui(ui: &mut egui::Ui, style: &mut TextStyle) {
+    egui::ComboBox::from_id_salt("text_font")
+        .selected_text(style.font.name())
+        .show_ui(ui, |ui| {
+            for font in TextFont::ALL {
+                ui.selectable_value(&mut style.font, font, font.name());
+            }
+        });
+    ui.toggle_value(&mut style.bold, egui::RichText::new("B").strong())
+        .on_hover_text("Bold");
+    ui.toggle_value(&mut style.italic, egui::RichText::new("I").italics())
+        .on_hover_text("Italic");
+    for align in TextAlign::ALL {
+        ui.selectable_value(&mut style.align, align, align.icon())
+            .on_hover_text(align.name());
+    }
+    optional_color_ui(ui, &mut style.background, "Box", Color32::WHITE);
+    optional_color_ui(ui, &mut style.outline, "Outline", Color32::BLACK);
+}
+
+/// Edits `style`. The response covers its sliders, so it is dragged while one of them is.
+fn stroke_style_ui(ui: &mut egui::Ui, style: &mut StrokeStyle) -> egui::Response {
+    egui::Grid::new("stroke_style")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Line");
+            ui.horizontal(|ui| {
+                for dash in Dash::ALL {
+                    ui.selectable_value(&mut style.dash, dash, dash.name());
                 }
             });
+            ui.end_row();
+            ui.label("Pattern");
+            let mut resp = ui
+                .horizontal(|ui| {
+                    let dash = ui.add_enabled(
+                        style.dash == Dash::Dashed,
+                        egui::DragValue::new(&mut style.pattern[0])
+                            .range(stroke::MIN_PATTERN..=20.0)
+                            .speed(0.1)
+                            .prefix("dash "),
+                    );
+                    let gap = ui.add_enabled(
+                        style.dash != Dash::Solid,
+                        egui::DragValue::new(&mut style.pattern[1])
+                            .range(stroke::MIN_PATTERN..=20.0)
+                            .speed(0.1)
+                            .prefix("gap "),
+                    );
+                    dash | gap
+                })
+                .inner;
+            ui.end_row();
+            ui.label("Caps");
+            ui.add_enabled_ui(style.dash != Dash::Dotted, |ui| {
+                ui.horizontal(|ui| {
+                    for cap in Cap::ALL {
+                        ui.selectable_value(&mut style.cap, cap, cap.name());
+                    }
+                });
+            });
+            ui.end_row();
+            ui.label("Corners");
+            ui.add_enabled_ui(style.dash != Dash::Dotted, |ui| {
+                ui.horizontal(|ui| {
+                    for join in Join::ALL {
+                        ui.selectable_value(&mut style.join, join, join.name());
+                    }
+                });
+            });
+            ui.end_row();
+            ui.label("Opacity");
+            resp |= ui.add(egui::Slider::new(&mut style.opacity, 0.0..=1.0));
+            ui.end_row();
+            resp
+        })
+        .inner
+}
+
+/// Edits the style of the selected annotation, as one undo step per click or slider drag.
+fn annotation_properties_ui(ui: &mut egui::Ui, doc: &mut Document) {
+    let Some(index) = doc.active_annotation_index else {
+        return;
+    };
+    let Some(mut ann) = doc.annotations.get(index).cloned() else {
+        return;
+    };
+    let resp = match &mut ann {
+        Annotation::Arrow { stroke, .. } => {
+            ui.separator();
+            ui.label("Stroke");
+            stroke_style_ui(ui, stroke)
+        }
+        Annotation::Rect { stroke, angle, .. } => {
+            ui.separator();
+            ui.label("Stroke");
+            let resp = stroke_style_ui(ui, stroke);
+            resp | rotation_ui(ui, angle)
+        }
+        Annotation::Text { angle, .. } | Annotation::Callout { angle, .. } => {
+            ui.separator();
+            rotation_ui(ui, angle)
+        }
+        Annotation::Spotlight {
+            areas,
+            effect,
+            strength,
+        } => {
+            ui.separator();
+            ui.label("Spotlight");
+            let resp = spotlight_style_ui(ui, effect, strength);
+            spot_areas_ui(ui, areas);
+            resp
+        }
+        Annotation::Magnifier {
+            zoom, connector, ..
+        } => {
+            ui.separator();
+            ui.label("Magnifier");
+            magnifier_style_ui(ui, zoom, connector)
+        }
+        Annotation::Sticker {
+            sticker,
+            angle,
+            color,
+            ..
+        } => {
+            ui.separator();
+            ui.label("Sticker");
+            let resp = rotation_ui(ui, angle);
+            if matches!(sticker, Sticker::Emoji(_)) {
+                ui.color_edit_button_srgba(color);
+            }
+            resp
+        }
+        Annotation::Redact { style, .. } => {
+            ui.separator();
+            ui.label("Redaction");
+            redact_style_ui(ui, style)
+        }
+        Annotation::Image { .. } => return,
+    };
+    if resp.drag_started() {
+        doc.move_checkpoint = Some(doc.snapshot());
+    }
+    if doc.annotations.get(index) != Some(&ann) {
+        if doc.move_checkpoint.is_some() {
+            doc.commit_move();
+        } else {
+            doc.checkpoint();
+        }
+        doc.annotations[index] = ann;
+    }
+    if resp.drag_stopped() {
+        doc.move_checkpoint = None;
+    }
+}
+
+/// A slider for an angle in radians, shown in degrees.
+fn rotation_ui(ui: &mut egui::Ui, angle: &mut f32) -> egui::Response {
+    let mut degrees = angle.to_degrees();
+    let resp = ui.add(
+        egui::Slider::new(&mut degrees, -180.0..=180.0)
+            .text("Rotation")
+            .suffix("°"),
+    );
+    if resp.changed() {
+        *angle = degrees.to_radians();
+    }
+    resp
+}
+
+/// The effect of a spotlight. The response is the strength slider's.
+fn spotlight_style_ui(
+    ui: &mut egui::Ui,
+    effect: &mut SpotEffect,
+    strength: &mut f32,
+) -> egui::Response {
+    for e in SpotEffect::ALL {
+        ui.selectable_value(effect, e, e.name());
+    }
+    ui.add(egui::Slider::new(strength, 0.0..=1.0).text("Strength"))
+}
+
+/// The aspect ratio presets and the sides of custom ratios. "Custom" starts from the ratio of
+/// `crop`, the size of the crop rect. Returns whether the aspect changed.
+fn crop_aspect_ui(ui: &mut egui::Ui, aspect: &mut Aspect, crop: Vec2) -> bool {
+    let mut changed = false;
+    ui.label("Aspect");
+    egui::ComboBox::from_id_salt("crop_aspect")
+        .selected_text(aspect.label())
+        .show_ui(ui, |ui| {
+            for (label, preset) in Aspect::PRESETS {
+                changed |= ui.selectable_value(aspect, preset, label).changed();
+            }
+        });
+    // Ratios with larger sides would be clamped by the fields below.
+    let in_range = aspect.in_range();
+    changed |= in_range != *aspect;
+    *aspect = in_range;
+    if let Aspect::Ratio { w, h } = aspect {
+        let side = |side| {
+            egui::DragValue::new(side)
+                .range(1.0..=crop::MAX_RATIO_SIDE)
+                .speed(0.1)
+        };
+        changed |= ui.add(side(w)).changed();
+        ui.label(":");
+        changed |= ui.add(side(h)).changed();
+    } else if ui.button("Custom").clicked() {
+        *aspect = Aspect::of_size(crop);
+        changed = true;
+    }
+    changed
+}
+
+fn redact_style_ui(ui: &mut egui::Ui, style: &mut RedactStyle) -> egui::Response {
+    RedactStyle::ALL
+        .map(|s| ui.selectable_value(style, s, s.name()))
+        .into_iter()
+        .reduce(|a, b| a | b)
+        .expect("there are redaction styles")
+}
+
+/// The regular expressions auto-redact looks for besides the built-in kinds. Invalid ones are
+/// marked.
+fn redact_patterns_ui(ui: &mut egui::Ui, patterns: &mut Vec<String>) -> bool {
+    let mut changed = false;
+    let mut to_remove = None;
+    for (i, pattern) in patterns.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(pattern)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(220.0),
+                )
+                .changed();
+            if let Err(err) = regex::Regex::new(pattern) {
+                ui.colored_label(ui.visuals().error_fg_color, "⚠")
+                    .on_hover_text(err.to_string());
+            }
+            if ui.small_button("🗑").clicked() {
+                to_remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = to_remove {
+        patterns.remove(i);
+        changed = true;
+    }
+    if ui.small_button("➕ Add pattern").clicked() {
+        patterns.push(String::new());
+        changed = true;
+    }
+    changed
+}
+
+/// The zoom and connector of a magnifier. The response is the zoom slider's.
+fn magnifier_style_ui(ui: &mut egui::Ui, zoom: &mut f32, connector: &mut bool) -> egui::Response {
+    let resp = ui.add(
+        egui::Slider::new(zoom, magnifier::ZOOM_RANGE)
+            .text("Zoom")
+            .suffix("×"),
+    );
+    ui.checkbox(connector, "Connector");
+    resp
+}
+
+/// The shape of each area of a spotlight, and a button to remove it while there are others.
+fn spot_areas_ui(ui: &mut egui::Ui, areas: &mut Vec<SpotArea>) {
+    let mut to_remove = None;
+    let removable = areas.len() > 1;
+    for (i, area) in areas.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            for shape in SpotShape::ALL {
+                ui.selectable_value(&mut area.shape, shape, shape.name());
+            }
+            if removable && ui.button("🗑").clicked() {
+                to_remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = to_remove {
+        areas.remove(i);
+    }
+}
+
+fn arrow_style_ui(ui: &mut egui::Ui, style: &mut ArrowStyle) {
+    ui.add_enabled_ui(style.shaft != ArrowShaft::Tapered, |ui| {
+        for head in ArrowHead::ALL {
+            ui.selectable_value(&mut style.head, head, head.name());
+        }
+    });
+    ui.checkbox(&mut style.double, "Both ends");
+    egui::ComboBox::from_id_salt("arrow_shaft")
+        .selected_text(style.shaft.name())
+        .show_ui(ui, |ui| {
+            for shaft in ArrowShaft::ALL {
+                ui.selectable_value(&mut style.shaft, shaft, shaft.name());
+            }
+        });
+}
+
+fn callout_style_ui(ui: &mut egui::Ui, shape: &mut CalloutShape, fill: &mut Color32) {
+    for s in CalloutShape::ALL {
+        ui.selectable_value(shape, s, s.name());
+    }
+    ui.label("Fill");
+    ui.color_edit_button_srgba(fill);
+}
+
+/// A checkbox that turns `color` on with `default`, and a colour picker while it is on.
+fn optional_color_ui(
+    ui: &mut egui::Ui,
+    color: &mut Option<Color32>,
+    label: &str,
+    default: Color32,
+) {
+    let mut on = color.is_some();
+    ui.checkbox(&mut on, label);
+    match (on, color.as_mut()) {
+        (true, Some(c)) => {
+            ui.color_edit_button_srgba(c);
+        }
+        (true, None) => *color = Some(default),
+        (false, _) => *color = None,
     }
 }
 
+fn auto_save_ui(ui: &mut egui::Ui, auto_save: &mut AutoSave) -> bool {
+    let mut changed = ui
+        .checkbox(
+            &mut auto_save.enabled,
+            "Save images to a folder automatically",
+        )
+        .changed();
+    ui.add_enabled_ui(auto_save.enabled, |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("📂 Folder…").clicked()
+                && let Some(folder) = rfd::FileDialog::new().pick_folder()
+            {
+                auto_save.folder = folder;
+                changed = true;
+            }
+            if auto_save.folder.as_os_str().is_empty() {
+                ui.weak("No folder chosen");
+            } else {
+                ui.label(auto_save.folder.display().to_string());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Save");
+            for (trigger, label) in [
+                (AutoSaveTrigger::Capture, "every capture"),
+                (AutoSaveTrigger::Export, "on copy and save"),
+            ] {
+                changed |= ui
+                    .radio_value(&mut auto_save.trigger, trigger, label)
+                    .changed();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("File name");
+            let help = PLACEHOLDERS.map(|(p, description)| format!("{p}: {description}"));
+            changed |= ui
+                .add(egui::TextEdit::singleline(&mut auto_save.template).desired_width(220.0))
+                .on_hover_text(help.join("\n"))
+                .changed();
+        });
+        let example = CaptureInfo {
+            title: "Untitled - Notepad".into(),
+            window_title: "Untitled - Notepad".into(),
+            app: "Notepad".into(),
+            monitor: "DISPLAY1".into(),
+        };
+        let name = auto_save.file_name(&example, Timestamp::now(), auto_save.counter + 1);
+        ui.weak(format!("e.g. {name}.png"));
+    });
+    changed
+}
+
+fn frame_style_ui(ui: &mut egui::Ui, style: &mut FrameStyle) -> bool {
+    let mut changed = false;
+    changed |= ui
+        .add(egui::Slider::new(&mut style.padding, 0.0..=256.0).text("Padding"))
+        .changed();
+    changed |= ui
+        .add(egui::Slider::new(&mut style.corner_radius, 0.0..=48.0).text("Corner radius"))
+        .changed();
+    changed |= ui
+        .add(egui::Slider::new(&mut style.shadow, 0.0..=64.0).text("Shadow"))
+        .changed();
+    ui.horizontal(|ui| {
+        ui.label("Window chrome");
+        for (chrome, label) in [
+            (Chrome::None, "None"),
+            (Chrome::Light, "Light"),
+            (Chrome::Dark, "Dark"),
+        ] {
+            changed |= ui
+                .selectable_value(&mut style.chrome, chrome, label)
+                .changed();
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Background");
+        for (label, default) in &Background::KINDS {
+            let same = std::mem::discriminant(&style.background) == std::mem::discriminant(default);
+            if ui.selectable_label(same, *label).clicked() && !same {
+                style.background = default.clone();
+                changed = true;
+            }
+        }
+    });
+    ui.horizontal(|ui| match &mut style.background {
+        Background::Solid { color } => {
+            changed |= ui.color_edit_button_srgba(color).changed();
+        }
+        Background::Gradient { from, to, angle } => {
+            changed |= ui.color_edit_button_srgba(from).changed();
+            changed |= ui.color_edit_button_srgba(to).changed();
+            changed |= ui
+                .add(egui::Slider::new(angle, 0.0..=360.0).suffix("°"))
+                .changed();
+        }
+        Background::Image { path } => {
+            if ui.button("📂 Choose…").clicked()
+                && let Some(picked) = rfd::FileDialog::new()
+                    .add_filter("Images", &IMAGE_EXTENSIONS)
+                    .pick_file()
+            {
+                *path = picked;
+                changed = true;
+            }
+            ui.weak(
+                path.file_name()
+                    .map_or("No image".into(), |n| n.to_string_lossy()),
+            );
+        }
+    });
+    changed
+}
+
 impl eframe::App for SnapCrabApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_close_request(ctx);
         if self.state != AppState::Normal {
             self.draw_picking_ui(ctx);
             return;
         }
+        self.handle_dropped_files(ctx);
+        // Before the file shortcuts, whose Ctrl+V also matches Ctrl+Shift+V.
+        self.handle_edit_shortcuts(ctx);
+        self.handle_file_shortcuts(ctx);
+        self.handle_capture_shortcuts(ctx);
+        self.handle_view_shortcuts(ctx);
         self.draw_top_panel(ctx);
+        if !self.tabs.is_empty() {
+            self.draw_tab_bar(ctx);
+        }
+        self.sync_crop_tool();
+        if self.show_history {
+            self.draw_history_panel(ctx);
+        }
+        if self.current_tool == Tool::Sticker {
+            self.draw_sticker_panel(ctx);
+        }
         if self.show_layers {
             self.draw_layers_panel(ctx);
         }
+        if self.show_settings {
+            self.draw_settings_window(ctx);
+        }
+        if self.show_frame {
+            self.draw_frame_window(ctx);
+        }
+        if self.show_resize {
+            self.draw_resize_window(ctx);
+        }
+        if self.show_expand {
+            self.draw_expand_window(ctx);
+        }
+        if self.show_text {
+            self.draw_text_window(ctx);
+        }
+        if self.show_redact {
+            self.draw_redact_window(ctx);
+        }
+        if self.show_codes {
+            self.draw_codes_window(ctx);
+        }
+        self.draw_crop_window(ctx);
+        self.draw_close_prompt(ctx);
         egui::CentralPanel::default().show(ctx, |ui| self.draw_main_canvas(ui));
     }
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
+    let cli = CliArgs::parse(std::env::args().skip(1))?;
     let icon = image::load_from_memory(include_bytes!("../assets/snapcrab.png"))
         .map_err(|e| eyre!("Failed to load icon: {e}"))
         .ok()
@@ -858,7 +3754,7 @@ fn main() -> Result<()> {
                 .with_icon(icon.unwrap_or_default()),
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(SnapCrabApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(SnapCrabApp::new(cc, &cli)))),
     )
     .map_err(|e| eyre!(e.to_string()))
 }
@@ -0,0 +1,326 @@
+use std::ops::Range;
+
+use eframe::egui::{Pos2, Rect};
+use xcap::image::RgbaImage;
+
+use crate::capture;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OcrError {
+    #[error("the area to read is empty")]
+    EmptyRegion,
+    #[cfg(all(windows, feature = "ocr"))]
+    #[error("text recognition failed: {0}")]
+    Windows(#[from] windows::core::Error),
+}
+
+/// A recognized word and where it is in the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub text: String,
+    pub rect: Rect,
+}
+
+/// A line of recognized words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub words: Vec<Word>,
+}
+
+impl Line {
+    /// The words separated by spaces.
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The area covered by the words that `range`, in bytes of [`Self::text`], touches.
+    pub fn span_rect(&self, range: Range<usize>) -> Option<Rect> {
+        let mut start = 0;
+        let mut rect: Option<Rect> = None;
+        for word in &self.words {
+            let end = start + word.text.len();
+            if start < range.end && range.start < end {
+                rect = Some(rect.map_or(word.rect, |r| r.union(word.rect)));
+            }
+            start = end + 1;
+        }
+        rect
+    }
+}
+
+/// Something that reads text in images. Implemented by the engine built into Windows when
+/// the `ocr` feature is on, and by stand-ins elsewhere.
+pub trait OcrEngine {
+    /// The lines of text in `img`, top to bottom, with word positions in its pixels.
+    fn recognize(&self, img: &RgbaImage) -> Result<Vec<Line>, OcrError>;
+}
+
+/// The OCR engine this build comes with, if any.
+pub fn engine() -> Option<Box<dyn OcrEngine>> {
+    #[cfg(all(windows, feature = "ocr"))]
+    return Some(Box::new(windows_ocr::WindowsOcr));
+    #[cfg(not(all(windows, feature = "ocr")))]
+    None
+}
+
+/// The lines of text within `region` of `img`, positioned in `img`'s pixels.
+pub fn recognize(
+    engine: &dyn OcrEngine,
+    img: &RgbaImage,
+    region: Rect,
+) -> Result<Vec<Line>, OcrError> {
+    let region = Rect::from_min_max(region.min.round(), region.max.round());
+    let part = capture::crop(img, region).ok_or(OcrError::EmptyRegion)?;
+    let offset = region.min.max(Pos2::ZERO).to_vec2();
+    let mut lines = engine.recognize(&part)?;
+    for word in lines.iter_mut().flat_map(|l| &mut l.words) {
+        word.rect = word.rect.translate(offset);
+    }
+    Ok(lines)
+}
+
+/// The recognized text, one line per line.
+pub fn text(lines: &[Line]) -> String {
+    lines.iter().map(Line::text).collect::<Vec<_>>().join("\n")
+}
+
+/// Where `query` appears in the recognized text, ignoring case. Matches spanning several
+/// words are covered by one rect.
+pub fn find(lines: &[Line], query: &str) -> Vec<Rect> {
+    let query: Vec<char> = query.trim().chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut found = Vec::new();
+    for line in lines {
+        let text = line.text();
+        // Lowercased characters with the byte each one came from.
+        let chars: Vec<(usize, char)> = text
+            .char_indices()
+            .flat_map(|(i, c)| c.to_lowercase().map(move |l| (i, l)))
+            .collect();
+        for start in 0..chars.len() {
+            let Some(window) = chars.get(start..start + query.len()) else {
+                break;
+            };
+            if window.iter().map(|&(_, c)| c).eq(query.iter().copied())
+                && let Some(rect) = line.span_rect(window[0].0..window[window.len() - 1].0 + 1)
+            {
+                found.push(rect);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(all(windows, feature = "ocr"))]
+mod windows_ocr {
+    use eframe::egui::{Pos2, Rect, Vec2};
+    use windows::{
+        Graphics::Imaging::{BitmapAlphaMode, BitmapPixelFormat, SoftwareBitmap},
+        Media::Ocr::OcrEngine,
+        Security::Cryptography::CryptographicBuffer,
+    };
+    use xcap::image::{
+        imageops::{self, FilterType},
+        RgbaImage,
+    };
+
+    use super::{Line, OcrError, Word};
+
+    /// `Windows.Media.Ocr`, in the languages of the user's profile. It runs offline.
+    pub struct WindowsOcr;
+
+    impl super::OcrEngine for WindowsOcr {
+        fn recognize(&self, img: &RgbaImage) -> Result<Vec<Line>, OcrError> {
+            let engine = OcrEngine::TryCreateFromUserProfileLanguages()?;
+            // The engine rejects images larger than this, so those are read scaled down.
+            let max = OcrEngine::MaxImageDimension()? as f32;
+            let scale = (max / img.width().max(img.height()) as f32).min(1.0);
+            let img = if scale < 1.0 {
+                imageops::resize(
+                    img,
+                    (img.width() as f32 * scale) as u32,
+                    (img.height() as f32 * scale) as u32,
+                    FilterType::Triangle,
+                )
+            } else {
+                img.clone()
+            };
+            let (width, height) = (img.width() as i32, img.height() as i32);
+            let mut bgra = img.into_raw();
+            for px in bgra.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+            let bitmap = SoftwareBitmap::CreateCopyWithAlphaFromBuffer(
+                &CryptographicBuffer::CreateFromByteArray(&bgra)?,
+                BitmapPixelFormat::Bgra8,
+                width,
+                height,
+                BitmapAlphaMode::Straight,
+            )?;
+            let result = engine.RecognizeAsync(&bitmap)?.get()?;
+            let mut lines = Vec::new();
+            for line in result.Lines()? {
+                let mut words = Vec::new();
+                for word in line.Words()? {
+                    let r = word.BoundingRect()?;
+                    words.push(Word {
+                        text: word.Text()?.to_string(),
+                        rect: Rect::from_min_size(
+                            Pos2::new(r.X, r.Y) / scale,
+                            Vec2::new(r.Width, r.Height) / scale,
+                        ),
+                    });
+                }
+                lines.push(Line { words });
+            }
+            Ok(lines)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use eframe::egui::Vec2;
+
+    use super::*;
+
+    /// Reads the same lines from every image, and remembers how large the last one was.
+    struct FixedOcr {
+        lines: Vec<Line>,
+        size: Cell<Option<(u32, u32)>>,
+    }
+
+    impl FixedOcr {
+        fn new(lines: Vec<Line>) -> Self {
+            Self {
+                lines,
+                size: Cell::new(None),
+            }
+        }
+    }
+
+    impl OcrEngine for FixedOcr {
+        fn recognize(&self, img: &RgbaImage) -> Result<Vec<Line>, OcrError> {
+            self.size.set(Some(img.dimensions()));
+            Ok(self.lines.clone())
+        }
+    }
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect::from_min_size(Pos2::new(x, y), Vec2::new(w, h))
+    }
+
+    /// A line of `words` 10 pixels wide per character, with a space's width between them.
+    fn line(y: f32, words: &[&str]) -> Line {
+        let mut x = 0.0;
+        Line {
+            words: words
+                .iter()
+                .map(|text| {
+                    let w = text.chars().count() as f32 * 10.0;
+                    let word = Word {
+                        text: (*text).to_owned(),
+                        rect: rect(x, y, w, 12.0),
+                    };
+                    x += w + 10.0;
+                    word
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn recognize_offsets_words() {
+        let img = RgbaImage::new(200, 100);
+        let engine = FixedOcr::new(vec![line(5.0, &["Error"])]);
+        let region = Rect::from_min_max(Pos2::new(50.4, 20.2), Pos2::new(150.0, 80.0));
+        let lines = recognize(&engine, &img, region).unwrap();
+        assert_eq!(engine.size.get(), Some((100, 60)));
+        assert_eq!(lines[0].words[0].rect, rect(50.0, 25.0, 50.0, 12.0));
+    }
+
+    #[test]
+    fn recognize_clips_region_to_image() {
+        let img = RgbaImage::new(200, 100);
+        let engine = FixedOcr::new(vec![line(5.0, &["Error"])]);
+        let region = Rect::from_min_max(Pos2::new(-10.0, -10.0), Pos2::new(30.0, 30.0));
+        let lines = recognize(&engine, &img, region).unwrap();
+        assert_eq!(engine.size.get(), Some((30, 30)));
+        assert_eq!(lines[0].words[0].rect, rect(0.0, 5.0, 50.0, 12.0));
+    }
+
+    #[test]
+    fn recognize_outside_image() {
+        let img = RgbaImage::new(200, 100);
+        let engine = FixedOcr::new(Vec::new());
+        let region = Rect::from_min_max(Pos2::new(300.0, 10.0), Pos2::new(400.0, 50.0));
+        assert!(matches!(
+            recognize(&engine, &img, region),
+            Err(OcrError::EmptyRegion)
+        ));
+        assert_eq!(engine.size.get(), None);
+    }
+
+    #[test]
+    fn find_ignores_case() {
+        let lines = [line(0.0, &["Error:", "File", "NOT", "found"])];
+        assert_eq!(find(&lines, "not"), [rect(120.0, 0.0, 30.0, 12.0)]);
+        assert_eq!(find(&lines, "ERROR"), [rect(0.0, 0.0, 60.0, 12.0)]);
+        assert_eq!(find(&lines, "missing"), []);
+        assert_eq!(find(&lines, "  "), []);
+    }
+
+    #[test]
+    fn find_across_words() {
+        let lines = [
+            line(0.0, &["Error:", "File", "NOT", "found"]),
+            line(20.0, &["file", "not", "found", "again"]),
+        ];
+        assert_eq!(
+            find(&lines, "le not fo"),
+            [rect(70.0, 0.0, 140.0, 12.0), rect(0.0, 20.0, 140.0, 12.0)]
+        );
+    }
+
+    #[test]
+    fn find_non_ascii() {
+        let lines = [line(0.0, &["Größe", "ÜBERSCHRITTEN", "Straße"])];
+        assert_eq!(
+            find(&lines, "überschritten"),
+            [rect(60.0, 0.0, 130.0, 12.0)]
+        );
+        assert_eq!(find(&lines, "GRÖßE"), [rect(0.0, 0.0, 50.0, 12.0)]);
+        // Part of a word marks the whole word.
+        assert_eq!(
+            find(&lines, "ße"),
+            [rect(0.0, 0.0, 50.0, 12.0), rect(200.0, 0.0, 60.0, 12.0)]
+        );
+    }
+
+    #[test]
+    fn span_rect_at_word_boundaries() {
+        let line = line(0.0, &["ab", "cd", "ef"]);
+        assert_eq!(line.text(), "ab cd ef");
+        let (ab, cd, ef) = (
+            rect(0.0, 0.0, 20.0, 12.0),
+            rect(30.0, 0.0, 20.0, 12.0),
+            rect(60.0, 0.0, 20.0, 12.0),
+        );
+        assert_eq!(line.span_rect(0..2), Some(ab));
+        assert_eq!(line.span_rect(0..3), Some(ab));
+        assert_eq!(line.span_rect(2..3), None);
+        assert_eq!(line.span_rect(2..4), Some(cd));
+        assert_eq!(line.span_rect(1..4), Some(ab.union(cd)));
+        assert_eq!(line.span_rect(6..8), Some(ef));
+        assert_eq!(line.span_rect(0..8), Some(ab.union(ef)));
+        assert_eq!(line.span_rect(8..9), None);
+    }
+}
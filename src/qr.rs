@@ -0,0 +1,892 @@
+use eframe::egui::{Pos2, Rect};
+
+use crate::barcode::{Binary, Code, Symbology};
+
+/// Most modules a finder pattern's centre may be away from another's to be the same one.
+const SAME_FINDER: f32 = 2.0;
+/// Most rows a finder pattern has to be found in, so specks in text aren't taken for one.
+const MIN_HITS: u32 = 2;
+/// How much longer one side of a code may be than the other.
+const MAX_SKEW: f32 = 0.15;
+/// Most bits a copy of the format or version information may be misread in.
+const MAX_FORMAT_ERRORS: u32 = 3;
+/// Error correction codewords per block and number of blocks, by error correction level
+/// (low, medium, quartile, high) and version.
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+const ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// One of the three squares in the corners of a QR code.
+#[derive(Debug, Clone, Copy)]
+struct Finder {
+    center: Pos2,
+    /// The width of a module, in pixels.
+    module: f32,
+    hits: u32,
+}
+
+/// The QR codes in `img` that can be read. Codes may be turned by quarter turns, but not
+/// tilted or mirrored.
+pub fn find(img: &Binary) -> Vec<Code> {
+    let finders = finders(img);
+    let mut triples = Vec::new();
+    for (i, a) in finders.iter().enumerate() {
+        for (j, b) in finders.iter().enumerate().skip(i + 1) {
+            for (k, c) in finders.iter().enumerate().skip(j + 1) {
+                // Any of the three may be the top-left corner.
+                for [tl, p, q] in [[a, b, c], [b, a, c], [c, a, b]] {
+                    if let Some(skew) = corner_skew(tl, p, q) {
+                        triples.push((skew, [i, j, k], [*tl, *p, *q]));
+                    }
+                }
+            }
+        }
+    }
+    triples.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut used: Vec<usize> = Vec::new();
+    let mut codes = Vec::new();
+    for (_, indices, [tl, p, q]) in triples {
+        if indices.iter().any(|i| used.contains(i)) {
+            continue;
+        }
+        // The top-right corner comes first going clockwise from the top-left one.
+        let (tr, bl) = if (p.center - tl.center).x * (q.center - tl.center).y
+            - (p.center - tl.center).y * (q.center - tl.center).x
+            > 0.0
+        {
+            (p, q)
+        } else {
+            (q, p)
+        };
+        if let Some(code) = read(img, &tl, &tr, &bl) {
+            used.extend(indices);
+            codes.push(code);
+        }
+    }
+    codes
+}
+
+/// How far `tl`, `p` and `q` are from being the corners of a square with the right angle at
+/// `tl`, if they could be.
+fn corner_skew(tl: &Finder, p: &Finder, q: &Finder) -> Option<f32> {
+    let modules = [tl.module, p.module, q.module];
+    let (smallest, largest) = modules
+        .iter()
+        .fold((f32::MAX, 0.0f32), |(lo, hi), &m| (lo.min(m), hi.max(m)));
+    if largest > smallest * 1.5 {
+        return None;
+    }
+    let (u, v) = (p.center - tl.center, q.center - tl.center);
+    let (lu, lv) = (u.length(), v.length());
+    let stretch = lu.max(lv) / lu.min(lv) - 1.0;
+    let cos = u.dot(v) / (lu * lv);
+    (stretch <= MAX_SKEW && cos.abs() < MAX_SKEW).then_some(stretch + cos.abs())
+}
+
+/// The finder patterns in `img`: dark, light, dark, light and dark stretches in the ratio
+/// 1:1:3:1:1 both across and down.
+fn finders(img: &Binary) -> Vec<Finder> {
+    let mut finders: Vec<Finder> = Vec::new();
+    for y in 0..img.height {
+        let runs = img.row_runs(y);
+        for w in runs.windows(5) {
+            let lens = [w[0].len, w[1].len, w[2].len, w[3].len, w[4].len];
+            if !w[0].dark || finder_module(lens).is_none() {
+                continue;
+            }
+            let total = lens.iter().sum::<u32>();
+            let x = (w[2].start + w[2].len / 2).into();
+            let Some((dy, down)) = cross_check(img, x, y.into(), (0, 1), total * 2) else {
+                continue;
+            };
+            let y = y as f32 + dy;
+            let Some((dx, across)) = cross_check(img, x, y as i64, (1, 0), total * 2) else {
+                continue;
+            };
+            if (down / across - 1.0).abs() > 0.5 {
+                continue;
+            }
+            let found = Finder {
+                center: Pos2::new(x as f32 + dx, y),
+                module: f32::midpoint(down, across),
+                hits: 1,
+            };
+            let same = finders.iter_mut().find(|f| {
+                f.center.distance(found.center) <= SAME_FINDER * f.module
+                    && (f.module / found.module - 1.0).abs() < 0.5
+            });
+            match same {
+                Some(f) => {
+                    let n = f.hits as f32;
+                    f.center =
+                        ((f.center.to_vec2() * n + found.center.to_vec2()) / (n + 1.0)).to_pos2();
+                    f.module = (f.module * n + found.module) / (n + 1.0);
+                    f.hits += 1;
+                }
+                None => finders.push(found),
+            }
+        }
+    }
+    finders.retain(|f| f.hits >= MIN_HITS);
+    finders
+}
+
+/// The width of a module if `lens` are the stretches across a finder pattern.
+fn finder_module(lens: [u32; 5]) -> Option<f32> {
+    let total: u32 = lens.iter().sum();
+    if total < 7 {
+        return None;
+    }
+    let module = total as f32 / 7.0;
+    let leeway = module / 2.0;
+    lens.iter()
+        .zip([1.0, 1.0, 3.0, 1.0, 1.0])
+        .all(|(&len, ratio)| (len as f32 - module * ratio).abs() < leeway * ratio)
+        .then_some(module)
+}
+
+/// Checks that the pixel at `(x, y)` is in the middle of a finder pattern along `step`,
+/// looking no further than `max` pixels each way. Returns how far the pattern's centre is
+/// from the pixel's top-left corner along `step` and the width of a module.
+fn cross_check(img: &Binary, x: i64, y: i64, step: (i64, i64), max: u32) -> Option<(f32, f32)> {
+    let dark = |k: i64| img.is_dark(x + step.0 * k, y + step.1 * k);
+    // The centre, light and outer stretches going backwards, then forwards.
+    let mut lens = [[0u32; 3]; 2];
+    for (side, dir) in [(0, -1), (1, 1)] {
+        let mut k = 0;
+        for (i, want) in [true, false, true].into_iter().enumerate() {
+            while dark(k) == want && lens[side][i] < max {
+                lens[side][i] += 1;
+                k += dir;
+            }
+        }
+    }
+    let [back, forward] = lens;
+    // Both ways counted the pixel itself.
+    let centre = back[0] + forward[0] - 1;
+    let module = finder_module([back[2], back[1], centre, forward[1], forward[2]])?;
+    let start = 1.0 - back[0] as f32;
+    Some((start + centre as f32 / 2.0, module))
+}
+
+/// The code whose finder patterns are `tl`, `tr` and `bl`, going clockwise.
+fn read(img: &Binary, tl: &Finder, tr: &Finder, bl: &Finder) -> Option<Code> {
+    let module = (tl.module + tr.module + bl.module) / 3.0;
+    let span = f32::midpoint(tl.center.distance(tr.center), tl.center.distance(bl.center));
+    // Finder pattern centres are 7 modules in from the sides. Module widths vary a little
+    // when codes are scaled, so larger codes say which version they are instead.
+    let estimate = ((span / module + 7.0 - 17.0) / 4.0).round() as i32;
+    let said = if estimate >= 6 {
+        version(img, tl, tr, bl)
+    } else {
+        None
+    };
+    said.into_iter()
+        .chain([estimate, estimate - 1, estimate + 1])
+        .filter(|v| (1..=40).contains(v))
+        .find_map(|version| {
+            let size = 17 + 4 * version as usize;
+            let steps = (size - 7) as f32;
+            let (u, v) = (
+                (tr.center - tl.center) / steps,
+                (bl.center - tl.center) / steps,
+            );
+            let at = |c: f32, r: f32| tl.center + u * (c - 3.0) + v * (r - 3.0);
+            let modules: Vec<bool> = (0..size * size)
+                .map(|i| {
+                    let p = at((i % size) as f32, (i / size) as f32);
+                    img.is_dark(p.x.floor() as i64, p.y.floor() as i64)
+                })
+                .collect();
+            let text = decode(&modules, version as usize)?;
+            let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+                .map(|(c, r)| at(c * size as f32 - 0.5, r * size as f32 - 0.5));
+            Some(Code {
+                symbology: Symbology::Qr,
+                text,
+                rect: Rect::from_points(&corners),
+            })
+        })
+}
+
+/// The version from the version information of a code of version 7 or later, from whichever
+/// copy is read with the fewest errors. The copies are next to the top-right and bottom-left
+/// finder patterns, so they are read with the module width there.
+fn version(img: &Binary, tl: &Finder, tr: &Finder, bl: &Finder) -> Option<i32> {
+    let (across, down) = (
+        (tr.center - tl.center).normalized(),
+        (bl.center - tl.center).normalized(),
+    );
+    let copy = |finder: &Finder, at: &dyn Fn(f32, f32) -> (f32, f32)| {
+        (0..18).fold(0u32, |bits, i| {
+            let (c, r) = at((i % 3) as f32, (i / 3) as f32);
+            let p = finder.center + (across * c + down * r) * finder.module;
+            bits | u32::from(img.is_dark(p.x.floor() as i64, p.y.floor() as i64)) << i
+        })
+    };
+    // Finder pattern centres are 3 modules in, and the version information is 3 by 6
+    // modules, 4 away from them.
+    let first = copy(tr, &|i, j| (i - 7.0, j - 3.0));
+    let second = copy(bl, &|i, j| (j - 3.0, i - 7.0));
+    let (version, errors) = (7..=40u32)
+        .map(|version| {
+            let mut rem = version;
+            for _ in 0..12 {
+                rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+            }
+            let bits = version << 12 | rem;
+            let errors = (bits ^ first)
+                .count_ones()
+                .min((bits ^ second).count_ones());
+            (version, errors)
+        })
+        .min_by_key(|&(_, errors)| errors)?;
+    (errors <= MAX_FORMAT_ERRORS).then_some(version.cast_signed())
+}
+
+/// The text of a QR code of `version` whose modules, row by row, are dark where `modules`
+/// is true.
+fn decode(modules: &[bool], version: usize) -> Option<String> {
+    let size = 17 + 4 * version;
+    let dark = |x: usize, y: usize| modules[y * size + x];
+    let (ecl, mask) = format(&dark, size)?;
+    let function = function_modules(version);
+    let mut codewords = vec![0u8; raw_modules(version) / 8];
+    let mut bit = 0;
+    let mut right = size - 1;
+    // Codewords zigzag up and down pairs of columns from the right, skipping the timing
+    // pattern.
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        for vert in 0..size {
+            for x in [right, right - 1] {
+                let y = if (right + 1) & 2 == 0 {
+                    size - 1 - vert
+                } else {
+                    vert
+                };
+                if function[y * size + x] || bit >= codewords.len() * 8 {
+                    continue;
+                }
+                if dark(x, y) != masked(mask, x, y) {
+                    codewords[bit / 8] |= 0x80 >> (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+        if right < 2 {
+            break;
+        }
+        right -= 2;
+    }
+    let data = correct(&codewords, version, ecl)?;
+    text(&data, version)
+}
+
+/// The error correction level, as an index into the tables, and the mask of a QR code,
+/// from whichever copy of its format information is read with the fewest errors.
+fn format(dark: &impl Fn(usize, usize) -> bool, size: usize) -> Option<(usize, u8)> {
+    let copy = |at: &dyn Fn(usize) -> (usize, usize)| {
+        (0..15).fold(0u32, |bits, i| {
+            let (x, y) = at(i);
+            bits | u32::from(dark(x, y)) << i
+        })
+    };
+    let first = copy(&|i| match i {
+        0..=5 => (8, i),
+        6 => (8, 7),
+        7 => (8, 8),
+        8 => (7, 8),
+        _ => (14 - i, 8),
+    });
+    let second = copy(&|i| {
+        if i < 8 {
+            (size - 1 - i, 8)
+        } else {
+            (8, size - 15 + i)
+        }
+    });
+    let (data, errors) = (0..32u32)
+        .map(|data| {
+            let mut rem = data;
+            for _ in 0..10 {
+                rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+            }
+            let bits = (data << 10 | rem) ^ 0x5412;
+            (
+                data,
+                (bits ^ first)
+                    .count_ones()
+                    .min((bits ^ second).count_ones()),
+            )
+        })
+        .min_by_key(|&(_, errors)| errors)?;
+    // Format information lists medium, low, high and quartile in that order.
+    (errors <= MAX_FORMAT_ERRORS).then_some(([1, 0, 3, 2][(data >> 3) as usize], (data & 7) as u8))
+}
+
+/// Whether `mask` flips the module in column `x` of row `y`.
+fn masked(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// Which modules of a code of `version`, row by row, are patterns or format and version
+/// information rather than data.
+fn function_modules(version: usize) -> Vec<bool> {
+    let size = 17 + 4 * version;
+    let mut function = vec![false; size * size];
+    let mut fill = |x: usize, y: usize, w: usize, h: usize| {
+        for row in function[y * size..(y + h) * size].chunks_mut(size) {
+            row[x..x + w].fill(true);
+        }
+    };
+    // Finder patterns with their separators and the format information.
+    fill(0, 0, 9, 9);
+    fill(size - 8, 0, 8, 9);
+    fill(0, size - 8, 9, 8);
+    // Timing patterns.
+    fill(6, 0, 1, size);
+    fill(0, 6, size, 1);
+    let align = alignment_positions(version);
+    let last = align.len().saturating_sub(1);
+    for (i, &y) in align.iter().enumerate() {
+        for (j, &x) in align.iter().enumerate() {
+            // Except where the finder patterns are.
+            if ![(0, 0), (0, last), (last, 0)].contains(&(i, j)) {
+                fill(x - 2, y - 2, 5, 5);
+            }
+        }
+    }
+    if version >= 7 {
+        fill(size - 11, 0, 3, 6);
+        fill(0, size - 11, 6, 3);
+    }
+    function
+}
+
+/// The rows and columns that alignment patterns are centred on.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let size = 17 + 4 * version;
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// How many modules of a code of `version` hold data and error correction.
+fn raw_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let count = version / 7 + 2;
+        modules -= (25 * count - 10) * count - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// The data codewords of a code, with errors corrected, from its interleaved blocks.
+fn correct(codewords: &[u8], version: usize, ecl: usize) -> Option<Vec<u8>> {
+    let blocks = usize::from(ERROR_CORRECTION_BLOCKS[ecl][version]);
+    let ecc = usize::from(ECC_CODEWORDS_PER_BLOCK[ecl][version]);
+    let short_blocks = blocks - codewords.len() % blocks;
+    let short_len = codewords.len() / blocks;
+    let mut split: Vec<Vec<u8>> = vec![Vec::with_capacity(short_len + 1); blocks];
+    let mut next = codewords.iter();
+    for i in 0..=short_len {
+        for (j, block) in split.iter_mut().enumerate() {
+            // Short blocks have one data codeword fewer.
+            if i != short_len - ecc || j >= short_blocks {
+                block.push(*next.next()?);
+            }
+        }
+    }
+    let gf = Gf::new();
+    let mut data = Vec::new();
+    for mut block in split {
+        gf.correct(&mut block, ecc)?;
+        data.extend_from_slice(&block[..block.len() - ecc]);
+    }
+    Some(data)
+}
+
+/// Arithmetic in GF(256) with the field polynomial QR codes use.
+struct Gf {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Gf {
+    fn new() -> Self {
+        let mut gf = Self {
+            exp: [0; 512],
+            log: [0; 256],
+        };
+        let mut x: u16 = 1;
+        for i in 0..255 {
+            gf.exp[i] = x as u8;
+            gf.exp[i + 255] = x as u8;
+            gf.log[usize::from(x)] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= 0x11D;
+            }
+        }
+        gf
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.exp[usize::from(self.log[usize::from(a)]) + usize::from(self.log[usize::from(b)])]
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 {
+            return 0;
+        }
+        self.exp
+            [usize::from(self.log[usize::from(a)]) + 255 - usize::from(self.log[usize::from(b)])]
+    }
+
+    /// α raised to `n`.
+    fn pow(&self, n: usize) -> u8 {
+        self.exp[n % 255]
+    }
+
+    /// The value at `x` of the polynomial with `coefficients`, lowest power first.
+    fn eval(&self, coefficients: &[u8], x: u8) -> u8 {
+        coefficients
+            .iter()
+            .rev()
+            .fold(0, |acc, &c| self.mul(acc, x) ^ c)
+    }
+
+    /// Corrects the errors in a Reed–Solomon block ending in `ecc` error correction
+    /// codewords, if there are few enough to.
+    fn correct(&self, block: &mut [u8], ecc: usize) -> Option<()> {
+        let n = block.len();
+        // The block is a polynomial with the first codeword as the highest power.
+        let syndromes: Vec<u8> = (0..ecc)
+            .map(|j| {
+                let x = self.pow(j);
+                block.iter().fold(0, |acc, &c| self.mul(acc, x) ^ c)
+            })
+            .collect();
+        if syndromes.iter().all(|&s| s == 0) {
+            return Some(());
+        }
+        // Berlekamp–Massey finds the error locator, lowest power first.
+        let (mut locator, mut previous) = (vec![1u8], vec![1u8]);
+        let (mut errors, mut shift, mut last) = (0, 1, 1u8);
+        for i in 0..ecc {
+            let discrepancy = (1..=errors).fold(syndromes[i], |d, k| {
+                d ^ self.mul(*locator.get(k).unwrap_or(&0), syndromes[i - k])
+            });
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+            let factor = self.div(discrepancy, last);
+            let mut next = locator.clone();
+            next.resize(next.len().max(previous.len() + shift), 0);
+            for (k, &p) in previous.iter().enumerate() {
+                next[k + shift] ^= self.mul(factor, p);
+            }
+            if 2 * errors <= i {
+                previous = std::mem::replace(&mut locator, next);
+                errors = i + 1 - errors;
+                last = discrepancy;
+                shift = 1;
+            } else {
+                locator = next;
+                shift += 1;
+            }
+        }
+        if 2 * errors > ecc {
+            return None;
+        }
+        // Ω = SΛ mod x^ecc, and Λ' has the odd powers of Λ moved down one.
+        let evaluator: Vec<u8> = (0..ecc)
+            .map(|i| {
+                (0..=i).fold(0, |acc, k| {
+                    acc ^ self.mul(syndromes[i - k], *locator.get(k).unwrap_or(&0))
+                })
+            })
+            .collect();
+        let derivative: Vec<u8> = locator
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(k, &c)| if k % 2 == 1 { c } else { 0 })
+            .collect();
+        let mut found = 0;
+        for (i, codeword) in block.iter_mut().enumerate() {
+            let x = self.pow(n - 1 - i);
+            let x_inv = self.div(1, x);
+            if self.eval(&locator, x_inv) != 0 {
+                continue;
+            }
+            let d = self.eval(&derivative, x_inv);
+            if d == 0 {
+                return None;
+            }
+            *codeword ^= self.mul(x, self.div(self.eval(&evaluator, x_inv), d));
+            found += 1;
+        }
+        (found == errors).then_some(())
+    }
+}
+
+/// Reads bits from the most significant of each byte onwards.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn left(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, n: usize) -> Option<u32> {
+        if n > self.left() {
+            return None;
+        }
+        let value = (self.pos..self.pos + n).fold(0, |value, i| {
+            value << 1 | u32::from(self.data[i / 8] >> (7 - i % 8) & 1)
+        });
+        self.pos += n;
+        Some(value)
+    }
+}
+
+/// The text in the data codewords of a code of `version`. Byte segments are taken for UTF-8,
+/// or for Latin-1 if they aren't valid UTF-8. Kanji segments aren't supported.
+fn text(data: &[u8], version: usize) -> Option<String> {
+    let mut bits = Bits { data, pos: 0 };
+    // Character counts take more bits in larger codes.
+    let group = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    let mut bytes = Vec::new();
+    while bits.left() >= 4 {
+        match bits.read(4)? {
+            0 => break,
+            // Numeric.
+            1 => {
+                let mut count = bits.read([10, 12, 14][group])?;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = bits.read([0, 4, 7, 10][digits as usize])?;
+                    bytes.extend(format!("{value:0width$}", width = digits as usize).bytes());
+                    count -= digits;
+                }
+            }
+            // Alphanumeric.
+            2 => {
+                let mut count = bits.read([9, 11, 13][group])?;
+                while count >= 2 {
+                    let value = bits.read(11)? as usize;
+                    bytes.push(*ALPHANUMERIC.get(value / 45)?);
+                    bytes.push(ALPHANUMERIC[value % 45]);
+                    count -= 2;
+                }
+                if count == 1 {
+                    bytes.push(*ALPHANUMERIC.get(bits.read(6)? as usize)?);
+                }
+            }
+            // Structured append, which says which of several codes this is.
+            3 => {
+                bits.read(16)?;
+            }
+            // Byte.
+            4 => {
+                let count = bits.read([8, 16, 16][group])?;
+                for _ in 0..count {
+                    bytes.push(bits.read(8)? as u8);
+                }
+            }
+            // FNC1 in the first position says nothing more, in the second it adds an
+            // application indicator.
+            5 => {}
+            9 => {
+                bits.read(8)?;
+            }
+            // ECI, which names a character set. Assignments take one to three bytes.
+            7 => {
+                let first = bits.read(8)?;
+                if first & 0x80 != 0 {
+                    bits.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(
+        String::from_utf8(bytes)
+            .unwrap_or_else(|e| e.into_bytes().into_iter().map(char::from).collect()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use xcap::image::{imageops, Rgba, RgbaImage};
+
+    use super::*;
+
+    /// "HELLO WORLD", version 1, medium error correction, mask 2.
+    const VERSION_1: [&str; 21] = [
+        "#######..#..#.#######",
+        "#.....#..####.#.....#",
+        "#.###.#.##..#.#.###.#",
+        "#.###.#.#.##..#.###.#",
+        "#.###.#.##.##.#.###.#",
+        "#.....#.###.#.#.....#",
+        "#######.#.#.#.#######",
+        "........#..##........",
+        "#.#####...#.#.#####..",
+        "#.####.##...##..#####",
+        "..#..###..##...#.#..#",
+        "..##....#......#.....",
+        ".###.####.##......#..",
+        "........#.#####..#.##",
+        "#######..##.#.#.###.#",
+        "#.....#.########..##.",
+        "#.###.#.#.#.#....###.",
+        "#.###.#.#.#.#..#.##..",
+        "#.###.#.#..#.#..##...",
+        "#.....#...........#.#",
+        "#######.#.##.#..#....",
+    ];
+    const VERSION_7_TEXT: &str = "https://example.com/docs/screenshots?page=7 — ünïcode ✓";
+    /// [`VERSION_7_TEXT`], version 7, quartile error correction, mask 5.
+    const VERSION_7: [&str; 45] = [
+        "#######.##..#..###.###.##..#..#.##..#.#######",
+        "#.....#.#.#.###.#.......#..###.#...#..#.....#",
+        "#.###.#.....#.#.#.####.#...##.##...#..#.###.#",
+        "#.###.#...##...##...#.##.#####..##.##.#.###.#",
+        "#.###.#..#.####.#########...####.####.#.###.#",
+        "#.....#....#.##...###...##.###........#.....#",
+        "#######.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#######",
+        "..........##.##..#..#...##..##......#........",
+        ".#....#####..#.#.#..######...#.##....#.....##",
+        "#.#.##.#.###.#.######...#.##.##...#####.###..",
+        "..##.#####.#.#.#.##.#..#.##..#.##...###..#.#.",
+        "####...#.#...#....##..####..#.#..##....#..#.#",
+        ".#..#.#...#..##.#.######.##.#....#.#...###.##",
+        "###..#.#.#.#.##.#.####..##.##...###.##.#..#.#",
+        "...####.#..##.####.#...##.##...###.##.##..##.",
+        "#...##.###.#####..#.#...#.##..##...#.#.###..#",
+        "#.#.#.#...###.#.###.####.#...##..###..###..##",
+        "...#.#..###..#.###.##..##..#..###...##....#.#",
+        "#....####..##.#.#.##.##.#.#...####........#.#",
+        ".#.###...##..#..###......##..#..###.#.....##.",
+        "#.#.#####.#.##..###.#####.#.#..#..###########",
+        "##..#...#.##...#.#..#...##...#.#.##.#...#...#",
+        "...##.#.#...##.##.#.#.#.#...#.#####.#.#.#.###",
+        "#.#.#...###..###..###...#.##....###.#...###.#",
+        ".#.#######.#..##.#.######.#####.#...#####....",
+        "#.###...##....#...###.####.#.#####..#...###.#",
+        ".#.##.#.####.##...#.##.#.#.#...##.#.#.#####..",
+        "##.###....#..##.#...#.##......#.#.#..##.#.###",
+        "##...##..##.##.#.........#...#.##.###..##..#.",
+        "##...#.#.#.#..#....#..#....##.#.#.##.###.####",
+        "...##.##.#.#...###..#####..###......###.#.###",
+        "..##.....#.###..#.###.##.#..#.##.####..#.###.",
+        "###.###.#.#.##.###.#.#.#.#....#..##.#####..##",
+        ".#####.#.#.##.#.#####...#.########.....##.##.",
+        "....#.#...#..##..#.#....####.....#..#####.##.",
+        ".####..##.#....#.##.#.##.#####..#..#...###.##",
+        "#..##.####..#..#.#..#######.#...#.########.##",
+        "........####.####..##...#..####....##...#.#.#",
+        "#######.#.#.######..#.#.#.#.#..##..##.#.#..#.",
+        "#.....#....#.#...#..#...#....#.#.#..#...#...#",
+        "#.###.#...###....##.#######....#..#.#######..",
+        "#.###.#..##..#.#......##...#.#.........###.##",
+        "#.###.#...##.####..##....#.####.##..###.##.##",
+        "#.....#.#.#...###.#.#..##..#..#.##.#.#...#...",
+        "#######...#.######.#..###.##.###.#.#.##...##.",
+    ];
+
+    fn modules(rows: &[&str]) -> Vec<bool> {
+        rows.iter()
+            .flat_map(|row| row.bytes().map(|b| b == b'#'))
+            .collect()
+    }
+
+    /// Flips `count` data modules spread over the code, each in a different codeword.
+    fn flip(modules: &mut [bool], version: usize, count: usize) {
+        let function = function_modules(version);
+        let data: Vec<usize> = (0..modules.len()).filter(|&i| !function[i]).collect();
+        for &i in data.iter().step_by(data.len() / count).take(count) {
+            modules[i] = !modules[i];
+        }
+    }
+
+    /// `rows` drawn `scale` pixels a module with a quiet zone of four modules.
+    fn render(rows: &[&str], scale: u32) -> RgbaImage {
+        let size = rows.len() as u32;
+        RgbaImage::from_fn((size + 8) * scale, (size + 8) * scale, |x, y| {
+            let (c, r) = ((x / scale).wrapping_sub(4), (y / scale).wrapping_sub(4));
+            let dark = r < size && c < size && rows[r as usize].as_bytes()[c as usize] == b'#';
+            Rgba(if dark { [0, 0, 0, 255] } else { [255; 4] })
+        })
+    }
+
+    /// `data` followed by its Reed–Solomon error correction codewords.
+    fn encode(gf: &Gf, data: &[u8], ecc: usize) -> Vec<u8> {
+        // The generator is the product of x - α^i, highest power first.
+        let mut generator = vec![1u8];
+        for i in 0..ecc {
+            let mut next = generator.clone();
+            next.push(0);
+            for (k, &c) in generator.iter().enumerate() {
+                next[k + 1] ^= gf.mul(c, gf.pow(i));
+            }
+            generator = next;
+        }
+        let mut remainder = data.to_vec();
+        remainder.resize(data.len() + ecc, 0);
+        for i in 0..data.len() {
+            let factor = remainder[i];
+            for (k, &g) in generator.iter().enumerate() {
+                remainder[i + k] ^= gf.mul(g, factor);
+            }
+        }
+        [data, &remainder[data.len()..]].concat()
+    }
+
+    #[test]
+    fn decodes_version_1() {
+        assert_eq!(
+            decode(&modules(&VERSION_1), 1).as_deref(),
+            Some("HELLO WORLD")
+        );
+    }
+
+    #[test]
+    fn decodes_version_7() {
+        assert_eq!(
+            decode(&modules(&VERSION_7), 7).as_deref(),
+            Some(VERSION_7_TEXT)
+        );
+    }
+
+    #[test]
+    fn corrects_misread_modules() {
+        let mut v1 = modules(&VERSION_1);
+        flip(&mut v1, 1, 4);
+        assert_eq!(decode(&v1, 1).as_deref(), Some("HELLO WORLD"));
+        let mut v7 = modules(&VERSION_7);
+        flip(&mut v7, 7, 20);
+        assert_eq!(decode(&v7, 7).as_deref(), Some(VERSION_7_TEXT));
+    }
+
+    #[test]
+    fn gives_up_on_too_many_errors() {
+        let mut v1 = modules(&VERSION_1);
+        flip(&mut v1, 1, 12);
+        assert_eq!(decode(&v1, 1), None);
+    }
+
+    #[test]
+    fn corrects_blocks() {
+        let gf = Gf::new();
+        let data: Vec<u8> = (0..20u8).map(|i| i.wrapping_mul(37) ^ 5).collect();
+        let block = encode(&gf, &data, 10);
+        let mut clean = block.clone();
+        assert_eq!(gf.correct(&mut clean, 10), Some(()));
+        assert_eq!(clean, block);
+        // Up to half as many errors as error correction codewords, in data or correction.
+        let mut damaged = block.clone();
+        for (i, error) in [(0, 0xFF), (7, 1), (19, 0x80), (22, 0x3C), (29, 0x55)] {
+            damaged[i] ^= error;
+        }
+        assert_eq!(gf.correct(&mut damaged, 10), Some(()));
+        assert_eq!(damaged, block);
+        let mut hopeless = block.clone();
+        for i in [1, 4, 9, 12, 16, 21, 25] {
+            hopeless[i] ^= 0xA5;
+        }
+        assert_eq!(gf.correct(&mut hopeless, 10), None);
+    }
+
+    #[test]
+    fn finds_codes_turned_by_quarter_turns() {
+        for (rows, text) in [
+            (&VERSION_1[..], "HELLO WORLD"),
+            (&VERSION_7[..], VERSION_7_TEXT),
+        ] {
+            let upright = render(rows, 3);
+            for img in [
+                imageops::rotate90(&upright),
+                imageops::rotate180(&upright),
+                imageops::rotate270(&upright),
+                upright,
+            ] {
+                let codes = find(&Binary::new(&img));
+                let texts: Vec<&str> = codes.iter().map(|c| c.text.as_str()).collect();
+                assert_eq!(texts, [text]);
+            }
+        }
+    }
+}
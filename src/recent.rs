@@ -0,0 +1,157 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use xcap::image::{self, imageops, RgbaImage};
+
+use crate::capture::CaptureInfo;
+
+const APP_ID: &str = "SnapCrab";
+const DIR_NAME: &str = "history";
+const INDEX_FILE: &str = "history.toml";
+/// How many captures are kept before the oldest ones are deleted.
+const LIMIT: usize = 30;
+const THUMBNAIL_SIZE: u32 = 160;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecentError {
+    #[error("no storage directory available on this platform")]
+    NoStorageDir,
+    #[error("failed to write capture history: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode capture: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("failed to serialize capture history: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+fn dir() -> Option<PathBuf> {
+    eframe::storage_dir(APP_ID).map(|dir| dir.join(DIR_NAME))
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentCapture {
+    /// File stem of the image and its thumbnail in the history directory.
+    pub id: String,
+    /// Seconds since the Unix epoch.
+    pub taken: u64,
+    #[serde(flatten)]
+    pub info: CaptureInfo,
+}
+
+impl RecentCapture {
+    fn image_path(&self) -> Option<PathBuf> {
+        dir().map(|dir| dir.join(format!("{}.png", self.id)))
+    }
+
+    fn thumbnail_path(&self) -> Option<PathBuf> {
+        dir().map(|dir| dir.join(format!("{}.thumb.png", self.id)))
+    }
+
+    pub fn load(&self) -> Option<RgbaImage> {
+        image::open(self.image_path()?)
+            .ok()
+            .map(|img| img.to_rgba8())
+    }
+
+    pub fn load_thumbnail(&self) -> Option<RgbaImage> {
+        image::open(self.thumbnail_path()?)
+            .ok()
+            .map(|img| img.to_rgba8())
+    }
+
+    /// How long ago the capture was taken, e.g. "5 min ago".
+    pub fn age(&self) -> String {
+        let secs = now().as_secs().saturating_sub(self.taken);
+        match secs {
+            0..60 => "just now".to_string(),
+            60..3600 => format!("{} min ago", secs / 60),
+            3600..86_400 => format!("{} h ago", secs / 3600),
+            _ => format!("{} d ago", secs / 86_400),
+        }
+    }
+
+    fn delete_files(&self) {
+        for path in [self.image_path(), self.thumbnail_path()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Recent captures cached on disk, newest first, so they survive closing their tab or the app.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentCaptures {
+    pub entries: Vec<RecentCapture>,
+}
+
+impl RecentCaptures {
+    /// Loads the history index, falling back to an empty history if it is missing or malformed.
+    pub fn load() -> Self {
+        dir()
+            .and_then(|dir| fs::read_to_string(dir.join(INDEX_FILE)).ok())
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), RecentError> {
+        let dir = dir().ok_or(RecentError::NoStorageDir)?;
+        fs::write(dir.join(INDEX_FILE), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Stores `img` as the newest capture, dropping the oldest ones beyond the limit.
+    pub fn add(&mut self, img: &RgbaImage, info: &CaptureInfo) -> Result<(), RecentError> {
+        let dir = dir().ok_or(RecentError::NoStorageDir)?;
+        fs::create_dir_all(&dir)?;
+        let now = now();
+        let mut id = now.as_millis().to_string();
+        while self.entries.iter().any(|e| e.id == id) {
+            id.push('_');
+        }
+        let entry = RecentCapture {
+            id,
+            taken: now.as_secs(),
+            info: info.clone(),
+        };
+        img.save(dir.join(format!("{}.png", entry.id)))?;
+        let scale = (THUMBNAIL_SIZE as f32 / img.width().max(img.height()) as f32).min(1.0);
+        imageops::thumbnail(
+            img,
+            (img.width() as f32 * scale).round().max(1.0) as u32,
+            (img.height() as f32 * scale).round().max(1.0) as u32,
+        )
+        .save(dir.join(format!("{}.thumb.png", entry.id)))?;
+        self.entries.insert(0, entry);
+        for old in self.entries.drain(LIMIT.min(self.entries.len())..) {
+            old.delete_files();
+        }
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(), RecentError> {
+        if index < self.entries.len() {
+            self.entries.remove(index).delete_files();
+        }
+        self.save()
+    }
+
+    pub fn clear(&mut self) -> Result<(), RecentError> {
+        for entry in self.entries.drain(..) {
+            entry.delete_files();
+        }
+        self.save()
+    }
+}
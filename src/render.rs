@@ -0,0 +1,372 @@
+use eframe::egui::{Color32, Pos2, Rect, Vec2};
+use tiny_skia::{
+    ColorU8, FillRule, FilterQuality, IntRect, LineJoin, Paint, PathBuilder, Pattern, Pixmap,
+    PixmapPaint, SpreadMode, Stroke, Transform,
+};
+use xcap::image::{Rgba, RgbaImage};
+
+use crate::{
+    annotation::{text_bounds, Annotation},
+    callout, magnifier,
+    redact::{self, RedactStyle},
+    spotlight,
+    stroke::{self, Piece},
+    text::{Fonts, TextLayout, TextStyle},
+};
+
+pub fn to_pixmap(img: &RgbaImage) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(img.width(), img.height())?;
+    for (dst, src) in pixmap.pixels_mut().iter_mut().zip(img.pixels()) {
+        *dst = ColorU8::from_rgba(src[0], src[1], src[2], src[3]).premultiply();
+    }
+    Some(pixmap)
+}
+
+pub fn from_pixmap(pixmap: &Pixmap) -> RgbaImage {
+    let mut img = RgbaImage::new(pixmap.width(), pixmap.height());
+    for (dst, src) in img.pixels_mut().zip(pixmap.pixels()) {
+        let c = src.demultiply();
+        *dst = Rgba([c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+    img
+}
+
+pub fn skia_color(color: Color32) -> tiny_skia::Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    tiny_skia::Color::from_rgba8(r, g, b, a)
+}
+
+pub fn paint(color: Color32) -> Paint<'static> {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+/// A path of closed `polygons`.
+fn polygon_path(polygons: impl IntoIterator<Item = Vec<Pos2>>) -> Option<tiny_skia::Path> {
+    let mut pb = PathBuilder::new();
+    for polygon in polygons {
+        for (i, p) in polygon.into_iter().enumerate() {
+            if i == 0 {
+                pb.move_to(p.x, p.y);
+            } else {
+                pb.line_to(p.x, p.y);
+            }
+        }
+        pb.close();
+    }
+    pb.finish()
+}
+
+/// A rectangle with circular corners of `radius`.
+pub fn rounded_rect_path(rect: Rect, radius: f32) -> Option<tiny_skia::Path> {
+    // Control point distance that makes a cubic Bézier approximate a quarter circle.
+    const KAPPA: f32 = 0.552_284_8;
+    let radius = radius
+        .min(rect.width() / 2.0)
+        .min(rect.height() / 2.0)
+        .max(0.0);
+    let k = radius * (1.0 - KAPPA);
+    let (min, max) = (rect.min, rect.max);
+    let mut pb = PathBuilder::new();
+    pb.move_to(min.x + radius, min.y);
+    pb.line_to(max.x - radius, min.y);
+    pb.cubic_to(max.x - k, min.y, max.x, min.y + k, max.x, min.y + radius);
+    pb.line_to(max.x, max.y - radius);
+    pb.cubic_to(max.x, max.y - k, max.x - k, max.y, max.x - radius, max.y);
+    pb.line_to(min.x + radius, max.y);
+    pb.cubic_to(min.x + k, max.y, min.x, max.y - k, min.x, max.y - radius);
+    pb.line_to(min.x, min.y + radius);
+    pb.cubic_to(min.x, min.y + k, min.x + k, min.y, min.x + radius, min.y);
+    pb.close();
+    pb.finish()
+}
+
+/// Turns by `angle` radians around `centre`.
+fn rotation(centre: Pos2, angle: f32) -> Transform {
+    Transform::from_rotate_at(angle.to_degrees(), centre.x, centre.y)
+}
+
+/// Fills the background box, then strokes the outline, fills the glyphs and strokes
+/// them again in the text colour if bold, all mapped by `transform`.
+fn draw_text(
+    pixmap: &mut Pixmap,
+    layout: &TextLayout,
+    pos: Pos2,
+    color: Color32,
+    transform: Transform,
+) {
+    let (size, style) = (layout.font_size(), layout.style());
+    if let Some(fill) = style.background
+        && let Some(path) = rounded_rect_path(layout.outer_rect(pos), TextStyle::box_rounding(size))
+    {
+        pixmap.fill_path(&path, &paint(fill), FillRule::Winding, transform, None);
+    }
+    let Some(path) = layout.path(Fonts::get(), pos) else {
+        return;
+    };
+    let stroke = |width: f32| Stroke {
+        width,
+        line_join: LineJoin::Round,
+        ..Stroke::default()
+    };
+    if let Some(outline) = style.outline {
+        let stroke = stroke(TextStyle::outline_width(size));
+        pixmap.stroke_path(&path, &paint(outline), &stroke, transform, None);
+    }
+    pixmap.fill_path(&path, &paint(color), FillRule::Winding, transform, None);
+    if let Some(width) = style.bold_width(size) {
+        pixmap.stroke_path(&path, &paint(color), &stroke(width), transform, None);
+    }
+}
+
+fn draw_callout(pixmap: &mut Pixmap, ann: &Annotation) {
+    let Annotation::Callout {
+        rect,
+        tail,
+        text,
+        shape,
+        color,
+        fill,
+        size,
+        angle,
+    } = ann
+    else {
+        return;
+    };
+    let outline = callout::geometry(*rect, *shape, *tail, *angle).outline;
+    if let Some(path) = polygon_path([outline]) {
+        pixmap.fill_path(
+            &path,
+            &paint(*fill),
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+        let stroke = Stroke {
+            width: callout::border_width(*size),
+            line_join: LineJoin::Round,
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(&path, &paint(*color), &stroke, Transform::identity(), None);
+    }
+    let (pos, layout) = callout::layout_text(*rect, *shape, text, *size);
+    draw_text(
+        pixmap,
+        &layout,
+        pos,
+        *color,
+        rotation(rect.center(), *angle),
+    );
+}
+
+/// Draws the pieces of a stroked shape, matching the canvas.
+fn draw_pieces(pixmap: &mut Pixmap, pieces: Vec<Piece>, width: f32, color: Color32) {
+    let paint = paint(color);
+    for piece in pieces {
+        let path = match piece {
+            Piece::Ribbon { sections, closed } => {
+                polygon_path(stroke::ribbon_outline(&sections, closed))
+            }
+            Piece::Fill(points) => polygon_path([points]),
+            Piece::Dot(centre) => PathBuilder::from_circle(centre.x, centre.y, width / 2.0),
+        };
+        if let Some(path) = path {
+            pixmap.fill_path(
+                &path,
+                &paint,
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+}
+
+/// Paints the enlarged copy of `source` from `image` into `inset`, sampled like the canvas
+/// texture.
+fn draw_magnified(pixmap: &mut Pixmap, image: &Pixmap, source: Rect, inset: Rect) {
+    let bounds = Rect::from_min_size(
+        Pos2::ZERO,
+        Vec2::new(image.width() as f32, image.height() as f32),
+    );
+    let Some((_, shown)) = magnifier::visible(source, inset, bounds) else {
+        return;
+    };
+    let Some(area) = tiny_skia::Rect::from_ltrb(shown.min.x, shown.min.y, shown.max.x, shown.max.y)
+    else {
+        return;
+    };
+    let zoom = inset.size() / source.size();
+    let paint = Paint {
+        shader: Pattern::new(
+            image.as_ref(),
+            SpreadMode::Pad,
+            FilterQuality::Bilinear,
+            1.0,
+            Transform::from_row(
+                zoom.x,
+                0.0,
+                0.0,
+                zoom.y,
+                inset.min.x - source.min.x * zoom.x,
+                inset.min.y - source.min.y * zoom.y,
+            ),
+        ),
+        anti_alias: true,
+        ..Paint::default()
+    };
+    pixmap.fill_rect(area, &paint, Transform::identity(), None);
+}
+
+/// Covers `rect` with black, or with a blur of what `image` shows there.
+fn draw_redaction(pixmap: &mut Pixmap, rect: Rect, style: RedactStyle, image: &Pixmap) {
+    match style {
+        RedactStyle::Black => {
+            if let Some(r) =
+                tiny_skia::Rect::from_ltrb(rect.min.x, rect.min.y, rect.max.x, rect.max.y)
+            {
+                pixmap.fill_rect(r, &paint(Color32::BLACK), Transform::identity(), None);
+            }
+        }
+        RedactStyle::Blur => {
+            let bounds = Rect::from_min_size(
+                Pos2::ZERO,
+                Vec2::new(image.width() as f32, image.height() as f32),
+            );
+            let Some(area) = redact::pixel_rect(rect, bounds) else {
+                return;
+            };
+            let (x, y) = (area.min.x as i32, area.min.y as i32);
+            let Some(part) = IntRect::from_xywh(x, y, area.width() as u32, area.height() as u32)
+                .and_then(|r| image.clone_rect(r))
+            else {
+                return;
+            };
+            if let Some(blurred) = to_pixmap(&redact::blur(&from_pixmap(&part))) {
+                pixmap.draw_pixmap(
+                    x,
+                    y,
+                    blurred.as_ref(),
+                    &PixmapPaint::default(),
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+    }
+}
+
+/// Draws `ann` in image coordinates, matching how the canvas paints it. `image` is the
+/// image being annotated, without any annotations.
+pub fn draw_annotation(pixmap: &mut Pixmap, ann: &Annotation, image: &Pixmap) {
+    match ann {
+        Annotation::Arrow { .. } | Annotation::Rect { .. } => {
+            if let Some((pieces, width, color)) = ann.stroke_pieces() {
+                draw_pieces(pixmap, pieces, width, color);
+            }
+        }
+        Annotation::Magnifier {
+            source,
+            centre,
+            zoom,
+            ..
+        } => {
+            draw_magnified(
+                pixmap,
+                image,
+                *source,
+                magnifier::inset(*source, *centre, *zoom),
+            );
+            if let Some((pieces, width, color)) = ann.stroke_pieces() {
+                draw_pieces(pixmap, pieces, width, color);
+            }
+        }
+        Annotation::Text {
+            pos,
+            text,
+            color,
+            size,
+            style,
+            angle,
+        } => draw_text(
+            pixmap,
+            &Fonts::get().layout(text, *size, style),
+            *pos,
+            *color,
+            rotation(text_bounds(*pos, text, *size, style).center(), *angle),
+        ),
+        Annotation::Callout { .. } => draw_callout(pixmap, ann),
+        Annotation::Redact { rect, style } => draw_redaction(pixmap, *rect, *style, image),
+        Annotation::Image { rect, image } => {
+            let Some(layer) = to_pixmap(image) else {
+                return;
+            };
+            let transform = Transform::from_row(
+                rect.width() / layer.width() as f32,
+                0.0,
+                0.0,
+                rect.height() / layer.height() as f32,
+                rect.min.x,
+                rect.min.y,
+            );
+            let paint = PixmapPaint {
+                quality: FilterQuality::Bicubic,
+                ..PixmapPaint::default()
+            };
+            pixmap.draw_pixmap(0, 0, layer.as_ref(), &paint, transform, None);
+        }
+        Annotation::Sticker {
+            sticker,
+            rect,
+            angle,
+            color,
+        } => {
+            let Some(layer) = sticker.render(rect.size(), *color) else {
+                return;
+            };
+            let transform = rotation(rect.center(), *angle)
+                .pre_translate(rect.min.x, rect.min.y)
+                .pre_scale(
+                    rect.width() / layer.width() as f32,
+                    rect.height() / layer.height() as f32,
+                );
+            let paint = PixmapPaint {
+                quality: FilterQuality::Bicubic,
+                ..PixmapPaint::default()
+            };
+            pixmap.draw_pixmap(0, 0, layer.as_ref(), &paint, transform, None);
+        }
+        Annotation::Spotlight {
+            areas,
+            effect,
+            strength,
+        } => {
+            let overlay = spotlight::overlay(&from_pixmap(pixmap), areas, *effect, *strength);
+            if let Some(layer) = to_pixmap(&overlay) {
+                pixmap.draw_pixmap(
+                    0,
+                    0,
+                    layer.as_ref(),
+                    &PixmapPaint::default(),
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+    }
+}
+
+/// Bakes `annotations` into a copy of `img`.
+pub fn render_annotations(img: &RgbaImage, annotations: &[Annotation]) -> RgbaImage {
+    let Some(image) = to_pixmap(img) else {
+        return img.clone();
+    };
+    let mut pixmap = image.clone();
+    for ann in annotations {
+        draw_annotation(&mut pixmap, ann, &image);
+    }
+    from_pixmap(&pixmap)
+}
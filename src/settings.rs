@@ -0,0 +1,321 @@
+use std::{fs, io, path::PathBuf};
+
+use eframe::egui::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crop::Aspect,
+    frame::{FramePreset, FrameStyle},
+    stroke::StrokeStyle,
+};
+
+const APP_ID: &str = "SnapCrab";
+const FILE_NAME: &str = "settings.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("no storage directory available on this platform")]
+    NoStorageDir,
+    #[error("failed to write settings: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to serialize settings: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// Matches a window by (case-insensitive) substrings of its title and app name.
+/// Empty fields match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowMatch {
+    pub title: String,
+    pub app_name: String,
+}
+
+impl WindowMatch {
+    pub fn matches(&self, title: &str, app_name: &str) -> bool {
+        let contains = |hay: &str, needle: &str| {
+            needle.is_empty() || hay.to_lowercase().contains(&needle.to_lowercase())
+        };
+        contains(title, &self.title) && contains(app_name, &self.app_name)
+    }
+}
+
+/// Case-insensitive glob match where `*` matches any run of characters.
+/// An empty pattern matches anything.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    if pattern.is_empty() {
+        return true;
+    }
+    let (pattern, text) = (pattern.to_lowercase(), text.to_lowercase());
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = text.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        let Some(i) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[i + part.len()..];
+    }
+    rest.ends_with(last)
+}
+
+/// A window filter rule. Both fields are glob patterns (see [`glob_match`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowRule {
+    pub title: String,
+    pub app_name: String,
+}
+
+impl WindowRule {
+    /// Whether neither pattern has been filled in yet.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_empty() && self.app_name.is_empty()
+    }
+
+    pub fn matches(&self, title: &str, app_name: &str) -> bool {
+        glob_match(&self.title, title) && glob_match(&self.app_name, app_name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowPicking {
+    /// If non-empty, only windows matching one of these rules can be picked.
+    pub include: Vec<WindowRule>,
+    pub exclude: Vec<WindowRule>,
+    /// Pick child controls inside the hovered window instead of the whole window.
+    pub pick_controls: bool,
+}
+
+impl Default for WindowPicking {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: vec![
+                WindowRule {
+                    title: "Program Manager".into(),
+                    app_name: String::new(),
+                },
+                WindowRule {
+                    title: "ms-*".into(),
+                    app_name: String::new(),
+                },
+            ],
+            pick_controls: false,
+        }
+    }
+}
+
+impl WindowPicking {
+    /// Whether a window may be picked. Rules that are still empty are ignored, so a rule
+    /// that was just added doesn't match every window.
+    pub fn allows(&self, title: &str, app_name: &str) -> bool {
+        let mut include = self.include.iter().filter(|r| !r.is_empty()).peekable();
+        (include.peek().is_none() || include.any(|r| r.matches(title, app_name)))
+            && !self
+                .exclude
+                .iter()
+                .any(|r| !r.is_empty() && r.matches(title, app_name))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowCapture {
+    /// Capture the title bar and borders too, not just the client area.
+    pub include_frame: bool,
+    /// Radius of the transparent rounded corners; `0` keeps them square.
+    pub corner_radius: f32,
+    pub drop_shadow: bool,
+}
+
+/// When images are written to the auto-save folder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSaveTrigger {
+    /// Every new capture, as soon as it is taken.
+    #[default]
+    Capture,
+    /// Every copy or save, with annotations.
+    Export,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoSave {
+    pub enabled: bool,
+    pub trigger: AutoSaveTrigger,
+    pub folder: PathBuf,
+    /// File name without extension; see [`crate::autosave::PLACEHOLDERS`].
+    pub template: String,
+    /// The last value used for `{counter}`.
+    pub counter: u32,
+}
+
+impl Default for AutoSave {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trigger: AutoSaveTrigger::default(),
+            folder: PathBuf::new(),
+            template: "Screenshot {date} {time}".into(),
+            counter: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PresetTarget {
+    /// A rectangle on the primary monitor, in screen pixels.
+    Region {
+        rect: Rect,
+    },
+    Window(WindowMatch),
+}
+
+impl PresetTarget {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Region { .. } => "✂",
+            Self::Window(_) => "🪟",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturePreset {
+    pub name: String,
+    pub target: PresetTarget,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub last_area: Option<Rect>,
+    pub last_window: Option<WindowMatch>,
+    pub presets: Vec<CapturePreset>,
+    pub window_picking: WindowPicking,
+    pub window_capture: WindowCapture,
+    /// Apply [`Self::frame`] to copied and saved images.
+    pub frame_enabled: bool,
+    pub frame: FrameStyle,
+    pub frame_presets: Vec<FramePreset>,
+    pub crop_aspect: Aspect,
+    pub auto_save: AutoSave,
+    /// Stroke style of new arrows and boxes.
+    pub stroke: StrokeStyle,
+    /// Where stickers besides the built-in ones are loaded from; empty for none.
+    pub sticker_folder: PathBuf,
+    /// Don't snap to guides while drawing and moving annotations.
+    pub disable_snapping: bool,
+    /// Regular expressions auto-redact looks for besides the built-in kinds.
+    pub redact_patterns: Vec<String>,
+    /// Auto-redact QR codes and barcodes too.
+    pub redact_codes: bool,
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        eframe::storage_dir(APP_ID).map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Loads the settings file, falling back to defaults if it is missing. Fields that fail to
+    /// parse fall back to their defaults one by one, after keeping a `.bak` copy of the file so
+    /// the next save doesn't lose them for good.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|err| {
+            eprintln!("ignoring invalid settings in {}: {err}", path.display());
+            let _ = fs::copy(&path, path.with_extension("toml.bak"));
+            Self::from_valid_fields(&text)
+        })
+    }
+
+    /// Parses `text`, leaving out the top-level fields that don't parse on their own.
+    fn from_valid_fields(text: &str) -> Self {
+        let Ok(table) = text.parse::<toml::Table>() else {
+            return Self::default();
+        };
+        let valid = table
+            .into_iter()
+            .filter(|(key, value)| {
+                let field = toml::Table::from_iter([(key.clone(), value.clone())]);
+                toml::Value::Table(field).try_into::<Self>().is_ok()
+            })
+            .collect::<toml::Table>();
+        toml::Value::Table(valid).try_into().unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let path = Self::path().ok_or(SettingsError::NoStorageDir)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn find_preset(&self, name: &str) -> Option<&CapturePreset> {
+        self.presets
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Inserts a preset, replacing any existing one with the same name.
+    pub fn upsert_preset(&mut self, preset: CapturePreset) {
+        match self
+            .presets
+            .iter_mut()
+            .find(|p| p.name.eq_ignore_ascii_case(&preset.name))
+        {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_field_keeps_the_rest() {
+        let mut settings = Settings {
+            disable_snapping: true,
+            ..Settings::default()
+        };
+        settings.upsert_preset(CapturePreset {
+            name: "Editor".into(),
+            target: PresetTarget::Window(WindowMatch {
+                title: "editor".into(),
+                ..WindowMatch::default()
+            }),
+        });
+        let text = toml::to_string_pretty(&settings)
+            .unwrap()
+            .replace("disable_snapping = true", "disable_snapping = \"yes\"");
+        assert!(toml::from_str::<Settings>(&text).is_err());
+
+        let loaded = Settings::from_valid_fields(&text);
+        assert_eq!(loaded.presets, settings.presets);
+        assert!(!loaded.disable_snapping);
+    }
+
+    #[test]
+    fn unparsable_file_is_default() {
+        let loaded = Settings::from_valid_fields("presets = [");
+        assert!(loaded.presets.is_empty());
+    }
+}
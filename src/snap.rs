@@ -0,0 +1,189 @@
+use std::f32::consts::FRAC_PI_4;
+
+use eframe::egui::{Pos2, Rangef, Rect, Vec2};
+use xcap::image::RgbaImage;
+
+/// How close something has to come to a guide to snap to it, in UI points.
+pub const SNAP_DISTANCE: f32 = 6.0;
+/// Shortest straight edge in a screenshot that becomes a guide, in pixels.
+pub const MIN_EDGE: u32 = 24;
+/// Smallest brightness difference between neighbouring pixels that counts as an edge, out
+/// of 255.
+const EDGE_CONTRAST: f32 = 40.0;
+
+/// A guide at `at`, an x for vertical guides and a y for horizontal ones, running from
+/// `from` to `to` along the other axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub at: f32,
+    pub from: f32,
+    pub to: f32,
+}
+
+/// The lines that points and rects snap to while they are drawn or moved.
+#[derive(Debug, Clone)]
+pub struct Guides {
+    pub vertical: Vec<Line>,
+    pub horizontal: Vec<Line>,
+    /// How far guides through annotations reach: the whole image.
+    bounds: Rect,
+}
+
+/// How far to move to line up with the guides, and the guides that were lined up with, as
+/// segments to draw.
+#[derive(Debug, Clone, Default)]
+pub struct Snap {
+    pub offset: Vec2,
+    pub lines: Vec<[Pos2; 2]>,
+}
+
+impl Guides {
+    /// Guides along the edges and through the centre of `bounds`, the image.
+    pub fn new(bounds: Rect) -> Self {
+        let mut guides = Self {
+            vertical: Vec::new(),
+            horizontal: Vec::new(),
+            bounds,
+        };
+        guides.add_rect(bounds);
+        guides
+    }
+
+    /// Adds guides along the edges and through the centre of `rect`, across the whole image.
+    pub fn add_rect(&mut self, rect: Rect) {
+        let (xs, ys) = (self.bounds.x_range(), self.bounds.y_range());
+        for x in [rect.min.x, rect.center().x, rect.max.x] {
+            self.vertical.push(Line {
+                at: x,
+                from: ys.min,
+                to: ys.max,
+            });
+        }
+        for y in [rect.min.y, rect.center().y, rect.max.y] {
+            self.horizontal.push(Line {
+                at: y,
+                from: xs.min,
+                to: xs.max,
+            });
+        }
+    }
+
+    pub fn extend(&mut self, other: &Self) {
+        self.vertical.extend_from_slice(&other.vertical);
+        self.horizontal.extend_from_slice(&other.horizontal);
+    }
+
+    /// The smallest move, on each axis up to `distance`, that puts an edge or the centre of
+    /// `rect` on a guide running alongside it.
+    pub fn snap(&self, rect: Rect, distance: f32) -> Snap {
+        let mut snap = Snap::default();
+        let xs = [rect.min.x, rect.center().x, rect.max.x];
+        let ys = [rect.min.y, rect.center().y, rect.max.y];
+        if let Some((line, offset)) = nearest(&self.vertical, &xs, rect.y_range(), distance) {
+            snap.offset.x = offset;
+            snap.lines.push([
+                Pos2::new(line.at, line.from.min(rect.min.y)),
+                Pos2::new(line.at, line.to.max(rect.max.y)),
+            ]);
+        }
+        if let Some((line, offset)) = nearest(&self.horizontal, &ys, rect.x_range(), distance) {
+            snap.offset.y = offset;
+            snap.lines.push([
+                Pos2::new(line.from.min(rect.min.x), line.at),
+                Pos2::new(line.to.max(rect.max.x), line.at),
+            ]);
+        }
+        snap
+    }
+
+    pub fn snap_point(&self, p: Pos2, distance: f32) -> Snap {
+        self.snap(Rect::from_min_max(p, p), distance)
+    }
+}
+
+/// The line nearest to one of `positions` within `distance`, among those that reach within
+/// `distance` of `span`, and how far that position has to move to be on it.
+fn nearest(lines: &[Line], positions: &[f32], span: Rangef, distance: f32) -> Option<(Line, f32)> {
+    lines
+        .iter()
+        .filter(|line| line.from - distance <= span.max && line.to + distance >= span.min)
+        .flat_map(|line| positions.iter().map(move |&p| (*line, line.at - p)))
+        .filter(|(_, offset)| offset.abs() <= distance)
+        .min_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+}
+
+/// `to` moved onto the nearest line from `from` at a multiple of 45°.
+pub fn snap_angle(from: Pos2, to: Pos2) -> Pos2 {
+    let d = to - from;
+    let angle = (d.angle() / FRAC_PI_4).round() * FRAC_PI_4;
+    let dir = Vec2::angled(angle);
+    from + dir * d.dot(dir)
+}
+
+/// The straight horizontal and vertical edges of the UI in a screenshot: runs of at least
+/// `min_length` pixels along which the brightness jumps by [`EDGE_CONTRAST`] or more from
+/// one side to the other. Edges lie between pixels, so an edge at x = 10 separates columns 9
+/// and 10.
+pub fn detect_edges(img: &RgbaImage, min_length: u32) -> Guides {
+    let (width, height) = img.dimensions();
+    let luma: Vec<f32> = img
+        .pixels()
+        .map(|p| {
+            let [r, g, b, _] = p.0;
+            0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b)
+        })
+        .collect();
+    let at = |x: u32, y: u32| luma[(y * width + x) as usize];
+    let mut guides = Guides {
+        vertical: Vec::new(),
+        horizontal: Vec::new(),
+        bounds: Rect::from_min_size(Pos2::ZERO, Vec2::new(width as f32, height as f32)),
+    };
+    for x in 1..width {
+        runs(
+            height,
+            min_length,
+            |y| (at(x - 1, y) - at(x, y)).abs() >= EDGE_CONTRAST,
+            |from, to| {
+                guides.vertical.push(Line {
+                    at: x as f32,
+                    from,
+                    to,
+                });
+            },
+        );
+    }
+    for y in 1..height {
+        runs(
+            width,
+            min_length,
+            |x| (at(x, y - 1) - at(x, y)).abs() >= EDGE_CONTRAST,
+            |from, to| {
+                guides.horizontal.push(Line {
+                    at: y as f32,
+                    from,
+                    to,
+                });
+            },
+        );
+    }
+    guides
+}
+
+/// Calls `found` with the start and end of every run of at least `min_length` positions in
+/// `0..len` where `strong` holds.
+fn runs(len: u32, min_length: u32, strong: impl Fn(u32) -> bool, mut found: impl FnMut(f32, f32)) {
+    let mut start = None;
+    for i in 0..=len {
+        match (start, i < len && strong(i)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                if i - s >= min_length {
+                    found(s as f32, i as f32);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+}
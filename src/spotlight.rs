@@ -0,0 +1,132 @@
+use eframe::egui::{Pos2, Rect, Vec2};
+use xcap::image::{Rgba, RgbaImage};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpotShape {
+    #[default]
+    Rect,
+    Ellipse,
+}
+
+impl SpotShape {
+    pub const ALL: [Self; 2] = [Self::Rect, Self::Ellipse];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rect => "▭ Rectangle",
+            Self::Ellipse => "⬭ Ellipse",
+        }
+    }
+}
+
+/// What happens to everything outside the spotlight.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpotEffect {
+    #[default]
+    Darken,
+    Desaturate,
+}
+
+impl SpotEffect {
+    pub const ALL: [Self; 2] = [Self::Darken, Self::Desaturate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Darken => "Darken",
+            Self::Desaturate => "Desaturate",
+        }
+    }
+}
+
+/// One of the areas a spotlight leaves untouched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotArea {
+    pub rect: Rect,
+    pub shape: SpotShape,
+}
+
+impl SpotArea {
+    /// Distance from `p` to the edge, negative inside. Approximate for ellipses, but
+    /// exact on the edge itself.
+    pub fn signed_distance(&self, p: Pos2) -> f32 {
+        let (centre, half) = (self.rect.center(), self.rect.size() / 2.0);
+        let d = p - centre;
+        match self.shape {
+            SpotShape::Rect => {
+                let q = d.abs() - half;
+                q.max(Vec2::ZERO).length() + q.x.max(q.y).min(0.0)
+            }
+            SpotShape::Ellipse => {
+                let half = half.max(Vec2::splat(f32::EPSILON));
+                let k = (d / half).length();
+                let gradient = (d / (half * half)).length();
+                if gradient <= f32::EPSILON {
+                    -half.min_elem()
+                } else {
+                    k * (k - 1.0) / gradient
+                }
+            }
+        }
+    }
+
+    /// How much of the pixel centred at `p` lies inside, in `0..=1`, for pixels `scale`
+    /// times the size of image pixels.
+    fn coverage(&self, p: Pos2, scale: f32) -> f32 {
+        (0.5 - self.signed_distance(p) * scale).clamp(0.0, 1.0)
+    }
+}
+
+/// How much of the pixel centred at `p` lies outside all of `areas`.
+fn outside(areas: &[SpotArea], p: Pos2, scale: f32) -> f32 {
+    1.0 - areas
+        .iter()
+        .map(|a| a.coverage(p, scale))
+        .fold(0.0, f32::max)
+}
+
+/// What to paint over `below` so everything outside `areas` is darkened or desaturated by
+/// `strength`, with anti-aliased edges. Shared by the canvas and export.
+pub fn overlay(
+    below: &RgbaImage,
+    areas: &[SpotArea],
+    effect: SpotEffect,
+    strength: f32,
+) -> RgbaImage {
+    let strength = strength.clamp(0.0, 1.0);
+    let mut out = RgbaImage::new(below.width(), below.height());
+    for (x, y, p) in out.enumerate_pixels_mut() {
+        let centre = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+        let outside = outside(areas, centre, 1.0);
+        if outside <= 0.0 {
+            continue;
+        }
+        let [r, g, b, a] = below.get_pixel(x, y).0;
+        let alpha = outside * f32::from(a);
+        *p = match effect {
+            SpotEffect::Darken => Rgba([0, 0, 0, (alpha * strength).round() as u8]),
+            SpotEffect::Desaturate => {
+                let luma = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+                let grey = |c: u8| (f32::from(c) + (luma - f32::from(c)) * strength).round() as u8;
+                Rgba([grey(r), grey(g), grey(b), alpha.round() as u8])
+            }
+        };
+    }
+    out
+}
+
+/// Black wherever a darkening spotlight covers `region` of the image, drawn `width` ×
+/// `height` pixels. Unlike [`overlay`] it doesn't depend on the pixels below, so the
+/// canvas only draws it again when the areas or the view change.
+pub fn mask(areas: &[SpotArea], region: Rect, width: u32, height: u32) -> RgbaImage {
+    let step = region.size() / Vec2::new(width as f32, height as f32);
+    let scale = 1.0 / step.x.max(step.y);
+    RgbaImage::from_fn(width, height, |x, y| {
+        let centre = region.min + Vec2::new(x as f32 + 0.5, y as f32 + 0.5) * step;
+        Rgba([
+            0,
+            0,
+            0,
+            (outside(areas, centre, scale) * 255.0).round() as u8,
+        ])
+    })
+}
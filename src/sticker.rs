@@ -0,0 +1,296 @@
+use std::{fs, io, path::Path, sync::Arc};
+
+use eframe::egui::{Color32, Pos2, Vec2};
+use tiny_skia::{FillRule, LineCap, LineJoin, PathBuilder, Pixmap, Stroke, Transform};
+use xcap::image::{
+    self,
+    imageops::{self, FilterType},
+    RgbaImage,
+};
+
+use crate::{
+    render,
+    svg::{Svg, SvgError},
+    text::{Fonts, TextStyle},
+};
+
+/// Longest side a sticker is drawn at, however large it gets.
+const MAX_PIXELS: f32 = 4096.0;
+/// Font size emoji outlines are measured at.
+const EMOJI_SIZE: f32 = 100.0;
+
+pub const EMOJI: [&str; 16] = [
+    "👍", "👎", "❤", "⭐", "🔥", "💡", "🎉", "🚀", "👀", "🐛", "🔒", "❓", "❗", "😀", "😢", "🤔",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum StickerError {
+    #[error("failed to read sticker: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to decode sticker: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("failed to parse sticker: {0}")]
+    Svg(#[from] SvgError),
+}
+
+/// A built-in stamp, drawn as shapes so it stays sharp at any size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stamp {
+    Check,
+    Cross,
+    Warning,
+}
+
+impl Stamp {
+    pub const ALL: [Self; 3] = [Self::Check, Self::Cross, Self::Warning];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Check => "✔ Check",
+            Self::Cross => "✖ Cross",
+            Self::Warning => "⚠ Warning",
+        }
+    }
+
+    /// Draws the stamp filling a unit square mapped by `transform`.
+    fn draw(self, pixmap: &mut Pixmap, transform: Transform) {
+        let stroke = |width: f32| Stroke {
+            width,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Stroke::default()
+        };
+        let path = |points: &[(f32, f32)], closed: bool| {
+            let mut pb = PathBuilder::new();
+            for (i, &(x, y)) in points.iter().enumerate() {
+                if i == 0 {
+                    pb.move_to(x, y);
+                } else {
+                    pb.line_to(x, y);
+                }
+            }
+            if closed {
+                pb.close();
+            }
+            pb.finish()
+        };
+        let (badge, marks): (Color32, &[&[(f32, f32)]]) = match self {
+            Self::Check => (
+                Color32::from_rgb(0x22, 0xA4, 0x47),
+                &[&[(0.27, 0.52), (0.43, 0.68), (0.74, 0.35)]],
+            ),
+            Self::Cross => (
+                Color32::from_rgb(0xD9, 0x30, 0x25),
+                &[&[(0.33, 0.33), (0.67, 0.67)], &[(0.67, 0.33), (0.33, 0.67)]],
+            ),
+            Self::Warning => {
+                let (amber, dark) = (
+                    Color32::from_rgb(0xF9, 0xAB, 0x00),
+                    Color32::from_rgb(0x20, 0x21, 0x24),
+                );
+                // Stroked as well as filled to round its corners.
+                if let Some(triangle) = path(&[(0.5, 0.08), (0.94, 0.88), (0.06, 0.88)], true) {
+                    pixmap.fill_path(
+                        &triangle,
+                        &render::paint(amber),
+                        FillRule::Winding,
+                        transform,
+                        None,
+                    );
+                    pixmap.stroke_path(
+                        &triangle,
+                        &render::paint(amber),
+                        &stroke(0.1),
+                        transform,
+                        None,
+                    );
+                }
+                if let Some(bar) = path(&[(0.5, 0.36), (0.5, 0.6)], false) {
+                    pixmap.stroke_path(&bar, &render::paint(dark), &stroke(0.09), transform, None);
+                }
+                if let Some(dot) = PathBuilder::from_circle(0.5, 0.75, 0.055) {
+                    pixmap.fill_path(
+                        &dot,
+                        &render::paint(dark),
+                        FillRule::Winding,
+                        transform,
+                        None,
+                    );
+                }
+                return;
+            }
+        };
+        if let Some(circle) = PathBuilder::from_circle(0.5, 0.5, 0.48) {
+            pixmap.fill_path(
+                &circle,
+                &render::paint(badge),
+                FillRule::Winding,
+                transform,
+                None,
+            );
+        }
+        for mark in marks {
+            if let Some(mark) = path(mark, false) {
+                pixmap.stroke_path(
+                    &mark,
+                    &render::paint(Color32::WHITE),
+                    &stroke(0.1),
+                    transform,
+                    None,
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Artwork {
+    Raster(RgbaImage),
+    Svg(Svg),
+}
+
+/// A sticker loaded from the sticker folder.
+#[derive(Debug)]
+pub struct StickerFile {
+    pub name: String,
+    artwork: Artwork,
+}
+
+#[derive(Debug, Clone)]
+pub enum Sticker {
+    Stamp(Stamp),
+    /// Drawn in the annotation's colour, as emoji glyphs only come in outline.
+    Emoji(&'static str),
+    File(Arc<StickerFile>),
+}
+
+/// Stickers from files are the same if they come from the same load.
+impl PartialEq for Sticker {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Stamp(a), Self::Stamp(b)) => a == b,
+            (Self::Emoji(a), Self::Emoji(b)) => a == b,
+            (Self::File(a), Self::File(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Default for Sticker {
+    fn default() -> Self {
+        Self::Stamp(Stamp::Check)
+    }
+}
+
+impl Sticker {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Stamp(stamp) => stamp.name().to_string(),
+            Self::Emoji(text) => (*text).to_string(),
+            Self::File(file) => file.name.clone(),
+        }
+    }
+
+    /// Width over height.
+    pub fn aspect(&self) -> f32 {
+        let (width, height) = match self {
+            Self::Stamp(_) => (1.0, 1.0),
+            Self::Emoji(text) => emoji_path(text).map_or((1.0, 1.0), |path| {
+                (path.bounds().width(), path.bounds().height())
+            }),
+            Self::File(file) => match &file.artwork {
+                Artwork::Raster(img) => (img.width() as f32, img.height() as f32),
+                Artwork::Svg(svg) => (svg.width, svg.height),
+            },
+        };
+        width / height.max(f32::EPSILON)
+    }
+
+    /// The sticker drawn at `size` in pixels, or smaller if that is huge; emoji in `color`.
+    pub fn render(&self, size: Vec2, color: Color32) -> Option<Pixmap> {
+        let size = size * (MAX_PIXELS / size.max_elem()).min(1.0);
+        let (width, height) = (size.x.round().max(1.0), size.y.round().max(1.0));
+        let mut pixmap = Pixmap::new(width as u32, height as u32)?;
+        match self {
+            Self::Stamp(stamp) => stamp.draw(&mut pixmap, Transform::from_scale(width, height)),
+            Self::Emoji(text) => {
+                let path = emoji_path(text)?;
+                let bounds = path.bounds();
+                let transform =
+                    Transform::from_scale(width / bounds.width(), height / bounds.height())
+                        .pre_translate(-bounds.left(), -bounds.top());
+                pixmap.fill_path(
+                    &path,
+                    &render::paint(color),
+                    FillRule::Winding,
+                    transform,
+                    None,
+                );
+            }
+            Self::File(file) => match &file.artwork {
+                Artwork::Raster(img) => {
+                    let resized =
+                        imageops::resize(img, width as u32, height as u32, FilterType::CatmullRom);
+                    return render::to_pixmap(&resized);
+                }
+                Artwork::Svg(svg) => svg.render(
+                    &mut pixmap,
+                    Transform::from_scale(width / svg.width, height / svg.height),
+                ),
+            },
+        }
+        Some(pixmap)
+    }
+}
+
+/// The outline of an emoji, at [`EMOJI_SIZE`].
+fn emoji_path(text: &str) -> Option<tiny_skia::Path> {
+    let fonts = Fonts::get();
+    fonts
+        .layout(text, EMOJI_SIZE, &TextStyle::default())
+        .path(fonts, Pos2::ZERO)
+}
+
+pub fn load(path: &Path) -> Result<StickerFile, StickerError> {
+    let is_svg = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    let artwork = if is_svg {
+        Artwork::Svg(Svg::parse(&fs::read_to_string(path)?)?)
+    } else {
+        Artwork::Raster(image::open(path)?.to_rgba8())
+    };
+    Ok(StickerFile {
+        name: path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        artwork,
+    })
+}
+
+/// The images and SVGs in `dir`, by file name. Files that can't be loaded are left out,
+/// including SVGs that use something stickers can't draw.
+pub fn load_folder(dir: &Path) -> Vec<Arc<StickerFile>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("svg")
+                    || crate::IMAGE_EXTENSIONS
+                        .iter()
+                        .any(|known| ext.eq_ignore_ascii_case(known))
+            })
+        })
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| load(path).ok())
+        .map(Arc::new)
+        .collect()
+}
@@ -0,0 +1,330 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use eframe::egui::{Color32, Pos2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Turns sharper than this, in radians, get the join style; gentler ones, like the
+/// segments of a curve, are always mitered.
+const CORNER_ANGLE: f32 = 0.2;
+const ARC_STEPS: u8 = 8;
+/// Longest miter, relative to half the stroke width, before it is bevelled instead.
+const MITER_LIMIT: f32 = 4.0;
+/// Shortest dash, gap or dot spacing, relative to the stroke width.
+pub const MIN_PATTERN: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dash {
+    #[default]
+    Solid,
+    Dashed,
+    /// Round dots, `pattern[1]` apart.
+    Dotted,
+}
+
+impl Dash {
+    pub const ALL: [Self; 3] = [Self::Solid, Self::Dashed, Self::Dotted];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Solid => "Solid",
+            Self::Dashed => "Dashed",
+            Self::Dotted => "Dotted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+impl Cap {
+    pub const ALL: [Self; 3] = [Self::Butt, Self::Round, Self::Square];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Butt => "Butt",
+            Self::Round => "Round",
+            Self::Square => "Square",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Join {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl Join {
+    pub const ALL: [Self; 3] = [Self::Miter, Self::Round, Self::Bevel];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Miter => "Miter",
+            Self::Round => "Round",
+            Self::Bevel => "Bevel",
+        }
+    }
+}
+
+/// How the outline of a shape is stroked.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrokeStyle {
+    pub dash: Dash,
+    /// Dash and gap length, relative to the stroke width.
+    pub pattern: [f32; 2],
+    pub cap: Cap,
+    pub join: Join,
+    /// From 0 (invisible) to 1 (opaque).
+    pub opacity: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            dash: Dash::Solid,
+            pattern: [3.0, 2.0],
+            cap: Cap::Butt,
+            join: Join::Miter,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl StrokeStyle {
+    /// `color` with the opacity applied.
+    pub fn color(&self, color: Color32) -> Color32 {
+        color.gamma_multiply(self.opacity.clamp(0.0, 1.0))
+    }
+
+    /// The same style without dashes, for parts that should stay solid.
+    pub fn solid(self) -> Self {
+        Self {
+            dash: Dash::Solid,
+            ..self
+        }
+    }
+}
+
+/// A cross-section of a ribbon: its centre, and the offset from there to one edge.
+pub type Section = (Pos2, Vec2);
+
+/// A primitive both the canvas and export can draw identically.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    /// The band swept by `sections`, back to the first one if `closed`.
+    Ribbon {
+        sections: Vec<Section>,
+        closed: bool,
+    },
+    /// A filled convex polygon.
+    Fill(Vec<Pos2>),
+    /// A filled circle with a diameter of the stroke width.
+    Dot(Pos2),
+}
+
+/// The polygons a ribbon fills under the non-zero rule: one loop around an open ribbon, or
+/// the outer and the reversed inner edge of a closed one.
+pub fn ribbon_outline(sections: &[Section], closed: bool) -> Vec<Vec<Pos2>> {
+    let left = sections.iter().map(|&(c, o)| c + o);
+    let right = sections.iter().rev().map(|&(c, o)| c - o);
+    if closed {
+        vec![left.collect(), right.collect()]
+    } else {
+        vec![left.chain(right).collect()]
+    }
+}
+
+/// The normal on the side egui extrudes strokes towards, so its own anti-aliasing of the
+/// ends points outwards.
+fn normal(dir: Vec2) -> Vec2 {
+    dir.rot90()
+}
+
+/// Points on the arc of `radius` around `centre` from `from` to `to`, the short way round,
+/// including both ends.
+fn arc(centre: Pos2, radius: f32, from: Vec2, to: Vec2) -> impl Iterator<Item = Pos2> {
+    let (start, end) = (from.angle(), to.angle());
+    let sweep = (end - start + PI).rem_euclid(2.0 * PI) - PI;
+    (0..=ARC_STEPS).map(move |step| {
+        centre + Vec2::angled(start + sweep * f32::from(step) / f32::from(ARC_STEPS)) * radius
+    })
+}
+
+/// The section between a point on the outer and one on the inner edge of a turn, where
+/// `outer_sign` tells which side of the line is outside.
+fn section(outer: Pos2, inner: Pos2, outer_sign: f32) -> Section {
+    (outer.lerp(inner, 0.5), (outer - inner) / 2.0 * outer_sign)
+}
+
+/// The sections through the vertex `b` on the way from `a` to `c`.
+fn join(a: Pos2, b: Pos2, c: Pos2, half: f32, join: Join, out: &mut Vec<Section>) {
+    let (d1, d2) = ((b - a).normalized(), (c - b).normalized());
+    let (n1, n2) = match (d1.is_finite(), d2.is_finite()) {
+        (true, true) => (normal(d1), normal(d2)),
+        (true, false) => (normal(d1), normal(d1)),
+        (false, true) => (normal(d2), normal(d2)),
+        (false, false) => return,
+    };
+    let dot = n1.dot(n2);
+    let miter = (n1 + n2) / (1.0 + dot).max(f32::EPSILON);
+    if dot > CORNER_ANGLE.cos() || (join == Join::Miter && miter.length() <= MITER_LIMIT) {
+        out.push((b, miter * half));
+        return;
+    }
+    // The outside of the turn gets the join; the inside meets at a point.
+    let sign = if d2.dot(n1) > 0.0 { -1.0 } else { 1.0 };
+    // Far enough inside for the two edges to meet, unless they would meet beyond a neighbour.
+    let reach = (miter.length() * half).min(a.distance(b).min(b.distance(c)).hypot(half));
+    let inner = match miter.normalized() {
+        dir if dir.is_finite() => b - dir * reach * sign,
+        // Turning right back.
+        _ => b,
+    };
+    let outer: Vec<Pos2> = match join {
+        Join::Round => arc(b, half, n1 * sign, n2 * sign).collect(),
+        Join::Miter | Join::Bevel => vec![b + n1 * half * sign, b + n2 * half * sign],
+    };
+    out.extend(outer.into_iter().map(|o| section(o, inner, sign)));
+}
+
+/// The sections that close off the end of a line at `end`, where it leaves along `dir`.
+/// `at_start` puts them in path order for the line's first point.
+fn cap(end: Pos2, dir: Vec2, half: f32, cap: Cap, at_start: bool) -> Vec<Section> {
+    // Offsets point to the same side as in the rest of the line.
+    let n = normal(dir) * half * if at_start { -1.0 } else { 1.0 };
+    match cap {
+        Cap::Butt => vec![(end, n)],
+        Cap::Square => vec![(end + dir * half, n)],
+        Cap::Round => {
+            let tip_first = (0..=ARC_STEPS).map(|step| {
+                let angle = FRAC_PI_2 * f32::from(step) / f32::from(ARC_STEPS);
+                (end + dir * half * angle.cos(), n * angle.sin())
+            });
+            if at_start {
+                tip_first.collect()
+            } else {
+                tip_first.rev().collect()
+            }
+        }
+    }
+}
+
+/// The ribbon that strokes `run` at `2 * half` wide.
+fn ribbon(run: &[Pos2], closed: bool, half: f32, style: &StrokeStyle) -> Vec<Section> {
+    let n = run.len();
+    let mut sections = Vec::new();
+    if closed {
+        for i in 0..n {
+            join(
+                run[(i + n - 1) % n],
+                run[i],
+                run[(i + 1) % n],
+                half,
+                style.join,
+                &mut sections,
+            );
+        }
+        return sections;
+    }
+    let (Some(start_dir), Some(end_dir)) = (
+        run.windows(2)
+            .map(|w| (w[0] - w[1]).normalized())
+            .find(|d| d.is_finite()),
+        run.windows(2)
+            .rev()
+            .map(|w| (w[1] - w[0]).normalized())
+            .find(|d| d.is_finite()),
+    ) else {
+        return sections;
+    };
+    sections.extend(cap(run[0], start_dir, half, style.cap, true));
+    for w in run.windows(3) {
+        join(w[0], w[1], w[2], half, style.join, &mut sections);
+    }
+    sections.extend(cap(run[n - 1], end_dir, half, style.cap, false));
+    sections
+}
+
+/// The points of `path` at every `spacing` along it, starting at its first point.
+fn points_along(path: &[Pos2], spacing: f32) -> Vec<Pos2> {
+    let mut points: Vec<Pos2> = path.first().copied().into_iter().collect();
+    let mut left = spacing;
+    for w in path.windows(2) {
+        let (mut a, b) = (w[0], w[1]);
+        while a.distance(b) >= left {
+            a += (b - a).normalized() * left;
+            points.push(a);
+            left = spacing;
+        }
+        left -= a.distance(b);
+    }
+    points
+}
+
+/// `path` cut into dashes of `on` separated by gaps of `off`.
+fn dashes(path: &[Pos2], on: f32, off: f32) -> Vec<Vec<Pos2>> {
+    let mut runs = Vec::new();
+    let mut current: Vec<Pos2> = path.first().copied().into_iter().collect();
+    let (mut drawing, mut left) = (true, on);
+    for w in path.windows(2) {
+        let (mut a, b) = (w[0], w[1]);
+        while a.distance(b) >= left {
+            a += (b - a).normalized() * left;
+            current.push(a);
+            if drawing {
+                runs.push(std::mem::take(&mut current));
+            }
+            drawing = !drawing;
+            left = if drawing { on } else { off };
+        }
+        left -= a.distance(b);
+        if drawing {
+            current.push(b);
+        }
+    }
+    if drawing && current.len() > 1 {
+        runs.push(current);
+    }
+    runs
+}
+
+/// The stroke of `path` at `width`, in pieces.
+pub fn pieces(path: &[Pos2], closed: bool, width: f32, style: &StrokeStyle) -> Vec<Piece> {
+    if path.len() < 2 {
+        return Vec::new();
+    }
+    let half = width / 2.0;
+    let mut looped = path.to_vec();
+    if closed {
+        looped.push(path[0]);
+    }
+    let [on, off] = style.pattern.map(|p| p.max(MIN_PATTERN) * width);
+    match style.dash {
+        Dash::Solid => vec![Piece::Ribbon {
+            sections: ribbon(path, closed, half, style),
+            closed,
+        }],
+        Dash::Dashed => dashes(&looped, on, off)
+            .iter()
+            .map(|run| Piece::Ribbon {
+                sections: ribbon(run, false, half, style),
+                closed: false,
+            })
+            .collect(),
+        Dash::Dotted => points_along(&looped, off + width)
+            .into_iter()
+            .map(Piece::Dot)
+            .collect(),
+    }
+}
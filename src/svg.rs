@@ -0,0 +1,925 @@
+use std::f32::consts::PI;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use tiny_skia::{
+    Color, FillRule, LineCap, LineJoin, Paint, Path, PathBuilder, Pixmap, Stroke, Transform,
+};
+
+/// Elements whose content is never drawn directly.
+const HIDDEN: [&[u8]; 13] = [
+    b"defs",
+    b"clipPath",
+    b"mask",
+    b"symbol",
+    b"marker",
+    b"pattern",
+    b"linearGradient",
+    b"radialGradient",
+    b"filter",
+    b"style",
+    b"title",
+    b"desc",
+    b"metadata",
+];
+/// Cubic control point distance that approximates a quarter circle of radius 1.
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SvgError {
+    #[error("invalid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("the image has no size")]
+    NoSize,
+    #[error("the image uses {0}, which isn't supported")]
+    Unsupported(&'static str),
+}
+
+/// Elements that would be drawn as something stickers can't draw.
+const UNSUPPORTED: [(&[u8], &str); 4] = [
+    (b"use", "<use>"),
+    (b"text", "text"),
+    (b"image", "embedded images"),
+    (b"foreignObject", "<foreignObject>"),
+];
+/// Attributes that refer to something stickers can't draw, like gradients, when they name it
+/// with `url(…)`.
+const UNSUPPORTED_REFERENCES: [(&str, &str); 5] = [
+    ("fill", "gradients or patterns"),
+    ("stroke", "gradients or patterns"),
+    ("clip-path", "clip paths"),
+    ("mask", "masks"),
+    ("filter", "filters"),
+];
+
+/// A rule from a `<style>` element. Only selectors made of a tag name, a class or both, or
+/// of an id, are understood; rules with any other selector are left out.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    tag: Option<String>,
+    class: Option<String>,
+    id: Option<String>,
+    declarations: Vec<(String, String)>,
+}
+
+impl Rule {
+    /// How much the rule wins over others, like CSS specificity.
+    fn specificity(&self) -> u32 {
+        100 * u32::from(self.id.is_some())
+            + 10 * u32::from(self.class.is_some())
+            + u32::from(self.tag.is_some())
+    }
+
+    fn matches(&self, e: &BytesStart, attrs: &[(String, String)]) -> bool {
+        self.tag
+            .as_ref()
+            .is_none_or(|tag| e.local_name().as_ref() == tag.as_bytes())
+            && self.class.as_ref().is_none_or(|class| {
+                attr(attrs, "class").is_some_and(|c| c.split_whitespace().any(|c| c == class))
+            })
+            && self
+                .id
+                .as_ref()
+                .is_none_or(|id| attr(attrs, "id") == Some(id))
+    }
+}
+
+/// Paint inherited from the enclosing elements.
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    fill: Option<Color>,
+    fill_rule: FillRule,
+    stroke: Option<Color>,
+    stroke_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    opacity: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    transform: Transform,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some(Color::BLACK),
+            fill_rule: FillRule::Winding,
+            stroke: None,
+            stroke_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            opacity: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            transform: Transform::identity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Shape {
+    path: Path,
+    fill: Option<(Color, FillRule)>,
+    stroke: Option<(Color, Stroke)>,
+    transform: Transform,
+}
+
+/// A parsed SVG image. Only shapes and paths with plain colours are drawn, styled by their
+/// attributes and simple `<style>` rules. Images with gradients, text, `<use>`, filters and
+/// the like don't parse, and group opacity applies to each shape separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Svg {
+    pub width: f32,
+    pub height: f32,
+    shapes: Vec<Shape>,
+}
+
+impl Svg {
+    pub fn parse(text: &str) -> Result<Self, SvgError> {
+        let rules = style_sheet(text)?;
+        let mut reader = Reader::from_str(text);
+        let (mut styles, mut shapes) = (Vec::<Style>::new(), Vec::new());
+        let mut size = None;
+        // How deep inside an element that isn't drawn.
+        let mut hidden = 0_usize;
+        loop {
+            let (e, open) = match reader.read_event()? {
+                Event::Start(e) => (e, true),
+                Event::Empty(e) => (e, false),
+                Event::End(_) => {
+                    if hidden > 0 {
+                        hidden -= 1;
+                    } else {
+                        styles.pop();
+                    }
+                    continue;
+                }
+                Event::Eof => break,
+                _ => continue,
+            };
+            // Illustrator puts its own data in a `<foreignObject>` that viewers skip.
+            let skipped = e.local_name().as_ref() == b"foreignObject"
+                && e.try_get_attribute("requiredExtensions")
+                    .ok()
+                    .flatten()
+                    .is_some();
+            if hidden > 0 || skipped || HIDDEN.contains(&e.local_name().as_ref()) {
+                hidden += usize::from(open);
+                continue;
+            }
+            let attrs = attributes(&e, &rules);
+            if let Some((_, feature)) = UNSUPPORTED
+                .iter()
+                .find(|(name, _)| e.local_name().as_ref() == *name)
+            {
+                return Err(SvgError::Unsupported(feature));
+            }
+            if let Some((_, feature)) = UNSUPPORTED_REFERENCES
+                .iter()
+                .find(|(key, _)| attr(&attrs, key).is_some_and(|v| v.trim().starts_with("url(")))
+            {
+                return Err(SvgError::Unsupported(feature));
+            }
+            let parent = styles.last().copied().unwrap_or_default();
+            let mut style = apply_style(parent, &attrs);
+            let name = e.local_name();
+            if name.as_ref() == b"svg" && size.is_none() {
+                let (width, height, view_box) = root_size(&attrs).ok_or(SvgError::NoSize)?;
+                size = Some((width, height));
+                style.transform = view_box.map_or(style.transform, |[x, y, w, h]| {
+                    style.transform.pre_concat(
+                        Transform::from_scale(width / w, height / h).pre_translate(-x, -y),
+                    )
+                });
+            } else if let Some(path) = shape_path(name.as_ref(), &attrs) {
+                shapes.push(shape(path, &style));
+            }
+            if open {
+                styles.push(style);
+            }
+        }
+        let (width, height) = size.ok_or(SvgError::NoSize)?;
+        Ok(Self {
+            width,
+            height,
+            shapes,
+        })
+    }
+
+    /// Draws the image onto `pixmap`, with its own coordinates mapped by `transform`.
+    pub fn render(&self, pixmap: &mut Pixmap, transform: Transform) {
+        let paint = |color: Color| {
+            let mut paint = Paint::default();
+            paint.set_color(color);
+            paint.anti_alias = true;
+            paint
+        };
+        for shape in &self.shapes {
+            let transform = transform.pre_concat(shape.transform);
+            if let Some((color, rule)) = shape.fill {
+                pixmap.fill_path(&shape.path, &paint(color), rule, transform, None);
+            }
+            if let Some((color, stroke)) = &shape.stroke {
+                pixmap.stroke_path(&shape.path, &paint(*color), stroke, transform, None);
+            }
+        }
+    }
+}
+
+/// The rules in the `<style>` elements, which apply wherever in the document they are.
+fn style_sheet(text: &str) -> Result<Vec<Rule>, SvgError> {
+    let mut reader = Reader::from_str(text);
+    let mut css = String::new();
+    let mut in_style = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"style" => in_style = true,
+            Event::End(e) if e.local_name().as_ref() == b"style" => in_style = false,
+            Event::Text(t) if in_style => {
+                css.push_str(&t.decode().map_err(quick_xml::Error::from)?);
+            }
+            Event::CData(t) if in_style => {
+                css.push_str(&t.decode().map_err(quick_xml::Error::from)?);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let mut rules = rules(&css);
+    // Later rules win over earlier ones of the same specificity.
+    rules.sort_by_key(Rule::specificity);
+    Ok(rules)
+}
+
+/// The rules in a style sheet. At-rules like `@media` are skipped, along with their blocks.
+fn rules(css: &str) -> Vec<Rule> {
+    let mut css = css.to_string();
+    while let Some(start) = css.find("/*") {
+        let end = css[start..]
+            .find("*/")
+            .map_or(css.len(), |end| start + end + 2);
+        css.replace_range(start..end, " ");
+    }
+    let mut rules = Vec::new();
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find('{') {
+        // Statements like `@import` end at a semicolon instead of a block.
+        let prelude = rest[..open].rsplit(';').next().unwrap_or_default().trim();
+        // The end of the block, counting nested ones.
+        let mut depth = 0;
+        let close = rest[open..]
+            .char_indices()
+            .find(|&(_, c)| {
+                depth += match c {
+                    '{' => 1,
+                    '}' => -1,
+                    _ => 0,
+                };
+                depth == 0
+            })
+            .map_or(rest.len(), |(i, _)| open + i);
+        let block = &rest[open + 1..close];
+        rest = rest.get(close + 1..).unwrap_or_default();
+        if prelude.starts_with('@') {
+            continue;
+        }
+        let declarations = declarations(block);
+        rules.extend(
+            prelude
+                .split(',')
+                .filter_map(|selector| rule(selector.trim(), &declarations)),
+        );
+    }
+    rules
+}
+
+/// The rule applying `declarations` to what `selector` matches, or `None` if the selector
+/// isn't a simple one.
+fn rule(selector: &str, declarations: &[(String, String)]) -> Option<Rule> {
+    let name = |s: &str| {
+        (!s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .then(|| s.to_string())
+    };
+    let (tag, class, id) = if let Some(id) = selector.strip_prefix('#') {
+        (None, None, Some(name(id)?))
+    } else {
+        let (tag, class) = match selector.split_once('.') {
+            Some((tag, class)) => (tag, Some(name(class)?)),
+            None => (selector, None),
+        };
+        let tag = match tag {
+            "*" => None,
+            "" if class.is_some() => None,
+            tag => Some(name(tag)?),
+        };
+        (tag, class, None)
+    };
+    Some(Rule {
+        tag,
+        class,
+        id,
+        declarations: declarations.to_vec(),
+    })
+}
+
+/// The declarations in a `style` attribute or a rule's block.
+fn declarations(block: &str) -> Vec<(String, String)> {
+    block
+        .split(';')
+        .filter_map(|decl| {
+            let (key, value) = decl.split_once(':')?;
+            let value = value.trim().trim_end_matches("!important").trim_end();
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The element's attributes, followed by the declarations of the rules that match it, then
+/// those from its `style` attribute, so later ones win.
+fn attributes(e: &BytesStart, rules: &[Rule]) -> Vec<(String, String)> {
+    let mut attrs: Vec<(String, String)> = e
+        .attributes()
+        .flatten()
+        .filter_map(|a| {
+            let key = String::from_utf8(a.key.local_name().as_ref().to_vec()).ok()?;
+            Some((key, a.unescape_value().ok()?.into_owned()))
+        })
+        .collect();
+    let matched: Vec<(String, String)> = rules
+        .iter()
+        .filter(|rule| rule.matches(e, &attrs))
+        .flat_map(|rule| rule.declarations.iter().cloned())
+        .collect();
+    let inline: Vec<(String, String)> = attrs
+        .iter()
+        .filter(|(key, _)| key == "style")
+        .flat_map(|(_, style)| declarations(style))
+        .collect();
+    attrs.extend(matched);
+    attrs.extend(inline);
+    attrs
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .rev()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// A number or length in user units, ignoring any unit after it.
+fn length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let end = value
+        .char_indices()
+        .find(|&(i, c)| {
+            !(c.is_ascii_digit()
+                || c == '.'
+                || ((c == '-' || c == '+') && (i == 0 || value[..i].ends_with(['e', 'E'])))
+                || ((c == 'e' || c == 'E') && i > 0))
+        })
+        .map_or(value.len(), |(i, _)| i);
+    value[..end].parse().ok()
+}
+
+fn number(attrs: &[(String, String)], key: &str) -> Option<f32> {
+    attr(attrs, key).and_then(length)
+}
+
+/// The numbers in a list separated by commas or whitespace.
+fn numbers(value: &str) -> Vec<f32> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .filter_map(length)
+        .collect()
+}
+
+/// A colour, or `None` for anything else, including `none`.
+fn color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    let rgb = |r: u8, g: u8, b: u8| Some(Color::from_rgba8(r, g, b, 255));
+    if let Some(hex) = value.strip_prefix('#') {
+        let digit = |i: usize| u8::from_str_radix(hex.get(i..=i)?, 16).ok();
+        let pair = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return match hex.len() {
+            3 => rgb(digit(0)? * 17, digit(1)? * 17, digit(2)? * 17),
+            6 => rgb(pair(0)?, pair(2)?, pair(4)?),
+            _ => None,
+        };
+    }
+    if let Some(args) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let channels: Vec<u8> = args
+            .split(',')
+            .filter_map(|c| {
+                let c = c.trim();
+                let v = match c.strip_suffix('%') {
+                    Some(percent) => percent.parse::<f32>().ok()? * 2.55,
+                    None => c.parse::<f32>().ok()?,
+                };
+                Some(v.round().clamp(0.0, 255.0) as u8)
+            })
+            .collect();
+        return match channels[..] {
+            [r, g, b] => rgb(r, g, b),
+            _ => None,
+        };
+    }
+    match value.to_ascii_lowercase().as_str() {
+        // There is no text colour to inherit.
+        "black" | "currentcolor" => rgb(0, 0, 0),
+        "white" => rgb(255, 255, 255),
+        "red" => rgb(255, 0, 0),
+        "green" => rgb(0, 128, 0),
+        "lime" => rgb(0, 255, 0),
+        "blue" => rgb(0, 0, 255),
+        "yellow" => rgb(255, 255, 0),
+        "orange" => rgb(255, 165, 0),
+        "purple" => rgb(128, 0, 128),
+        "gray" | "grey" => rgb(128, 128, 128),
+        _ => None,
+    }
+}
+
+/// The colour `value` paints with, keeping `inherited` if it isn't one SVG stickers support.
+fn paint_color(value: &str, inherited: Option<Color>) -> Option<Color> {
+    match value.trim() {
+        "none" | "transparent" => None,
+        value => color(value).or(inherited),
+    }
+}
+
+fn opacity(value: &str) -> Option<f32> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok().map(|p| p / 100.0),
+        None => value.parse().ok(),
+    }
+    .map(|o| o.clamp(0.0, 1.0))
+}
+
+/// `transform` attribute functions, applied in order.
+fn transform(value: &str) -> Transform {
+    let mut result = Transform::identity();
+    for function in value.split_inclusive(')') {
+        let Some((name, args)) = function.split_once('(') else {
+            continue;
+        };
+        let args = numbers(args.trim_end_matches(')'));
+        let t = match (
+            name.trim_matches(|c: char| c == ',' || c.is_whitespace()),
+            &args[..],
+        ) {
+            ("matrix", &[sx, ky, kx, sy, tx, ty]) => Transform::from_row(sx, ky, kx, sy, tx, ty),
+            ("translate", &[x]) => Transform::from_translate(x, 0.0),
+            ("translate", &[x, y]) => Transform::from_translate(x, y),
+            ("scale", &[s]) => Transform::from_scale(s, s),
+            ("scale", &[x, y]) => Transform::from_scale(x, y),
+            ("rotate", &[a]) => Transform::from_rotate(a),
+            ("rotate", &[a, x, y]) => Transform::from_rotate_at(a, x, y),
+            ("skewX", &[a]) => Transform::from_skew(a.to_radians().tan(), 0.0),
+            ("skewY", &[a]) => Transform::from_skew(0.0, a.to_radians().tan()),
+            _ => continue,
+        };
+        result = result.pre_concat(t);
+    }
+    result
+}
+
+/// `parent` with the element's own presentation attributes applied.
+fn apply_style(parent: Style, attrs: &[(String, String)]) -> Style {
+    let mut style = parent;
+    for (key, value) in attrs {
+        match key.as_str() {
+            "fill" => style.fill = paint_color(value, style.fill),
+            "stroke" => style.stroke = paint_color(value, style.stroke),
+            "fill-rule" => {
+                style.fill_rule = match value.trim() {
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => FillRule::Winding,
+                };
+            }
+            "stroke-width" => style.stroke_width = length(value).unwrap_or(style.stroke_width),
+            "stroke-linecap" => {
+                style.line_cap = match value.trim() {
+                    "round" => LineCap::Round,
+                    "square" => LineCap::Square,
+                    _ => LineCap::Butt,
+                };
+            }
+            "stroke-linejoin" => {
+                style.line_join = match value.trim() {
+                    "round" => LineJoin::Round,
+                    "bevel" => LineJoin::Bevel,
+                    _ => LineJoin::Miter,
+                };
+            }
+            "opacity" => style.opacity *= opacity(value).unwrap_or(1.0),
+            "fill-opacity" => style.fill_opacity = opacity(value).unwrap_or(1.0),
+            "stroke-opacity" => style.stroke_opacity = opacity(value).unwrap_or(1.0),
+            "transform" => style.transform = style.transform.pre_concat(transform(value)),
+            _ => {}
+        }
+    }
+    style
+}
+
+fn shape(path: Path, style: &Style) -> Shape {
+    let with_alpha = |mut color: Color, alpha: f32| {
+        color.apply_opacity(alpha * style.opacity);
+        color
+    };
+    Shape {
+        path,
+        fill: style
+            .fill
+            .map(|c| (with_alpha(c, style.fill_opacity), style.fill_rule)),
+        stroke: style.stroke.filter(|_| style.stroke_width > 0.0).map(|c| {
+            let stroke = Stroke {
+                width: style.stroke_width,
+                line_cap: style.line_cap,
+                line_join: style.line_join,
+                ..Stroke::default()
+            };
+            (with_alpha(c, style.stroke_opacity), stroke)
+        }),
+        transform: style.transform,
+    }
+}
+
+/// The size of the root `<svg>` element, falling back to its view box, and the view box.
+fn root_size(attrs: &[(String, String)]) -> Option<(f32, f32, Option<[f32; 4]>)> {
+    let view_box = attr(attrs, "viewBox")
+        .map(numbers)
+        .and_then(|v| <[f32; 4]>::try_from(v).ok())
+        .filter(|[_, _, w, h]| *w > 0.0 && *h > 0.0);
+    // Percentages are relative to a viewport stickers don't have.
+    let dimension = |key: &str| {
+        number(attrs, key).filter(|_| attr(attrs, key).is_some_and(|v| !v.contains('%')))
+    };
+    let (width, height) = match (dimension("width"), dimension("height"), view_box) {
+        (Some(w), Some(h), _) => (w, h),
+        (Some(w), None, Some([_, _, vw, vh])) => (w, w * vh / vw),
+        (None, Some(h), Some([_, _, vw, vh])) => (h * vw / vh, h),
+        (None, None, Some([_, _, vw, vh])) => (vw, vh),
+        _ => return None,
+    };
+    (width > 0.0 && height > 0.0).then_some((width, height, view_box))
+}
+
+/// The outline of a basic shape or `<path>`.
+fn shape_path(name: &[u8], attrs: &[(String, String)]) -> Option<Path> {
+    let n = |key: &str| number(attrs, key).unwrap_or(0.0);
+    let mut pb = PathBuilder::new();
+    match name {
+        b"path" => return path_data(attr(attrs, "d")?),
+        b"rect" => {
+            let rect = tiny_skia::Rect::from_xywh(n("x"), n("y"), n("width"), n("height"))?;
+            let radii = match (number(attrs, "rx"), number(attrs, "ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(radius), None) | (None, Some(radius)) => (radius, radius),
+                (None, None) => (0.0, 0.0),
+            };
+            return rounded_rect(rect, radii);
+        }
+        b"circle" => {
+            let r = n("r");
+            pb.push_oval(tiny_skia::Rect::from_xywh(
+                n("cx") - r,
+                n("cy") - r,
+                r * 2.0,
+                r * 2.0,
+            )?);
+        }
+        b"ellipse" => {
+            let (rx, ry) = (n("rx"), n("ry"));
+            pb.push_oval(tiny_skia::Rect::from_xywh(
+                n("cx") - rx,
+                n("cy") - ry,
+                rx * 2.0,
+                ry * 2.0,
+            )?);
+        }
+        b"line" => {
+            pb.move_to(n("x1"), n("y1"));
+            pb.line_to(n("x2"), n("y2"));
+        }
+        b"polyline" | b"polygon" => {
+            let points = numbers(attr(attrs, "points")?);
+            for (i, p) in points.chunks_exact(2).enumerate() {
+                if i == 0 {
+                    pb.move_to(p[0], p[1]);
+                } else {
+                    pb.line_to(p[0], p[1]);
+                }
+            }
+            if name == b"polygon" {
+                pb.close();
+            }
+        }
+        _ => return None,
+    }
+    pb.finish()
+}
+
+fn rounded_rect(rect: tiny_skia::Rect, (rx, ry): (f32, f32)) -> Option<Path> {
+    let (rx, ry) = (
+        rx.clamp(0.0, rect.width() / 2.0),
+        ry.clamp(0.0, rect.height() / 2.0),
+    );
+    if rx == 0.0 || ry == 0.0 {
+        return Some(PathBuilder::from_rect(rect));
+    }
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    let (left, top, right, bottom) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+    let mut pb = PathBuilder::new();
+    pb.move_to(left + rx, top);
+    pb.line_to(right - rx, top);
+    pb.cubic_to(right - rx + kx, top, right, top + ry - ky, right, top + ry);
+    pb.line_to(right, bottom - ry);
+    pb.cubic_to(
+        right,
+        bottom - ry + ky,
+        right - rx + kx,
+        bottom,
+        right - rx,
+        bottom,
+    );
+    pb.line_to(left + rx, bottom);
+    pb.cubic_to(
+        left + rx - kx,
+        bottom,
+        left,
+        bottom - ry + ky,
+        left,
+        bottom - ry,
+    );
+    pb.line_to(left, top + ry);
+    pb.cubic_to(left, top + ry - ky, left + rx - kx, top, left + rx, top);
+    pb.close();
+    pb.finish()
+}
+
+/// Reads the numbers and flags of path data.
+struct PathData<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl PathData<'_> {
+    fn skip_separators(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace() || *b == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let b = *self.bytes.get(self.pos)?;
+        b.is_ascii_alphabetic().then(|| {
+            self.pos += 1;
+            b
+        })
+    }
+
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+        self.bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.'))
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut seen_dot = false;
+        let mut prev = None;
+        while let Some(&b) = self.bytes.get(self.pos) {
+            let ok = match b {
+                b'0'..=b'9' => true,
+                b'.' if !seen_dot => {
+                    seen_dot = true;
+                    true
+                }
+                b'-' | b'+' => self.pos == start || matches!(prev, Some(b'e' | b'E')),
+                b'e' | b'E' => self.pos > start,
+                _ => false,
+            };
+            if !ok {
+                break;
+            }
+            prev = Some(b);
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// An arc flag, which may be written without a separator before the next number.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let b = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        match b {
+            b'0' => Some(false),
+            b'1' => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// Where path data has got to.
+#[derive(Default)]
+struct Pen {
+    current: (f32, f32),
+    /// Where the current subpath started, for closing it.
+    start: (f32, f32),
+    /// The command and second control point of the last curve, for smooth curves.
+    last_control: Option<(u8, (f32, f32))>,
+}
+
+impl Pen {
+    /// Follows `cmd` with its numbers from `data`, or returns `None` if they are malformed.
+    fn follow(&mut self, cmd: u8, data: &mut PathData, pb: &mut PathBuilder) -> Option<()> {
+        let current = self.current;
+        let base = if cmd.is_ascii_lowercase() {
+            current
+        } else {
+            (0.0, 0.0)
+        };
+        let point = |data: &mut PathData| -> Option<(f32, f32)> {
+            let (x, y) = (data.number()?, data.number()?);
+            Some((base.0 + x, base.1 + y))
+        };
+        let reflected = |kinds: &[u8]| match self.last_control {
+            Some((kind, c)) if kinds.contains(&kind) => {
+                (2.0 * current.0 - c.0, 2.0 * current.1 - c.1)
+            }
+            _ => current,
+        };
+        let (end, control) = match cmd.to_ascii_uppercase() {
+            b'M' => {
+                let p = point(data)?;
+                pb.move_to(p.0, p.1);
+                self.start = p;
+                (p, None)
+            }
+            b'L' => (point(data)?, None),
+            b'H' => ((base.0 + data.number()?, current.1), None),
+            b'V' => ((current.0, base.1 + data.number()?), None),
+            b'C' => {
+                let (c1, c2, p) = (point(data)?, point(data)?, point(data)?);
+                pb.cubic_to(c1.0, c1.1, c2.0, c2.1, p.0, p.1);
+                (p, Some((b'C', c2)))
+            }
+            b'S' => {
+                let c1 = reflected(b"CS");
+                let (c2, p) = (point(data)?, point(data)?);
+                pb.cubic_to(c1.0, c1.1, c2.0, c2.1, p.0, p.1);
+                (p, Some((b'S', c2)))
+            }
+            b'Q' => {
+                let (c, p) = (point(data)?, point(data)?);
+                pb.quad_to(c.0, c.1, p.0, p.1);
+                (p, Some((b'Q', c)))
+            }
+            b'T' => {
+                let c = reflected(b"QT");
+                let p = point(data)?;
+                pb.quad_to(c.0, c.1, p.0, p.1);
+                (p, Some((b'T', c)))
+            }
+            b'A' => {
+                let radii = (data.number()?, data.number()?);
+                let angle = data.number()?;
+                let flags = (data.flag()?, data.flag()?);
+                let p = point(data)?;
+                arc(pb, current, radii, angle, flags, p);
+                (p, None)
+            }
+            b'Z' => {
+                pb.close();
+                (self.start, None)
+            }
+            _ => return None,
+        };
+        if matches!(cmd.to_ascii_uppercase(), b'L' | b'H' | b'V') {
+            pb.line_to(end.0, end.1);
+        }
+        self.current = end;
+        self.last_control = control;
+        Some(())
+    }
+}
+
+/// Parses path data, stopping at the first error like browsers do.
+fn path_data(d: &str) -> Option<Path> {
+    let mut data = PathData {
+        bytes: d.as_bytes(),
+        pos: 0,
+    };
+    let mut pb = PathBuilder::new();
+    let mut pen = Pen::default();
+    let mut command = None;
+    loop {
+        let cmd = match data.command() {
+            Some(c) => c,
+            None if data.at_number() => match command {
+                // Coordinates after a move are implicit lines.
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                Some(c) => c,
+                None => break,
+            },
+            None => break,
+        };
+        command = Some(cmd);
+        if pen.follow(cmd, &mut data, &mut pb).is_none() {
+            break;
+        }
+    }
+    pb.finish()
+}
+
+/// Adds the elliptical arc from `from` to `to` as cubic curves, following the SVG spec's
+/// conversion to centre parameterization.
+fn arc(
+    pb: &mut PathBuilder,
+    from: (f32, f32),
+    (rx, ry): (f32, f32),
+    angle: f32,
+    (large, sweep): (bool, bool),
+    to: (f32, f32),
+) {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 || from == to {
+        pb.line_to(to.0, to.1);
+        return;
+    }
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    // Radii too small to reach are scaled up.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut scale = (num / den).max(0.0).sqrt();
+    if large == sweep {
+        scale = -scale;
+    }
+    let (cx1, cy1) = (scale * rx * y1 / ry, -scale * ry * x1 / rx);
+    let centre = (
+        cos * cx1 - sin * cy1 + f32::midpoint(from.0, to.0),
+        sin * cx1 + cos * cy1 + f32::midpoint(from.1, to.1),
+    );
+    let vector_angle = |ux: f32, uy: f32| uy.atan2(ux);
+    let start = vector_angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = vector_angle((-x1 - cx1) / rx, (-y1 - cy1) / ry) - start;
+    if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    } else if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    }
+    // At most a quarter turn per curve.
+    let segments = (delta.abs() / (PI / 2.0)).ceil().max(1.0);
+    let step = delta / segments;
+    let handle = 4.0 / 3.0 * (step / 4.0).tan();
+    let point = |t: f32| {
+        let (s, c) = t.sin_cos();
+        let (x, y) = (rx * c, ry * s);
+        (centre.0 + cos * x - sin * y, centre.1 + sin * x + cos * y)
+    };
+    let derivative = |t: f32| {
+        let (s, c) = t.sin_cos();
+        let (x, y) = (-rx * s, ry * c);
+        (cos * x - sin * y, sin * x + cos * y)
+    };
+    for i in 0..segments as u32 {
+        let (t1, t2) = (start + step * i as f32, start + step * (i + 1) as f32);
+        let (p1, p2) = (point(t1), point(t2));
+        let (d1, d2) = (derivative(t1), derivative(t2));
+        pb.cubic_to(
+            p1.0 + d1.0 * handle,
+            p1.1 + d1.1 * handle,
+            p2.0 - d2.0 * handle,
+            p2.1 - d2.1 * handle,
+            p2.0,
+            p2.1,
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use std::{ops::Range, sync::OnceLock};
+
+use ab_glyph::{Font, FontArc, GlyphId, OutlineCurve};
+use eframe::egui::{self, Color32, FontFamily, Pos2, Rect, Vec2};
+use tiny_skia::{Path, PathBuilder, PathSegment};
+
+/// Stroke width that fakes a bold weight, relative to the font size, as the built-in
+/// fonts only come in one weight.
+const BOLD_WEIGHT: f32 = 0.05;
+/// Stroke width of the outline, relative to the font size. Half of it lies outside the glyphs.
+const OUTLINE_WIDTH: f32 = 0.2;
+/// How far the top of an italic glyph leans right, per pixel of height, matching egui.
+const ITALIC_SLANT: f32 = 0.25;
+/// Space around the text inside its background box, relative to the font size.
+const BOX_PADDING: f32 = 0.3;
+const BOX_ROUNDING: f32 = 0.2;
+/// Line segments per curve when flattening glyph outlines.
+const CURVE_STEPS: u8 = 6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextFont {
+    #[default]
+    Sans,
+    Mono,
+}
+
+impl TextFont {
+    pub const ALL: [Self; 2] = [Self::Sans, Self::Mono];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sans => "Sans",
+            Self::Mono => "Monospace",
+        }
+    }
+
+    pub fn family(self) -> FontFamily {
+        match self {
+            Self::Sans => FontFamily::Proportional,
+            Self::Mono => FontFamily::Monospace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    pub const ALL: [Self; 3] = [Self::Left, Self::Center, Self::Right];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Left => "Align left",
+            Self::Center => "Center",
+            Self::Right => "Align right",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Left => "⬅",
+            Self::Center => "↔",
+            Self::Right => "➡",
+        }
+    }
+
+    /// How much of the spare width goes to the left of a line.
+    fn factor(self) -> f32 {
+        match self {
+            Self::Left => 0.0,
+            Self::Center => 0.5,
+            Self::Right => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextStyle {
+    pub font: TextFont,
+    pub bold: bool,
+    pub italic: bool,
+    /// Alignment of the lines of multiline text with each other.
+    pub align: TextAlign,
+    /// Fill of a rounded box behind the text.
+    pub background: Option<Color32>,
+    /// Halo around the glyphs that keeps text readable on busy backgrounds.
+    pub outline: Option<Color32>,
+}
+
+impl TextStyle {
+    /// Width of the stroke drawn over the glyphs in the text colour, if bold.
+    pub fn bold_width(&self, size: f32) -> Option<f32> {
+        self.bold.then_some(size * BOLD_WEIGHT)
+    }
+
+    pub fn outline_width(size: f32) -> f32 {
+        size * OUTLINE_WIDTH
+    }
+
+    pub fn box_rounding(size: f32) -> f32 {
+        size * BOX_ROUNDING
+    }
+
+    /// Everything the text paints, given the bounds of its glyphs: the background box if
+    /// there is one, otherwise the glyphs and any strokes around them.
+    pub fn outer_rect(&self, text_rect: Rect, size: f32) -> Rect {
+        if self.background.is_some() {
+            return text_rect.expand(size * BOX_PADDING);
+        }
+        let outline = self.outline.map_or(0.0, |_| Self::outline_width(size));
+        let bold = self.bold_width(size).unwrap_or(0.0);
+        text_rect.expand(outline.max(bold) / 2.0)
+    }
+}
+
+struct Face {
+    font: FontArc,
+    /// egui's per-font size tweak, e.g. to shrink emoji.
+    scale: f32,
+}
+
+/// Fallback chains of faces mirroring egui's default font families. Layout happens in
+/// image pixels, independent of the UI scale, so exports match the canvas.
+pub struct Fonts {
+    sans: Vec<Face>,
+    mono: Vec<Face>,
+}
+
+struct PlacedGlyph {
+    face: usize,
+    id: GlyphId,
+    /// Baseline origin relative to the top-left of the text.
+    origin: Pos2,
+    px_per_unit: f32,
+    /// Bottom of the glyph's bounds for italic text, which egui slants from.
+    slant_base: Option<f32>,
+}
+
+struct Line {
+    /// Where the line is in the laid out text.
+    range: Range<usize>,
+    /// Top-left relative to the top-left of the text.
+    start: Pos2,
+}
+
+pub struct TextLayout {
+    glyphs: Vec<PlacedGlyph>,
+    lines: Vec<Line>,
+    size: Vec2,
+    font_size: f32,
+    wrap_width: f32,
+    style: TextStyle,
+}
+
+impl Fonts {
+    pub fn get() -> &'static Self {
+        static FONTS: OnceLock<Fonts> = OnceLock::new();
+        FONTS.get_or_init(|| {
+            let defs = egui::FontDefinitions::default();
+            let faces = |family: FontFamily| {
+                defs.families[&family]
+                    .iter()
+                    .filter_map(|name| {
+                        let data = defs.font_data.get(name)?;
+                        let font = FontArc::try_from_vec(data.font.to_vec()).ok()?;
+                        Some(Face {
+                            font,
+                            scale: data.tweak.scale,
+                        })
+                    })
+                    .collect()
+            };
+            Self {
+                sans: faces(FontFamily::Proportional),
+                mono: faces(FontFamily::Monospace),
+            }
+        })
+    }
+
+    fn faces(&self, font: TextFont) -> &[Face] {
+        match font {
+            TextFont::Sans => &self.sans,
+            TextFont::Mono => &self.mono,
+        }
+    }
+
+    /// Lays out `text` at `size` (in the same units as the result) the way egui does for
+    /// each of its lines, stacking the lines and aligning them as `style` says.
+    pub fn layout(&self, text: &str, size: f32, style: &TextStyle) -> TextLayout {
+        self.layout_wrapped(text, size, style, f32::INFINITY)
+    }
+
+    /// Like [`Self::layout`], but also breaks lines at spaces to keep them within `width`.
+    pub fn layout_wrapped(
+        &self,
+        text: &str,
+        size: f32,
+        style: &TextStyle,
+        width: f32,
+    ) -> TextLayout {
+        let mut layout = TextLayout {
+            glyphs: Vec::new(),
+            lines: Vec::new(),
+            size: Vec2::ZERO,
+            font_size: size,
+            wrap_width: width,
+            style: *style,
+        };
+        let faces = self.faces(style.font);
+        let Some(primary) = faces.first() else {
+            return layout;
+        };
+        let unit = size / primary.font.units_per_em().unwrap_or(1.0);
+        let ascent = primary.font.ascent_unscaled() * unit;
+        let line_height = (primary.font.ascent_unscaled() - primary.font.descent_unscaled()
+            + primary.font.line_gap_unscaled())
+            * unit;
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        for paragraph in text.split('\n') {
+            ranges.extend(
+                wrap(faces, paragraph, size, width)
+                    .into_iter()
+                    .map(|r| offset + r.start..offset + r.end),
+            );
+            offset += paragraph.len() + 1;
+        }
+        // Each line's first glyph and width, before alignment.
+        let mut placed = Vec::with_capacity(ranges.len());
+        for (row, range) in ranges.iter().enumerate() {
+            let top = row as f32 * line_height;
+            let (glyphs, line_width) = place_line(
+                faces,
+                &text[range.clone()],
+                size,
+                style.italic,
+                top + ascent,
+            );
+            placed.push((layout.glyphs.len(), top, line_width));
+            layout.glyphs.extend(glyphs);
+        }
+        let width = placed.iter().map(|&(_, _, w)| w).fold(0.0, f32::max);
+        let mut ends = placed.iter().skip(1).map(|&(first, _, _)| first);
+        for (&(first, top, line_width), range) in placed.iter().zip(ranges) {
+            let shift = (width - line_width) * style.align.factor();
+            let end = ends.next().unwrap_or(layout.glyphs.len());
+            for g in &mut layout.glyphs[first..end] {
+                g.origin.x += shift;
+            }
+            layout.lines.push(Line {
+                range,
+                start: Pos2::new(shift, top),
+            });
+        }
+        layout.size = Vec2::new(width, placed.len() as f32 * line_height);
+        layout
+    }
+}
+
+/// Places the glyphs of a single line with their baseline at `baseline`. Returns them with
+/// the line's width.
+fn place_line(
+    faces: &[Face],
+    line: &str,
+    size: f32,
+    italic: bool,
+    baseline: f32,
+) -> (Vec<PlacedGlyph>, f32) {
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    let mut last: Option<(usize, GlyphId)> = None;
+    for c in line.chars() {
+        let face_idx = faces
+            .iter()
+            .position(|f| f.font.glyph_id(c).0 != 0)
+            .unwrap_or(0);
+        let face = &faces[face_idx];
+        let px_per_unit = size * face.scale / face.font.units_per_em().unwrap_or(1.0);
+        let id = face.font.glyph_id(c);
+        if let Some((last_face, last_id)) = last
+            && last_face == face_idx
+        {
+            x += face.font.kern_unscaled(last_id, id) * px_per_unit;
+        }
+        let slant_base = italic.then(|| {
+            let bottom = face.font.outline(id).map_or(0.0, |o| o.bounds.min.y);
+            baseline - bottom * px_per_unit
+        });
+        glyphs.push(PlacedGlyph {
+            face: face_idx,
+            id,
+            origin: Pos2::new(x, baseline),
+            px_per_unit,
+            slant_base,
+        });
+        x += face.font.h_advance_unscaled(id) * px_per_unit;
+        last = Some((face_idx, id));
+    }
+    (glyphs, x)
+}
+
+/// Byte ranges of the lines `paragraph` breaks into to stay within `width`, leaving out the
+/// spaces it breaks at. Words wider than `width` get a line of their own.
+fn wrap(faces: &[Face], paragraph: &str, size: f32, width: f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let (mut start, mut last_break) = (0, 0);
+    let breaks = paragraph.match_indices(' ').map(|(i, _)| i);
+    for end in breaks.chain([paragraph.len()]) {
+        if width.is_finite()
+            && last_break > start
+            && place_line(faces, &paragraph[start..end], size, false, 0.0).1 > width
+        {
+            lines.push(start..last_break);
+            start = last_break + 1;
+        }
+        last_break = end;
+    }
+    lines.push(start..paragraph.len());
+    lines
+}
+
+impl TextLayout {
+    /// Bounds of the glyphs' advances and line boxes with the text's top-left at `pos`.
+    pub fn rect(&self, pos: Pos2) -> Rect {
+        Rect::from_min_size(pos, self.size)
+    }
+
+    /// Everything the text paints with its top-left at `pos`; see [`TextStyle::outer_rect`].
+    pub fn outer_rect(&self, pos: Pos2) -> Rect {
+        self.style.outer_rect(self.rect(pos), self.font_size)
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    pub fn wrap_width(&self) -> f32 {
+        self.wrap_width
+    }
+
+    pub fn style(&self) -> &TextStyle {
+        &self.style
+    }
+
+    /// The text of each line of `text`, which must be the text that was laid out, with its
+    /// top-left relative to the top-left of the text.
+    pub fn lines<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (&'a str, Pos2)> + 'a {
+        self.lines
+            .iter()
+            .map(|line| (text.get(line.range.clone()).unwrap_or_default(), line.start))
+    }
+
+    /// Builds the glyph outlines as a fillable path with the text's top-left at `pos`.
+    pub fn path(&self, fonts: &Fonts, pos: Pos2) -> Option<Path> {
+        let faces = fonts.faces(self.style.font);
+        let mut pb = PathBuilder::new();
+        for g in &self.glyphs {
+            let Some(outline) = faces[g.face].font.outline(g.id) else {
+                continue;
+            };
+            let map = |p: ab_glyph::Point| {
+                let y = g.origin.y - p.y * g.px_per_unit;
+                let slant = g.slant_base.map_or(0.0, |base| (base - y) * ITALIC_SLANT);
+                (pos.x + g.origin.x + p.x * g.px_per_unit + slant, pos.y + y)
+            };
+            let mut last_end = None;
+            for curve in &outline.curves {
+                let (start, end) = match curve {
+                    OutlineCurve::Line(a, b)
+                    | OutlineCurve::Quad(a, _, b)
+                    | OutlineCurve::Cubic(a, _, _, b) => (*a, *b),
+                };
+                if last_end != Some(start) {
+                    if last_end.is_some() {
+                        pb.close();
+                    }
+                    let (x, y) = map(start);
+                    pb.move_to(x, y);
+                }
+                match curve {
+                    OutlineCurve::Line(_, b) => {
+                        let (x, y) = map(*b);
+                        pb.line_to(x, y);
+                    }
+                    OutlineCurve::Quad(_, c, b) => {
+                        let ((cx, cy), (x, y)) = (map(*c), map(*b));
+                        pb.quad_to(cx, cy, x, y);
+                    }
+                    OutlineCurve::Cubic(_, c1, c2, b) => {
+                        let ((c1x, c1y), (c2x, c2y), (x, y)) = (map(*c1), map(*c2), map(*b));
+                        pb.cubic_to(c1x, c1y, c2x, c2y, x, y);
+                    }
+                }
+                last_end = Some(end);
+            }
+            if last_end.is_some() {
+                pb.close();
+            }
+        }
+        pb.finish()
+    }
+}
+
+/// Flattens `path` into closed polylines, for stroking glyph outlines with egui.
+pub fn contours(path: &Path) -> Vec<Vec<Pos2>> {
+    let mut contours: Vec<Vec<Pos2>> = Vec::new();
+    let mut last = Pos2::ZERO;
+    for segment in path.segments() {
+        let points: Vec<Pos2> = match segment {
+            PathSegment::MoveTo(p) => {
+                last = Pos2::new(p.x, p.y);
+                contours.push(vec![last]);
+                continue;
+            }
+            PathSegment::LineTo(p) => vec![Pos2::new(p.x, p.y)],
+            PathSegment::QuadTo(c, p) => (1..=CURVE_STEPS)
+                .map(|i| {
+                    let t = f32::from(i) / f32::from(CURVE_STEPS);
+                    let (c, p) = (Pos2::new(c.x, c.y), Pos2::new(p.x, p.y));
+                    let mt = 1.0 - t;
+                    (last.to_vec2() * mt * mt + c.to_vec2() * 2.0 * mt * t + p.to_vec2() * t * t)
+                        .to_pos2()
+                })
+                .collect(),
+            PathSegment::CubicTo(c1, c2, p) => (1..=CURVE_STEPS)
+                .map(|i| {
+                    let t = f32::from(i) / f32::from(CURVE_STEPS);
+                    let (c1, c2, p) = (
+                        Pos2::new(c1.x, c1.y),
+                        Pos2::new(c2.x, c2.y),
+                        Pos2::new(p.x, p.y),
+                    );
+                    let mt = 1.0 - t;
+                    (last.to_vec2() * mt * mt * mt
+                        + c1.to_vec2() * 3.0 * mt * mt * t
+                        + c2.to_vec2() * 3.0 * mt * t * t
+                        + p.to_vec2() * t * t * t)
+                        .to_pos2()
+                })
+                .collect(),
+            PathSegment::Close => continue,
+        };
+        if let (Some(contour), Some(&end)) = (contours.last_mut(), points.last()) {
+            contour.extend(points);
+            last = end;
+        }
+    }
+    contours
+}
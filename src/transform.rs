@@ -0,0 +1,187 @@
+use eframe::egui::{Color32, Pos2, Rect, Vec2};
+use xcap::image::{
+    imageops::{self, FilterType},
+    Rgba, RgbaImage,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    #[default]
+    Lanczos,
+}
+
+impl Filter {
+    pub const ALL: [Self; 4] = [Self::Nearest, Self::Bilinear, Self::Bicubic, Self::Lanczos];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nearest => "Nearest",
+            Self::Bilinear => "Bilinear",
+            Self::Bicubic => "Bicubic",
+            Self::Lanczos => "Lanczos",
+        }
+    }
+
+    /// Parses a filter name, ignoring case.
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s))
+    }
+
+    fn image_filter(self) -> FilterType {
+        match self {
+            Self::Nearest => FilterType::Nearest,
+            Self::Bilinear => FilterType::Triangle,
+            Self::Bicubic => FilterType::CatmullRom,
+            Self::Lanczos => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// How to pick the size of a resized image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeSpec {
+    Percent(f32),
+    /// Shrink to fit within the given limits, keeping the aspect ratio. Never enlarges.
+    Fit {
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+    },
+}
+
+impl Default for ResizeSpec {
+    fn default() -> Self {
+        Self::Percent(50.0)
+    }
+}
+
+impl ResizeSpec {
+    /// The scale factor that resizes an image of `size` according to the spec.
+    pub fn factor(self, size: Vec2) -> f32 {
+        match self {
+            Self::Percent(p) => p / 100.0,
+            Self::Fit {
+                max_width,
+                max_height,
+            } => {
+                let limit = |max: Option<u32>, len: f32| max.map_or(1.0, |m| m as f32 / len);
+                limit(max_width, size.x)
+                    .min(limit(max_height, size.y))
+                    .min(1.0)
+            }
+        }
+    }
+}
+
+/// A whole-image operation. Points map in continuous pixel coordinates, so they can be
+/// applied to annotations and crop rectangles as well as to the pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Resize {
+        factor: f32,
+        filter: Filter,
+    },
+    /// Clockwise.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    /// Keeps only `rect`, which must lie within the image.
+    Crop {
+        rect: Rect,
+    },
+    /// Adds margins filled with `fill` around the image.
+    Expand {
+        left: u32,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        fill: Color32,
+    },
+}
+
+impl Transform {
+    pub fn output_size(self, size: Vec2) -> Vec2 {
+        match self {
+            Self::Resize { factor, .. } => (size * factor).round().max(Vec2::splat(1.0)),
+            Self::Rotate90 | Self::Rotate270 => Vec2::new(size.y, size.x),
+            Self::Rotate180 | Self::FlipHorizontal | Self::FlipVertical => size,
+            Self::Crop { rect } => rect.size(),
+            Self::Expand {
+                left,
+                top,
+                right,
+                bottom,
+                ..
+            } => size + Vec2::new((left + right) as f32, (top + bottom) as f32),
+        }
+    }
+
+    /// Where `p` of an image of `size` ends up.
+    pub fn map(self, p: Pos2, size: Vec2) -> Pos2 {
+        match self {
+            Self::Resize { .. } => (p.to_vec2() * self.output_size(size) / size).to_pos2(),
+            Self::Rotate90 => Pos2::new(size.y - p.y, p.x),
+            Self::Rotate180 => Pos2::new(size.x - p.x, size.y - p.y),
+            Self::Rotate270 => Pos2::new(p.y, size.x - p.x),
+            Self::FlipHorizontal => Pos2::new(size.x - p.x, p.y),
+            Self::FlipVertical => Pos2::new(p.x, size.y - p.y),
+            Self::Crop { rect } => p - rect.min.to_vec2(),
+            Self::Expand { left, top, .. } => p + Vec2::new(left as f32, top as f32),
+        }
+    }
+
+    pub fn map_rect(self, rect: Rect, size: Vec2) -> Rect {
+        Rect::from_two_pos(self.map(rect.min, size), self.map(rect.max, size))
+    }
+
+    /// How lengths such as stroke widths scale.
+    pub fn scale(self, size: Vec2) -> f32 {
+        match self {
+            Self::Resize { .. } => {
+                let s = self.output_size(size) / size;
+                (s.x * s.y).sqrt()
+            }
+            _ => 1.0,
+        }
+    }
+
+    pub fn apply(self, img: &RgbaImage) -> RgbaImage {
+        match self {
+            Self::Resize { filter, .. } => {
+                let size = self.output_size(Vec2::new(img.width() as f32, img.height() as f32));
+                imageops::resize(img, size.x as u32, size.y as u32, filter.image_filter())
+            }
+            Self::Rotate90 => imageops::rotate90(img),
+            Self::Rotate180 => imageops::rotate180(img),
+            Self::Rotate270 => imageops::rotate270(img),
+            Self::FlipHorizontal => imageops::flip_horizontal(img),
+            Self::FlipVertical => imageops::flip_vertical(img),
+            Self::Crop { rect } => imageops::crop_imm(
+                img,
+                rect.min.x as u32,
+                rect.min.y as u32,
+                rect.width() as u32,
+                rect.height() as u32,
+            )
+            .to_image(),
+            Self::Expand {
+                left, top, fill, ..
+            } => {
+                let size = self.output_size(Vec2::new(img.width() as f32, img.height() as f32));
+                let mut out = RgbaImage::from_pixel(
+                    size.x as u32,
+                    size.y as u32,
+                    Rgba(fill.to_srgba_unmultiplied()),
+                );
+                imageops::replace(&mut out, img, i64::from(left), i64::from(top));
+                out
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use eframe::egui::{Pos2, Rect, Vec2};
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zoom {
+    /// Fit the whole image into the canvas, never enlarging it.
+    Fit,
+    /// Physical screen pixels per image pixel, so `1.0` is a true 1:1 view.
+    Fixed(f32),
+}
+
+/// How the image is placed on the canvas.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub zoom: Zoom,
+    /// Offset of the image centre from the canvas centre, in UI points.
+    pan: Vec2,
+    canvas: Rect,
+    image_size: Vec2,
+    pixels_per_point: f32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            zoom: Zoom::Fit,
+            pan: Vec2::ZERO,
+            canvas: Rect::NOTHING,
+            image_size: Vec2::ZERO,
+            pixels_per_point: 1.0,
+        }
+    }
+}
+
+impl View {
+    /// Updates the canvas the image is shown in; call once per frame before anything else.
+    pub fn layout(&mut self, canvas: Rect, image_size: Vec2, pixels_per_point: f32) {
+        self.canvas = canvas;
+        self.image_size = image_size;
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// UI points per image pixel.
+    pub fn scale(&self) -> f32 {
+        match self.zoom {
+            Zoom::Fit => (self.canvas.width() / self.image_size.x)
+                .min(self.canvas.height() / self.image_size.y)
+                .min(1.0),
+            Zoom::Fixed(z) => z / self.pixels_per_point,
+        }
+    }
+
+    /// The effective zoom, as shown to the user.
+    pub fn zoom_factor(&self) -> f32 {
+        self.scale() * self.pixels_per_point
+    }
+
+    /// Where the image lands on the canvas; feed this to `ui_to_image`/`image_to_ui`.
+    pub fn image_rect(&self) -> Rect {
+        let pan = if self.zoom == Zoom::Fit {
+            Vec2::ZERO
+        } else {
+            self.pan
+        };
+        Rect::from_center_size(self.canvas.center() + pan, self.image_size * self.scale())
+    }
+
+    pub fn set_zoom(&mut self, zoom: Zoom) {
+        self.zoom = zoom;
+        self.pan = Vec2::ZERO;
+    }
+
+    /// Zooms by `factor`, keeping the image point under `anchor` in place.
+    pub fn zoom_at(&mut self, factor: f32, anchor: Pos2) {
+        let (rect, old) = (self.image_rect(), self.scale());
+        let zoom = (self.zoom_factor() * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let new = zoom / self.pixels_per_point;
+        let new_min = anchor - (anchor - rect.min) / old * new;
+        self.pan = new_min + self.image_size * new / 2.0 - self.canvas.center();
+        self.zoom = Zoom::Fixed(zoom);
+    }
+
+    /// Zooms by `factor` around the centre of the canvas.
+    pub fn zoom_by(&mut self, factor: f32) {
+        self.zoom_at(factor, self.canvas.center());
+    }
+
+    pub fn pan_by(&mut self, delta: Vec2) {
+        if self.zoom == Zoom::Fit {
+            self.zoom = Zoom::Fixed(self.zoom_factor());
+        }
+        self.pan += delta;
+    }
+}